@@ -8,8 +8,15 @@ import Chip from "@mui/material/Chip";
 import Typography from "@mui/material/Typography";
 import {
   useConfigQuery,
+  useCreateGooglePicker,
+  useDeleteInboxItem,
+  useGoogleDeviceFlowStatus,
+  useInboxItems,
   useSetImmichCredentials,
   useSetImmichFilters,
+  useSourceBlacklist,
+  useStartGoogleDeviceFlow,
+  useUnblacklistAsset,
 } from "../../hooks/http";
 import TextField from "@mui/material/TextField";
 import IconButton from "@mui/material/IconButton";
@@ -52,6 +59,91 @@ export default function SourcesPage() {
                     </p>
                   </div>
                 )}
+                {src.kind === "webdav" && (
+                  <div className="text-sm opacity-80">
+                    <p>
+                      URL: <code>{src.webdav?.url || "(none)"}</code>
+                    </p>
+                    <p>
+                      Order: <code>{src.webdav?.order || "random"}</code>
+                    </p>
+                  </div>
+                )}
+                {src.kind === "s3" && (
+                  <div className="text-sm opacity-80">
+                    <p>
+                      Bucket:{" "}
+                      <code>
+                        {src.s3?.bucket
+                          ? `${src.s3.endpoint}/${src.s3.bucket}/${src.s3.prefix || ""}`
+                          : "(none)"}
+                      </code>
+                    </p>
+                    <p>
+                      Order: <code>{src.s3?.order || "random"}</code>
+                    </p>
+                  </div>
+                )}
+                {src.kind === "google_photos" && (
+                  <GooglePhotosInlineOnboard
+                    apiBase={API_BASE}
+                    sourceId={id}
+                    pickerSessionId={src.google_photos?.picker_session_id}
+                  />
+                )}
+                {src.kind === "photoprism" && (
+                  <div className="text-sm opacity-80">
+                    <p>
+                      URL: <code>{src.photoprism?.base_url || "(none)"}</code>
+                    </p>
+                    <p>
+                      Query: <code>{src.photoprism?.query || "(all photos)"}</code>
+                    </p>
+                    <p>
+                      Order: <code>{src.photoprism?.order || "random"}</code>
+                    </p>
+                  </div>
+                )}
+                {src.kind === "http" && (
+                  <div className="text-sm opacity-80">
+                    <p>
+                      URL:{" "}
+                      <code>{src.http?.list_url || src.http?.image_url || "(none)"}</code>
+                    </p>
+                    <p>
+                      Order:{" "}
+                      <code>{src.http?.image_url ? "fetched on every selection" : src.http?.order || "random"}</code>
+                    </p>
+                  </div>
+                )}
+                {src.kind === "command" && (
+                  <div className="text-sm opacity-80">
+                    <p>
+                      Command:{" "}
+                      <code>
+                        {src.command?.program
+                          ? [src.command.program, ...(src.command.args || [])].join(" ")
+                          : "(none)"}
+                      </code>
+                    </p>
+                    <p>
+                      Timeout: <code>{src.command?.timeout_secs ?? 30}s</code>
+                    </p>
+                  </div>
+                )}
+                {src.kind === "inbox" && <InboxInline apiBase={API_BASE} sourceId={id} />}
+                {src.kind === "composite" && (
+                  <div className="text-sm opacity-80">
+                    <p>
+                      {src.composite?.operation || "union"} of{" "}
+                      <code>{(src.composite?.sources || []).join(", ") || "(none)"}</code>
+                    </p>
+                    <p>
+                      Order: <code>{src.composite?.order || "random"}</code>
+                    </p>
+                  </div>
+                )}
+                {src.kind !== "composite" && <BlacklistInline apiBase={API_BASE} sourceId={id} />}
               </Stack>
             </Paper>
           </Grid>
@@ -61,6 +153,145 @@ export default function SourcesPage() {
   );
 }
 
+function InboxInline({ apiBase, sourceId }: { apiBase: string; sourceId: string }) {
+  const items = useInboxItems(apiBase, sourceId);
+  const del = useDeleteInboxItem(apiBase, sourceId);
+
+  return (
+    <div className="flex flex-col gap-2 text-sm">
+      {items.isError && <p className="text-red-600">{items.error.message}</p>}
+      {items.data?.length === 0 && <p className="opacity-80">No uploads yet.</p>}
+      {items.data?.map((item) => (
+        <div key={item.id} className="flex gap-2 items-center">
+          <img
+            src={`${apiBase}/sources/${sourceId}/assets/${encodeURIComponent(item.id)}/thumbnail`}
+            alt=""
+            className="w-16 h-16 object-cover"
+          />
+          <span className="flex-1">
+            {item.uploader || "Unknown"}, {new Date(item.uploaded_at).toLocaleString()}
+          </span>
+          <Button
+            variant="outlined"
+            size="small"
+            color="error"
+            onClick={() => del.mutate(item.id)}
+            disabled={del.isPending}
+          >
+            Delete
+          </Button>
+        </div>
+      ))}
+    </div>
+  );
+}
+
+function BlacklistInline({ apiBase, sourceId }: { apiBase: string; sourceId: string }) {
+  const items = useSourceBlacklist(apiBase, sourceId);
+  const unblacklist = useUnblacklistAsset(apiBase, sourceId);
+  if (!items.data?.length && !items.isError) return null;
+
+  return (
+    <div className="flex flex-col gap-2 text-sm">
+      <Typography variant="subtitle2">Blacklisted</Typography>
+      {items.isError && <p className="text-red-600">{items.error.message}</p>}
+      {items.data?.map((item) => (
+        <div key={item.asset_id} className="flex gap-2 items-center">
+          <img
+            src={`${apiBase}/sources/${sourceId}/blacklist/${encodeURIComponent(item.asset_id)}/thumbnail`}
+            alt=""
+            className="w-16 h-16 object-cover"
+          />
+          <span className="flex-1">
+            {item.date_taken ? new Date(item.date_taken).toLocaleDateString() : "Unknown date"}
+            {item.from_config && " (config)"}
+          </span>
+          <Button
+            variant="outlined"
+            size="small"
+            onClick={() => unblacklist.mutate(item.asset_id)}
+            disabled={unblacklist.isPending}
+          >
+            Restore
+          </Button>
+        </div>
+      ))}
+    </div>
+  );
+}
+
+function GooglePhotosInlineOnboard({
+  apiBase,
+  sourceId,
+  pickerSessionId,
+}: {
+  apiBase: string;
+  sourceId: string;
+  pickerSessionId?: string;
+}) {
+  const start = useStartGoogleDeviceFlow(apiBase, sourceId);
+  const status = useGoogleDeviceFlowStatus(apiBase, sourceId, start.isSuccess);
+  const picker = useCreateGooglePicker(apiBase, sourceId);
+  const flow = status.data;
+
+  return (
+    <div className="flex flex-col gap-2 text-sm">
+      <p className="opacity-80">
+        {pickerSessionId ? (
+          <>
+            Picker session: <code>{pickerSessionId}</code>
+          </>
+        ) : (
+          "No photos picked yet."
+        )}
+      </p>
+      <div className="flex gap-2 items-center">
+        <Button
+          variant="outlined"
+          size="small"
+          onClick={() => start.mutate()}
+          disabled={start.isPending}
+        >
+          Sign in with Google
+        </Button>
+        <Button
+          variant="outlined"
+          size="small"
+          onClick={() => picker.mutate()}
+          disabled={picker.isPending}
+        >
+          Pick photos
+        </Button>
+      </div>
+      {start.isError && <p className="text-red-600">{start.error.message}</p>}
+      {flow?.state === "pending" && (
+        <p>
+          Enter <code>{flow.user_code}</code> at{" "}
+          <a href={flow.verification_url} target="_blank" rel="noreferrer">
+            {flow.verification_url}
+          </a>
+        </p>
+      )}
+      {flow?.state === "authorized" && <p>Signed in.</p>}
+      {flow?.state === "failed" && (
+        <p className="text-red-600">{flow.error}</p>
+      )}
+      {picker.isError && <p className="text-red-600">{picker.error.message}</p>}
+      {picker.data && (
+        <p>
+          Choose photos in{" "}
+          <a href={picker.data.picker_uri} target="_blank" rel="noreferrer">
+            Google Photos
+          </a>
+          {picker.data.expire_time &&
+            ` before ${new Date(picker.data.expire_time).toLocaleString()}`}
+          . They are copied to the frame server once you are done.
+        </p>
+      )}
+    </div>
+  );
+}
+
 function ImmichInlineOnboard({
   apiBase,
   sourceId,
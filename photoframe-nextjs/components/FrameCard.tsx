@@ -15,6 +15,7 @@ import {
   FrameConfig,
   useFlipFrameMutation,
   useBlacklistAssetMutation,
+  useAssetActionMutation,
   TimestampPosition,
   TimestampColor,
   TimestampStrokeColor,
@@ -92,6 +93,7 @@ export function FrameCard({ frame, apiBase }: Props) {
   const flipMutation = useFlipFrameMutation(apiBase, frame.id);
   const clearMutation = useClearFrameMutation(apiBase, frame.id);
   const blacklistMutation = useBlacklistAssetMutation(apiBase, frame.id);
+  const assetActionMutation = useAssetActionMutation(apiBase, frame.id);
 
   const patchMutation = usePatchFrameMutation(apiBase, frame.id, {
     onSuccess: (payload) => {
@@ -565,6 +567,15 @@ export function FrameCard({ frame, apiBase }: Props) {
                 )
               }
               blacklistPending={blacklistMutation.isPending}
+              onAssetAction={(payload) =>
+                assetActionMutation.mutate(payload, {
+                  onSuccess: () => {
+                    if (payload.action === "archive")
+                      requestImageRef.current(uiState.showIntermediate);
+                  },
+                })
+              }
+              assetActionPending={assetActionMutation.isPending}
               currentAssetId={metadataQuery.data?.asset_id ?? undefined}
               currentSourceId={metadataQuery.data?.source_id ?? undefined}
               palette={paletteQuery.data}
@@ -16,6 +16,10 @@ import BuildCircleIcon from "@mui/icons-material/BuildCircle";
 import ScreenRotationIcon from "@mui/icons-material/ScreenRotation";
 import PlayArrowIcon from "@mui/icons-material/PlayArrow";
 import BlockIcon from "@mui/icons-material/Block";
+import FavoriteIcon from "@mui/icons-material/Favorite";
+import PhotoAlbumIcon from "@mui/icons-material/PhotoAlbum";
+import ArchiveIcon from "@mui/icons-material/Archive";
+import type { AssetActionPayload } from "../../hooks/http";
 
 interface PaletteInfo {
   palette: { input: string; hex: string; rgb: [number, number, number] }[];
@@ -37,6 +41,8 @@ interface Props {
   triggerPending: boolean;
   onBlacklist: (assetId: string, sourceId: string) => void;
   blacklistPending: boolean;
+  onAssetAction: (payload: AssetActionPayload) => void;
+  assetActionPending: boolean;
   currentAssetId?: string;
   currentSourceId?: string;
   palette: PaletteInfo | undefined;
@@ -59,6 +65,8 @@ export function MiscSettingsAccordion(props: Props) {
     triggerPending,
     onBlacklist,
     blacklistPending,
+    onAssetAction,
+    assetActionPending,
     currentAssetId,
     currentSourceId,
     palette,
@@ -191,6 +199,30 @@ export function MiscSettingsAccordion(props: Props) {
             >
               Blacklist current image
             </Button>
+            <Button
+              size="small"
+              startIcon={<FavoriteIcon fontSize="small" />}
+              onClick={() => onAssetAction({ action: "favorite" })}
+              disabled={assetActionPending || !currentAssetId}
+            >
+              Favorite in Immich
+            </Button>
+            <Button
+              size="small"
+              startIcon={<PhotoAlbumIcon fontSize="small" />}
+              onClick={() => onAssetAction({ action: "add_to_album" })}
+              disabled={assetActionPending || !currentAssetId}
+            >
+              Add to &quot;Frame favorites&quot;
+            </Button>
+            <Button
+              size="small"
+              startIcon={<ArchiveIcon fontSize="small" />}
+              onClick={() => onAssetAction({ action: "archive" })}
+              disabled={assetActionPending || !currentAssetId}
+            >
+              Archive in Immich
+            </Button>
           </Stack>
 
           {palette ? (
@@ -59,7 +59,7 @@ export interface ConfigResponse {
   sources: Record<string, SourceConfig>;
 }
 
-export type SourceKind = "filesystem" | "immich" | string;
+export type SourceKind = "filesystem" | "immich" | "webdav" | "s3" | "photoprism" | "google_photos" | "http" | "command" | "inbox" | "composite" | string;
 export type OrderKind = "random" | "sequential";
 export interface FilesystemSourceCfg {
   glob?: string;
@@ -73,10 +73,62 @@ export interface ImmichSourceCfg {
   // Can be either a single filter object or an array of filter objects
   filters?: Record<string, unknown> | Record<string, unknown>[];
 }
+export interface WebdavSourceCfg {
+  url?: string;
+  username?: string;
+  depth?: number;
+  include?: string[];
+  order?: OrderKind;
+}
+export interface S3SourceCfg {
+  endpoint?: string;
+  bucket?: string;
+  prefix?: string;
+  region?: string;
+  include?: string[];
+  order?: OrderKind;
+}
+export interface PhotoprismSourceCfg {
+  base_url?: string;
+  query?: string;
+  order?: OrderKind;
+}
+export interface GooglePhotosSourceCfg {
+  client_id?: string;
+  picker_session_id?: string;
+  order?: OrderKind;
+}
+export interface HttpSourceCfg {
+  list_url?: string;
+  image_url?: string;
+  order?: OrderKind;
+}
+export interface CommandSourceCfg {
+  program?: string;
+  args?: string[];
+  timeout_secs?: number;
+}
+export interface InboxSourceCfg {
+  dir?: string;
+  order?: OrderKind;
+}
+export interface CompositeSourceCfg {
+  sources?: string[];
+  operation?: "union" | "intersection" | "difference";
+  order?: OrderKind;
+}
 export interface SourceConfig {
   kind: SourceKind;
   filesystem?: FilesystemSourceCfg;
   immich?: ImmichSourceCfg;
+  webdav?: WebdavSourceCfg;
+  s3?: S3SourceCfg;
+  photoprism?: PhotoprismSourceCfg;
+  google_photos?: GooglePhotosSourceCfg;
+  http?: HttpSourceCfg;
+  command?: CommandSourceCfg;
+  inbox?: InboxSourceCfg;
+  composite?: CompositeSourceCfg;
 }
 
 // Immich onboarding
@@ -96,6 +148,135 @@ export function useSetImmichCredentials(apiBase: string, sourceId: string) {
   });
 }
 
+// Google Photos onboarding
+export interface GoogleDeviceFlow {
+  user_code: string;
+  verification_url: string;
+  expires_in: number;
+}
+export type GoogleDeviceFlowStatus =
+  | { state: "pending"; user_code: string; verification_url: string }
+  | { state: "authorized" }
+  | { state: "failed"; error: string };
+
+export function useStartGoogleDeviceFlow(apiBase: string, sourceId: string) {
+  return useMutation<GoogleDeviceFlow, Error, void>({
+    mutationFn: async () => {
+      const res = await fetch(`${apiBase}/sources/${sourceId}/google/device`, {
+        method: "POST",
+      });
+      if (!res.ok) throw new Error("Starting Google sign-in failed");
+      return res.json();
+    },
+  });
+}
+
+export function useGoogleDeviceFlowStatus(
+  apiBase: string,
+  sourceId: string,
+  enabled: boolean,
+) {
+  return useQuery<GoogleDeviceFlowStatus>({
+    queryKey: ["google-device", apiBase, sourceId],
+    queryFn: async () => {
+      const res = await fetch(`${apiBase}/sources/${sourceId}/google/device`);
+      if (!res.ok) throw new Error("Google sign-in status failed");
+      return res.json();
+    },
+    enabled,
+    refetchInterval: (q) =>
+      q.state.data?.state === "pending" || !q.state.data ? 3000 : false,
+  });
+}
+
+export function useCreateGooglePicker(apiBase: string, sourceId: string) {
+  const qc = useQueryClient();
+  return useMutation<
+    { id: string; picker_uri: string; expire_time?: string },
+    Error,
+    void
+  >({
+    mutationFn: async () => {
+      const res = await fetch(`${apiBase}/sources/${sourceId}/google/picker`, {
+        method: "POST",
+      });
+      if (!res.ok) throw new Error("Creating Google Photos picker failed");
+      return res.json();
+    },
+    onSuccess: () => qc.invalidateQueries({ queryKey: ["config", apiBase] }),
+  });
+}
+
+export interface InboxItem {
+  id: string;
+  file: string;
+  uploader?: string | null;
+  uploaded_at: string;
+  orientation: "landscape" | "portrait";
+  date_taken?: string | null;
+}
+
+export function useInboxItems(apiBase: string, sourceId: string) {
+  return useQuery<InboxItem[]>({
+    queryKey: ["inbox", apiBase, sourceId],
+    queryFn: async () => {
+      const res = await fetch(`${apiBase}/sources/${sourceId}/inbox`);
+      if (!res.ok) throw new Error("Listing inbox failed");
+      return res.json();
+    },
+  });
+}
+
+export function useDeleteInboxItem(apiBase: string, sourceId: string) {
+  const qc = useQueryClient();
+  return useMutation<void, Error, string>({
+    mutationFn: async (itemId) => {
+      const res = await fetch(
+        `${apiBase}/sources/${sourceId}/inbox/${encodeURIComponent(itemId)}`,
+        { method: "DELETE" },
+      );
+      if (!res.ok && res.status !== 404) throw new Error("Deleting inbox item failed");
+    },
+    onSuccess: () => qc.invalidateQueries({ queryKey: ["inbox", apiBase, sourceId] }),
+  });
+}
+
+export interface BlacklistItem {
+  asset_id: string;
+  date_taken?: string | null;
+  added_at?: string | null;
+  // Listed in the source's `immich.blacklist` setting.
+  from_config: boolean;
+}
+
+export function useSourceBlacklist(apiBase: string, sourceId: string) {
+  return useQuery<BlacklistItem[]>({
+    queryKey: ["blacklist", apiBase, sourceId],
+    queryFn: async () => {
+      const res = await fetch(`${apiBase}/sources/${sourceId}/blacklist`);
+      if (!res.ok) throw new Error("Listing blacklist failed");
+      return res.json();
+    },
+  });
+}
+
+export function useUnblacklistAsset(apiBase: string, sourceId: string) {
+  const qc = useQueryClient();
+  return useMutation<void, Error, string>({
+    mutationFn: async (assetId) => {
+      const res = await fetch(
+        `${apiBase}/sources/${sourceId}/blacklist/${encodeURIComponent(assetId)}`,
+        { method: "DELETE" },
+      );
+      if (!res.ok && res.status !== 404) throw new Error("Un-blacklisting failed");
+    },
+    onSuccess: () => {
+      qc.invalidateQueries({ queryKey: ["blacklist", apiBase, sourceId] });
+      qc.invalidateQueries({ queryKey: ["config", apiBase] });
+    },
+  });
+}
+
 export function useBlacklistAssetMutation(apiBase: string, frameId: string) {
   const qc = useQueryClient();
   return useMutation<void, Error, { asset_id: string; source_id: string }>({
@@ -118,6 +299,36 @@ export function useBlacklistAssetMutation(apiBase: string, frameId: string) {
       // Invalidate config and metadata queries to reflect changes and trigger new image
       qc.invalidateQueries({ queryKey: ["config", apiBase] });
       qc.invalidateQueries({ queryKey: ["metadata", apiBase, frameId] });
+      qc.invalidateQueries({ queryKey: ["blacklist", apiBase] });
+    },
+  });
+}
+
+export type AssetActionPayload =
+  | { action: "favorite" }
+  | { action: "archive" }
+  | { action: "add_to_album"; album?: string };
+
+// Write-back action on the frame's current asset in its source library (Immich).
+export function useAssetActionMutation(apiBase: string, frameId: string) {
+  const qc = useQueryClient();
+  return useMutation<void, Error, AssetActionPayload>({
+    mutationFn: async (payload) => {
+      const res = await fetch(
+        `${apiBase}/frames/${frameId}/current/actions`,
+        {
+          method: "POST",
+          headers: { "Content-Type": "application/json" },
+          body: JSON.stringify(payload),
+        },
+      );
+      if (!res.ok) throw new Error("Asset action failed");
+    },
+    onSuccess: (_, payload) => {
+      // Archiving advances the frame to a new image
+      if (payload.action === "archive") {
+        qc.invalidateQueries({ queryKey: ["metadata", apiBase, frameId] });
+      }
     },
   });
 }
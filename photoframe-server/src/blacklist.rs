@@ -0,0 +1,219 @@
+//! Per-source blacklists persisted as `<source_id>_blacklist.json`, with a thumbnail of
+//! each entry in `<source_id>_blacklist/` so hidden images can still be reviewed. Entries
+//! match by asset id (the file path for filesystem sources) or by the SHA-256 of the
+//! image as the source delivered it, which catches copies under another path. Hashes are
+//! only recorded for sources serving original bytes, since a rendition sized for one
+//! frame hashes differently from the one fetched for another. Files are read once and
+//! kept in memory; `add` and `remove` update both.
+
+use crate::sources::SourceData;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlacklistEntry {
+    pub asset_id: String,
+    /// Hex SHA-256 of the image bytes, if the source serves original bytes and the image
+    /// could be fetched when blacklisting.
+    pub content_hash: Option<String>,
+    pub date_taken: Option<DateTime<Utc>>,
+    pub added_at: DateTime<Utc>,
+}
+
+/// Asset ids and content hashes to keep off the frames.
+#[derive(Debug, Clone, Default)]
+pub struct Blacklist {
+    pub asset_ids: HashSet<String>,
+    pub content_hashes: HashSet<String>,
+}
+
+impl Blacklist {
+    pub fn extend(&mut self, entries: &[BlacklistEntry]) {
+        for e in entries {
+            self.asset_ids.insert(e.asset_id.clone());
+            if let Some(hash) = &e.content_hash {
+                self.content_hashes.insert(hash.clone());
+            }
+        }
+    }
+}
+
+type Cache = HashMap<String, Vec<BlacklistEntry>>;
+
+// Entries of every blacklist read so far, by source id. The lock also serializes
+// read-modify-write cycles on blacklist files within this process.
+static BLACKLISTS: OnceLock<Mutex<Cache>> = OnceLock::new();
+
+fn blacklists() -> &'static Mutex<Cache> {
+    BLACKLISTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn blacklist_path(source_id: &str) -> PathBuf {
+    PathBuf::from(format!("{source_id}_blacklist.json"))
+}
+
+fn thumbnail_path(source_id: &str, asset_id: &str) -> PathBuf {
+    // Asset ids may be paths or URLs; name thumbnails after a digest instead.
+    let name = hex(&Sha256::digest(asset_id.as_bytes()));
+    PathBuf::from(format!("{source_id}_blacklist")).join(format!("{name}.jpg"))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Hex SHA-256 of an image's bytes.
+pub fn content_hash(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+/// [`content_hash`] of an image, reading it from disk if needed.
+pub async fn data_hash(data: &SourceData) -> Result<String> {
+    match data {
+        SourceData::Path(p) => {
+            let bytes = tokio::fs::read(p)
+                .await
+                .with_context(|| format!("reading {}", p.display()))?;
+            Ok(content_hash(&bytes))
+        }
+        SourceData::Bytes(b) => Ok(content_hash(b)),
+    }
+}
+
+async fn read_entries(source_id: &str) -> Vec<BlacklistEntry> {
+    match tokio::fs::read(blacklist_path(source_id)).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            tracing::warn!(source=%source_id, error=%e, "ignoring unreadable blacklist file");
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// A source's cached entries, reading its file the first time.
+async fn cached<'a>(cache: &'a mut Cache, source_id: &str) -> &'a mut Vec<BlacklistEntry> {
+    if !cache.contains_key(source_id) {
+        let entries = read_entries(source_id).await;
+        cache.insert(source_id.to_string(), entries);
+    }
+    cache.get_mut(source_id).expect("inserted above")
+}
+
+async fn write_entries(source_id: &str, entries: &[BlacklistEntry]) -> Result<()> {
+    let path = blacklist_path(source_id);
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(entries)?)
+        .await
+        .with_context(|| format!("writing {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .with_context(|| format!("renaming {}", path.display()))?;
+    Ok(())
+}
+
+/// All entries of a source's blacklist, oldest first.
+pub async fn load(source_id: &str) -> Vec<BlacklistEntry> {
+    let mut cache = blacklists().lock().await;
+    cached(&mut cache, source_id).await.clone()
+}
+
+/// Add an entry and its thumbnail. Returns Ok(false) if the asset was already listed.
+pub async fn add(source_id: &str, entry: BlacklistEntry, thumbnail: Option<&[u8]>) -> Result<bool> {
+    let mut cache = blacklists().lock().await;
+    let entries = cached(&mut cache, source_id).await;
+    if entries.iter().any(|e| e.asset_id == entry.asset_id) {
+        return Ok(false);
+    }
+    if let Some(thumb) = thumbnail {
+        let path = thumbnail_path(source_id, &entry.asset_id);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&path, thumb)
+            .await
+            .with_context(|| format!("writing {}", path.display()))?;
+    }
+    let mut updated = entries.clone();
+    updated.push(entry);
+    write_entries(source_id, &updated).await?;
+    *entries = updated;
+    Ok(true)
+}
+
+/// Remove an asset from a source's blacklist. Returns Ok(false) if it wasn't listed.
+pub async fn remove(source_id: &str, asset_id: &str) -> Result<bool> {
+    let mut cache = blacklists().lock().await;
+    let entries = cached(&mut cache, source_id).await;
+    let mut updated = entries.clone();
+    updated.retain(|e| e.asset_id != asset_id);
+    if updated.len() == entries.len() {
+        return Ok(false);
+    }
+    write_entries(source_id, &updated).await?;
+    *entries = updated;
+    let _ = tokio::fs::remove_file(thumbnail_path(source_id, asset_id)).await;
+    Ok(true)
+}
+
+/// The JPEG thumbnail stored when the asset was blacklisted.
+pub async fn thumbnail(source_id: &str, asset_id: &str) -> Option<Vec<u8>> {
+    tokio::fs::read(thumbnail_path(source_id, asset_id))
+        .await
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn add_list_and_remove_entries() {
+        let source = format!("blacklist_test_{}", std::process::id());
+        let entry = |id: &str, hash: Option<&str>| BlacklistEntry {
+            asset_id: id.to_string(),
+            content_hash: hash.map(str::to_string),
+            date_taken: None,
+            added_at: Utc::now(),
+        };
+        assert!(
+            add(&source, entry("/photos/a.jpg", Some("aa")), Some(b"thumb"))
+                .await
+                .unwrap()
+        );
+        assert!(
+            !add(&source, entry("/photos/a.jpg", None), None)
+                .await
+                .unwrap()
+        );
+        assert!(
+            add(&source, entry("/photos/b.jpg", None), None)
+                .await
+                .unwrap()
+        );
+
+        let mut list = Blacklist::default();
+        list.extend(&load(&source).await);
+        assert_eq!(list.asset_ids.len(), 2);
+        assert!(list.content_hashes.contains("aa"));
+        assert_eq!(
+            thumbnail(&source, "/photos/a.jpg").await.as_deref(),
+            Some(&b"thumb"[..])
+        );
+
+        assert!(remove(&source, "/photos/a.jpg").await.unwrap());
+        assert!(!remove(&source, "/photos/a.jpg").await.unwrap());
+        assert!(thumbnail(&source, "/photos/a.jpg").await.is_none());
+        assert_eq!(load(&source).await.len(), 1);
+
+        // Later loads are served from memory rather than the file.
+        std::fs::remove_file(blacklist_path(&source)).unwrap();
+        assert_eq!(load(&source).await.len(), 1);
+        let _ = std::fs::remove_dir_all(format!("{source}_blacklist"));
+    }
+}
@@ -25,6 +25,8 @@ pub struct Config {
     pub env: Option<String>,
     pub server: Option<Server>,
     pub logging: Option<Logging>,
+    /// Optional embedded SMTP listener for emailing photos to frames.
+    pub smtp: Option<SmtpServer>,
     /// Optional global limits for original (base) image dimensions. Images larger than these
     /// limits will be downscaled (aspect preserved) before being cached/saved as base images.
     pub image_limits: Option<ImageLimits>,
@@ -45,6 +47,41 @@ pub struct Logging {
     pub filter: Option<String>,
 }
 
+/// Embedded SMTP listener. Mail to `<mailbox>+<secret>@<domain>` is delivered to the
+/// mailbox of that name, or shown on the frame with that id when no such mailbox is
+/// configured.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SmtpServer {
+    /// Defaults to `127.0.0.1:2525`.
+    pub bind_address: Option<String>,
+    /// Shared secret senders append to the mailbox name (`livingroom+<secret>@...`).
+    /// All mail is refused when unset. Never included in API responses.
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    /// Recipient domain to accept (e.g. `frames.local`). Any domain when unset.
+    pub domain: Option<String>,
+    /// Envelope senders allowed to deliver, as full addresses or `@domain`. Matched
+    /// case-insensitively; all mail is refused when empty.
+    #[serde(default)]
+    pub allowed_senders: Vec<String>,
+    /// Largest accepted message in bytes. Defaults to 25 MiB.
+    pub max_message_bytes: Option<usize>,
+    #[serde(default)]
+    pub mailboxes: std::collections::HashMap<String, SmtpMailbox>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SmtpMailbox {
+    /// Frame to show attachments on. Defaults to the mailbox name.
+    pub frame: Option<String>,
+    /// Save attachments into this directory instead of showing them.
+    pub save_dir: Option<String>,
+    /// Source to rescan after saving, typically a filesystem source covering `save_dir`.
+    pub refresh_source: Option<String>,
+    /// Keep a shown attachment on the frame this long (e.g. `3h`), see display overrides.
+    pub duration: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ImageLimits {
     pub max_width: Option<u32>,
@@ -90,9 +127,37 @@ pub enum UploadTransport {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum OrderKind {
+    /// Independent random pick on every selection (assets may repeat).
     #[default]
     Random,
     Sequential,
+    /// Shuffle-bag: every eligible asset is shown once before the bag is reshuffled.
+    /// Bag state is persisted per (frame, source).
+    Shuffle,
+}
+
+/// How a frame picks which of its sources to draw from next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceSelection {
+    /// Every source is equally likely to be probed first.
+    #[default]
+    Uniform,
+    /// Sources are picked according to `source_weights` (missing entries weigh 1.0).
+    Weighted,
+    /// Sources are picked proportionally to how many matching images they hold.
+    Proportional,
+}
+
+/// How an Immich source combines the `albums` and `people` it lists by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NameMatch {
+    /// Assets in any of the albums or showing any of the people.
+    #[default]
+    Any,
+    /// Assets in every album and showing every person.
+    All,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -111,6 +176,11 @@ pub struct PhotoFrame {
     pub upload_transport: Option<UploadTransport>,
     #[serde(default)]
     pub source_ids: Vec<String>,
+    /// Strategy for choosing between `source_ids`.
+    pub source_selection: Option<SourceSelection>,
+    /// Relative source weights used by `source_selection = "weighted"`.
+    #[serde(default)]
+    pub source_weights: std::collections::HashMap<String, f64>,
     pub update_cron: Option<croner::Cron>,
     pub dithering: Option<String>,
     #[serde(default)]
@@ -130,6 +200,14 @@ pub struct PhotoFrame {
     pub dummy: bool,
     #[serde(default)]
     pub paused: bool,
+    /// Don't show an asset again on this frame within this many days (0/None = no limit).
+    pub no_repeat_days: Option<u32>,
+    /// Skip images that look nearly the same as one shown on this frame within this many
+    /// days, e.g. burst shots or edited copies (0/None = off).
+    pub skip_similar_days: Option<u32>,
+    /// Largest perceptual hash distance (0-64) at which two images count as the same
+    /// scene. Defaults to 10.
+    pub similar_max_distance: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -236,7 +314,27 @@ pub enum Source {
         filesystem: Option<FilesystemSource>,
     },
     #[serde(rename = "immich")]
-    Immich { immich: Option<ImmichSource> },
+    Immich { immich: Option<Box<ImmichSource>> },
+    #[serde(rename = "webdav")]
+    Webdav { webdav: Option<WebdavSource> },
+    #[serde(rename = "s3")]
+    S3 { s3: Option<S3Source> },
+    #[serde(rename = "photoprism")]
+    Photoprism {
+        photoprism: Option<PhotoprismSource>,
+    },
+    #[serde(rename = "google_photos")]
+    GooglePhotos {
+        google_photos: Option<GooglePhotosSource>,
+    },
+    #[serde(rename = "http")]
+    Http { http: Option<HttpSource> },
+    #[serde(rename = "command")]
+    Command { command: Option<CommandSource> },
+    #[serde(rename = "inbox")]
+    Inbox { inbox: Option<InboxSource> },
+    #[serde(rename = "composite")]
+    Composite { composite: Option<CompositeSource> },
     #[serde(other)]
     Unknown,
 }
@@ -250,21 +348,256 @@ pub struct FilesystemSource {
 pub struct ImmichSource {
     pub base_url: Option<String>,
     pub api_key: Option<String>,
+    /// Key of a shared link (the `key` parameter of its URL), used instead of `api_key`
+    /// to list the link's assets read-only. Takes precedence over `api_key`. Never
+    /// included in API responses.
+    #[serde(default, skip_serializing)]
+    pub shared_key: Option<String>,
     pub order: Option<OrderKind>,
     /// Maximum number of pages to fetch per filter when listing assets.
-    /// Defaults to 1 (backwards compatible behavior). Each page defaults to size=1000 unless
-    /// overridden in filters. Listing stops earlier if the API indicates no more items.
+    /// Unlimited by default. Each page defaults to size=1000 unless overridden in filters.
+    /// Listing stops earlier if the API indicates no more items.
     pub max_pages: Option<u32>,
+    /// Seconds between incremental syncs, which only fetch assets updated since the last
+    /// sync. Defaults to 3600.
+    pub refresh_interval_secs: Option<u64>,
+    /// Seconds between full re-listings, which also catch permanently deleted assets and
+    /// assets that no longer match the filters. Defaults to 7 days.
+    pub full_sync_interval_secs: Option<u64>,
     /// Arbitrary search filters passed directly to Immich `searchAssets` endpoint body.
     /// This allows specifying albumIds, personIds, etc. Always merged with type=IMAGE.
     /// Can be either a single filter object or an array of filter objects that get combined.
     #[serde(with = "filters_serde")]
     pub filters: Option<Vec<serde_json::Value>>,
+    /// Natural-language query (e.g. "beach sunset") listed through Immich's CLIP-based
+    /// `smartSearch` endpoint instead of `searchAssets`. Filters still apply. Results are
+    /// ranked by relevance, so `max_pages` defaults to 1 for these sources.
+    pub smart_query: Option<String>,
+    /// Album names, resolved to `albumIds` at list time. Matching is case-insensitive and
+    /// each name must identify exactly one album.
+    #[serde(default)]
+    pub albums: Vec<String>,
+    /// Person names, resolved to `personIds` at list time like `albums`.
+    #[serde(default)]
+    pub people: Vec<String>,
+    /// Whether assets must match any (default) or all of `albums` and `people`.
+    pub name_match: Option<NameMatch>,
+    /// List today's Immich memories ("on this day") instead of searching, falling back
+    /// to `filters` when there are none. Re-listed at local midnight.
+    #[serde(default)]
+    pub memories: bool,
+    /// Further accounts or shared links merged into this source. Assets found in more
+    /// than one are shown once (matched by checksum).
+    #[serde(default)]
+    pub accounts: Vec<ImmichAccount>,
     /// List of blacklisted asset IDs. These assets will be filtered out.
     #[serde(default)]
     pub blacklist: Vec<String>,
 }
 
+/// WebDAV collection (e.g. a Nextcloud folder) listed via PROPFIND.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WebdavSource {
+    /// Collection URL, e.g. `https://cloud.example/remote.php/dav/files/alice/Photos/`.
+    pub url: Option<String>,
+    pub username: Option<String>,
+    /// Password, or an app password for Nextcloud. Never included in API responses.
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// Levels of sub-collections listed below `url`; 0 lists only `url` itself.
+    /// Defaults to 3.
+    pub depth: Option<u32>,
+    /// Glob patterns matched case-insensitively against paths relative to `url`, e.g.
+    /// `"2024/**/*.jpg"`. Defaults to all JPEG and PNG files.
+    #[serde(default)]
+    pub include: Vec<String>,
+    pub order: Option<OrderKind>,
+    /// Seconds between re-listings. Defaults to 3600.
+    pub refresh_interval_secs: Option<u64>,
+}
+
+/// S3-compatible bucket (AWS, MinIO, Garage, ...) listed with ListObjectsV2.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct S3Source {
+    /// Service URL, e.g. `http://minio.local:9000` or `https://s3.eu-central-1.amazonaws.com`.
+    pub endpoint: Option<String>,
+    pub bucket: Option<String>,
+    /// Only keys starting with this prefix are listed, e.g. `photos/`.
+    pub prefix: Option<String>,
+    /// Signing region. Defaults to `us-east-1`, which MinIO accepts by default.
+    pub region: Option<String>,
+    /// Leave both unset for a public bucket.
+    pub access_key_id: Option<String>,
+    /// Never included in API responses.
+    #[serde(default, skip_serializing)]
+    pub secret_access_key: Option<String>,
+    /// Address the bucket as `endpoint/bucket/key` rather than `bucket.endpoint/key`.
+    /// Defaults to true, as MinIO and most self-hosted servers expect.
+    pub path_style: Option<bool>,
+    /// Glob patterns matched case-insensitively against keys relative to `prefix`.
+    /// Defaults to all JPEG and PNG files.
+    #[serde(default)]
+    pub include: Vec<String>,
+    pub order: Option<OrderKind>,
+    /// Seconds between re-listings. Defaults to 3600.
+    pub refresh_interval_secs: Option<u64>,
+}
+
+/// PhotoPrism library listed through its photo search API.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PhotoprismSource {
+    pub base_url: Option<String>,
+    /// App password (Settings > Account > Apps and Devices), sent as a bearer token.
+    /// Never included in API responses.
+    #[serde(default, skip_serializing)]
+    pub app_password: Option<String>,
+    /// Search filter in PhotoPrism's syntax, e.g. `favorite:true label:cat` or
+    /// `album:"Summer 2023"`. Lists all photos when unset.
+    pub query: Option<String>,
+    pub order: Option<OrderKind>,
+    /// Seconds between re-listings. Defaults to 3600.
+    pub refresh_interval_secs: Option<u64>,
+}
+
+/// Google Photos Picker session, authorized through the OAuth2 device flow
+/// (`POST /api/sources/<id>/google/device`). Whole albums can't be listed: Google retired
+/// the Library API's read-all scope, and the device flow only grants the Picker scope.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GooglePhotosSource {
+    /// OAuth client of type "TVs and Limited Input devices" from the Google Cloud console.
+    pub client_id: Option<String>,
+    /// Never included in API responses.
+    #[serde(default, skip_serializing)]
+    pub client_secret: Option<String>,
+    /// Stored by the device flow. Never included in API responses.
+    #[serde(default, skip_serializing)]
+    pub refresh_token: Option<String>,
+    /// Photos Picker session whose picked items are copied and shown; created through
+    /// `POST /api/sources/<id>/google/picker`.
+    pub picker_session_id: Option<String>,
+    pub order: Option<OrderKind>,
+}
+
+/// Images exposed by an HTTP service: either a JSON listing mapped through JSON
+/// pointers, or a single image URL fetched anew on every selection.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HttpSource {
+    /// URL returning a JSON listing of images.
+    pub list_url: Option<String>,
+    /// Fixed image URL (e.g. a webcam snapshot) fetched on every selection, instead of
+    /// `list_url`.
+    pub image_url: Option<String>,
+    /// Headers sent with requests to the origin of `list_url` or `image_url`, e.g.
+    /// `{ Authorization = "Bearer ..." }`. Item URLs on other origins get none. Never
+    /// included in API responses, as they usually carry credentials.
+    #[serde(default, skip_serializing)]
+    pub headers: std::collections::HashMap<String, String>,
+    /// Where the listing's items are found and what their fields mean.
+    #[serde(default)]
+    pub fields: HttpFieldMap,
+    pub order: Option<OrderKind>,
+    /// Seconds between re-listings. Defaults to 3600.
+    pub refresh_interval_secs: Option<u64>,
+}
+
+/// JSON pointers (RFC 6901, e.g. `/data/items` or `/image/src`) into an
+/// [`HttpSource`] listing. `items` is resolved against the whole response, the others
+/// against each item.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HttpFieldMap {
+    /// Array of items; the empty pointer (default) is the response itself.
+    pub items: String,
+    pub id: String,
+    /// Image URL, absolute or relative to `list_url`.
+    pub url: String,
+    pub width: Option<String>,
+    pub height: Option<String>,
+    /// RFC 3339 string or Unix seconds.
+    pub date: Option<String>,
+}
+
+impl Default for HttpFieldMap {
+    fn default() -> Self {
+        Self {
+            items: String::new(),
+            id: "/id".into(),
+            url: "/url".into(),
+            width: Some("/width".into()),
+            height: Some("/height".into()),
+            date: Some("/date".into()),
+        }
+    }
+}
+
+/// An executable run on every selection, for integrations written as scripts. It
+/// receives the frame and desired orientation and prints an image path, a JSON object
+/// or the image bytes; see the README for the exact protocol.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CommandSource {
+    /// Executable to run (looked up in `PATH` unless it contains a slash).
+    pub program: Option<String>,
+    /// Arguments; `{frame_id}`, `{orientation}` and `{max_dimension}` are substituted.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extra environment variables.
+    #[serde(default)]
+    pub env: std::collections::HashMap<String, String>,
+    /// Working directory; defaults to the server's.
+    pub working_dir: Option<String>,
+    /// Seconds before the command is killed. Defaults to 30.
+    pub timeout_secs: Option<u64>,
+}
+
+/// Photos uploaded through `upload_frame` with `save_to` naming this source, kept in a
+/// managed directory together with who uploaded them and when.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct InboxSource {
+    /// Directory holding the uploads. Defaults to `<source_id>_inbox`.
+    pub dir: Option<String>,
+    pub order: Option<OrderKind>,
+}
+
+/// How a composite source combines its members' assets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CompositeOperation {
+    /// Assets of any member.
+    #[default]
+    Union,
+    /// Assets of the first member that every other member also has.
+    Intersection,
+    /// Assets of the first member that none of the others have.
+    Difference,
+}
+
+/// Combines other configured sources. Assets are matched across members by id or, where
+/// the member reports one, by checksum.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CompositeSource {
+    /// Ids of the member sources; the first is the base for intersection and difference.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    pub operation: Option<CompositeOperation>,
+    pub order: Option<OrderKind>,
+    /// How often members are re-listed and combined again. Defaults to 600.
+    pub refresh_interval_secs: Option<u64>,
+}
+
+/// An extra Immich login for [`ImmichSource::accounts`]. Searches (filters, names,
+/// smart query, memories) only apply to API keys; shared links list all their assets.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ImmichAccount {
+    /// Server URL; defaults to the source's `base_url`.
+    pub base_url: Option<String>,
+    /// Never included in API responses, like `shared_key`.
+    #[serde(default, skip_serializing)]
+    pub api_key: Option<String>,
+    /// Shared-link key, as for [`ImmichSource::shared_key`].
+    #[serde(default, skip_serializing)]
+    pub shared_key: Option<String>,
+}
+
 mod filters_serde {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
     use serde_json::Value;
@@ -595,6 +928,14 @@ impl ConfigManager {
         fs::write(&tmp, contents)
             .await
             .with_context(|| format!("writing tmp config {}", tmp.display()))?;
+        // The config holds API keys and refresh tokens; keep it private to the owner.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))
+                .await
+                .with_context(|| format!("restricting permissions of {}", tmp.display()))?;
+        }
         fs::rename(&tmp, &path)
             .await
             .with_context(|| format!("renaming tmp config to {}", path.display()))?;
@@ -628,6 +969,47 @@ impl ConfigManager {
         Ok(())
     }
 
+    /// Persist the refresh token obtained by a Google Photos device flow.
+    pub async fn set_google_refresh_token(
+        cfg: &SharedConfig,
+        source_id: &str,
+        refresh_token: &str,
+    ) -> Result<()> {
+        Self::set_google_photos_value(cfg, source_id, "refresh_token", refresh_token).await
+    }
+
+    /// Point a Google Photos source at a new Photos Picker session.
+    pub async fn set_google_picker_session(
+        cfg: &SharedConfig,
+        source_id: &str,
+        session_id: &str,
+    ) -> Result<()> {
+        Self::set_google_photos_value(cfg, source_id, "picker_session_id", session_id).await
+    }
+
+    async fn set_google_photos_value(
+        cfg: &SharedConfig,
+        source_id: &str,
+        key: &str,
+        val: &str,
+    ) -> Result<()> {
+        let mut guard = cfg.write().await;
+        let sources_tbl = guard.doc["sources"]
+            .as_table_mut()
+            .ok_or_else(|| anyhow::anyhow!("sources table missing"))?;
+        let src = sources_tbl
+            .get_mut(source_id)
+            .ok_or_else(|| anyhow::anyhow!("source '{}' not found", source_id))?;
+        if let Item::Table(tbl) = src {
+            let gp = tbl["google_photos"].or_insert(Item::Table(toml_edit::Table::new()));
+            if let Item::Table(gpt) = gp {
+                gpt[key] = value(val);
+                return Ok(());
+            }
+        }
+        bail!("source '{}' is not a google photos table", source_id);
+    }
+
     /// Update Immich source filters JSON object or array (replaces previous value).
     pub async fn set_immich_filters(
         cfg: &SharedConfig,
@@ -661,49 +1043,83 @@ impl ConfigManager {
         Ok(())
     }
 
-    /// Add an asset ID to an Immich source's blacklist.
+    /// Remove an asset ID from an Immich source's `blacklist` setting.
     ///
-    /// Returns Ok(true) if the asset was appended, Ok(false) if it was already present.
-    pub async fn add_immich_blacklist_item(
+    /// Returns Ok(true) if the asset was removed, Ok(false) if it wasn't listed.
+    pub async fn remove_immich_blacklist_item(
         cfg: &SharedConfig,
         source_id: &str,
         asset_id: &str,
     ) -> Result<bool> {
         let mut guard = cfg.write().await;
-        let sources_tbl = guard.doc["sources"]
-            .as_table_mut()
-            .ok_or_else(|| anyhow::anyhow!("sources table missing"))?;
-        let src = sources_tbl
-            .get_mut(source_id)
-            .ok_or_else(|| anyhow::anyhow!("source '{}' not found", source_id))?;
-        if let Item::Table(tbl) = src {
-            if tbl.get("kind").is_none() {
-                tbl["kind"] = value("immich");
-            }
-            let im = tbl["immich"].or_insert(Item::Table(toml_edit::Table::new()));
-            if let Item::Table(imt) = im {
-                let blacklist_entry =
-                    imt.entry("blacklist")
-                        .or_insert(Item::Value(
-                            toml_edit::Value::Array(toml_edit::Array::new()),
-                        ));
-                if let Item::Value(toml_edit::Value::Array(arr)) = blacklist_entry {
-                    // check for existing entry first
-                    for existing in arr.iter() {
-                        if let toml_edit::Value::String(s) = existing
-                            && s.value() == asset_id
-                        {
-                            return Ok(false);
-                        }
-                    }
-                    arr.push(toml_edit::Value::String(toml_edit::Formatted::new(
-                        asset_id.to_string(),
-                    )));
-                } else {
-                    bail!("immich.blacklist is not an array");
-                }
-            }
-        }
-        Ok(true)
+        // `get_mut` rather than indexing, which would insert missing tables.
+        let Some(arr) = guard
+            .doc
+            .get_mut("sources")
+            .and_then(|s| s.get_mut(source_id))
+            .and_then(|s| s.get_mut("immich"))
+            .and_then(|s| s.get_mut("blacklist"))
+            .and_then(|b| b.as_array_mut())
+        else {
+            return Ok(false);
+        };
+        let before = arr.len();
+        arr.retain(|v| v.as_str() != Some(asset_id));
+        Ok(arr.len() != before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_are_left_out_of_serialized_config() {
+        let doc: DocumentMut = r#"
+            [smtp]
+            secret = "smtp-secret"
+
+            [sources.nas]
+            kind = "webdav"
+
+            [sources.nas.webdav]
+            url = "https://cloud.example/dav/"
+            password = "webdav-secret"
+
+            [sources.bucket]
+            kind = "s3"
+
+            [sources.bucket.s3]
+            access_key_id = "AKID"
+            secret_access_key = "s3-secret"
+
+            [sources.prism]
+            kind = "photoprism"
+
+            [sources.prism.photoprism]
+            app_password = "prism-secret"
+
+            [sources.feed]
+            kind = "http"
+
+            [sources.feed.http]
+            list_url = "https://feed.example/list"
+            headers = { Authorization = "Bearer http-secret" }
+
+            [sources.family]
+            kind = "immich"
+
+            [sources.family.immich]
+            base_url = "http://immich.local"
+            shared_key = "share-secret"
+            filters = []
+            accounts = [{ api_key = "account-secret" }]
+        "#
+        .parse()
+        .unwrap();
+        let cfg: Config = toml_edit::de::from_document(doc).unwrap();
+        let json = serde_json::to_string(&cfg).unwrap();
+        assert!(json.contains("AKID"));
+        assert!(!json.contains("secret"), "{json}");
     }
 }
@@ -81,12 +81,24 @@ pub async fn load_and_store_base(
     }; // original full-resolution
 
     // Prefer EXIF metadata from source (e.g., Immich original asset) over thumbnail EXIF
-    if let Some(source_date) = meta.date_taken {
-        date_taken = Some(source_date);
-    }
     if let Some(source_exif) = &meta.exif_blob {
         exif_blob = Some(source_exif.clone());
     }
+    // A date known from the source's listing is patched into the EXIF only when it has
+    // none, so the other tags survive.
+    if let Some(source_date) = meta.date_taken {
+        let has_date = exif_blob
+            .as_deref()
+            .and_then(|b| extract_exif_date_taken_from_blob(b).ok().flatten())
+            .is_some();
+        if !has_date {
+            match exif_with_date(exif_blob.as_deref(), source_date) {
+                Ok(patched) => exif_blob = Some(patched),
+                Err(e) => tracing::debug!(frame=%frame_id, error=%e, "could not add date to EXIF"),
+            }
+        }
+        date_taken = Some(source_date);
+    }
 
     if let Some(orient) = orientation_tag {
         img = apply_exif_orientation(img, orient);
@@ -111,7 +123,77 @@ pub fn extract_exif_date_taken_from_blob(
     exif_bytes: &[u8],
 ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
     let exif = exif::Reader::new().read_raw(exif_bytes.to_vec())?;
+    Ok(exif_date_taken(&exif))
+}
+
+/// Build a minimal EXIF blob carrying only DateTimeOriginal (as UTC), for sources that
+/// know the capture time without having the original file's EXIF.
+pub fn exif_blob_for_date(date_taken: chrono::DateTime<chrono::Utc>) -> Result<Vec<u8>> {
+    let date = exif::Field {
+        tag: exif::Tag::DateTimeOriginal,
+        ifd_num: exif::In::PRIMARY,
+        value: exif::Value::Ascii(vec![
+            date_taken
+                .format("%Y:%m:%d %H:%M:%S")
+                .to_string()
+                .into_bytes(),
+        ]),
+    };
+    let offset = exif::Field {
+        tag: exif::Tag::OffsetTimeOriginal,
+        ifd_num: exif::In::PRIMARY,
+        value: exif::Value::Ascii(vec![b"+00:00".to_vec()]),
+    };
+    let mut writer = exif::experimental::Writer::new();
+    writer.push_field(&date);
+    writer.push_field(&offset);
+    let mut buf = std::io::Cursor::new(Vec::new());
+    writer.write(&mut buf, false)?;
+    Ok(buf.into_inner())
+}
 
+/// `existing` EXIF (if any) with DateTimeOriginal set to `date_taken` (as UTC). The other
+/// primary-image tags are kept; the embedded thumbnail is dropped.
+pub fn exif_with_date(
+    existing: Option<&[u8]>,
+    date_taken: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<u8>> {
+    let Some(existing) = existing else {
+        return exif_blob_for_date(date_taken);
+    };
+    let exif = exif::Reader::new().read_raw(existing.to_vec())?;
+    let date_fields = exif::Reader::new().read_raw(exif_blob_for_date(date_taken)?)?;
+    let replaced = [exif::Tag::DateTimeOriginal, exif::Tag::OffsetTimeOriginal];
+    let mut writer = exif::experimental::Writer::new();
+    for field in exif.fields() {
+        // Offsets into the old blob and pointers the writer recreates itself.
+        let skip = field.ifd_num != exif::In::PRIMARY
+            || replaced.contains(&field.tag)
+            || matches!(
+                field.tag,
+                exif::Tag::ExifIFDPointer
+                    | exif::Tag::GPSInfoIFDPointer
+                    | exif::Tag::InteropIFDPointer
+                    | exif::Tag::JPEGInterchangeFormat
+                    | exif::Tag::JPEGInterchangeFormatLength
+                    | exif::Tag::StripOffsets
+                    | exif::Tag::StripByteCounts
+            );
+        if !skip {
+            writer.push_field(field);
+        }
+    }
+    for field in date_fields.fields() {
+        writer.push_field(field);
+    }
+    let mut buf = std::io::Cursor::new(Vec::new());
+    writer.write(&mut buf, exif.little_endian())?;
+    Ok(buf.into_inner())
+}
+
+/// Derive the capture time from parsed EXIF (DateTimeOriginal/DateTime plus optional
+/// subseconds and offset). Times without an offset are interpreted as local time.
+pub fn exif_date_taken(exif: &exif::Exif) -> Option<chrono::DateTime<chrono::Utc>> {
     // Helpers to retrieve ASCII values and to search all IFDs if PRIMARY is missing
     let get_ascii = |tag: exif::Tag| -> Option<String> {
         exif.get_field(tag, exif::In::PRIMARY)
@@ -126,10 +208,7 @@ pub fn extract_exif_date_taken_from_blob(
 
     // Build a base timestamp string from tags
     let mut base =
-        match get_ascii(exif::Tag::DateTimeOriginal).or_else(|| get_ascii(exif::Tag::DateTime)) {
-            Some(s) => s,
-            None => return Ok(None),
-        };
+        get_ascii(exif::Tag::DateTimeOriginal).or_else(|| get_ascii(exif::Tag::DateTime))?;
 
     // Append subseconds if present
     if let Some(sub) = get_ascii(exif::Tag::SubSecTimeOriginal)
@@ -159,25 +238,25 @@ pub fn extract_exif_date_taken_from_blob(
 
     // Try parsing with several formats
     if let Ok(dt) = chrono::DateTime::parse_from_str(&base, "%Y:%m:%d %H:%M:%S%.f%:z") {
-        return Ok(Some(dt.with_timezone(&chrono::Utc)));
+        return Some(dt.with_timezone(&chrono::Utc));
     }
     if let Ok(dt) = chrono::DateTime::parse_from_str(&base, "%Y:%m:%d %H:%M:%S%:z") {
-        return Ok(Some(dt.with_timezone(&chrono::Utc)));
+        return Some(dt.with_timezone(&chrono::Utc));
     }
     if let Ok(naive) = chrono::NaiveDateTime::parse_from_str(&base, "%Y:%m:%d %H:%M:%S%.f") {
         // Interpret as local time if no offset is present, then convert to UTC for storage
         let local = chrono::Local.from_local_datetime(&naive).earliest();
-        return Ok(local.map(|ldt| ldt.with_timezone(&chrono::Utc)));
+        return local.map(|ldt| ldt.with_timezone(&chrono::Utc));
     }
     if let Ok(naive) = chrono::NaiveDateTime::parse_from_str(&base, "%Y:%m:%d %H:%M:%S") {
         let local = chrono::Local.from_local_datetime(&naive).earliest();
-        return Ok(local.map(|ldt| ldt.with_timezone(&chrono::Utc)));
+        return local.map(|ldt| ldt.with_timezone(&chrono::Utc));
     }
-    Ok(None)
+    None
 }
 
 /// Extract EXIF DateTimeOriginal/DateTime via image crate decoder.
-fn extract_exif_date_taken(bytes: &[u8]) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+pub fn extract_exif_date_taken(bytes: &[u8]) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
     use std::io::Cursor;
     // First, try to get raw EXIF via image decoder
     let exif_opt: Option<exif::Exif> = (|| {
@@ -200,68 +279,7 @@ fn extract_exif_date_taken(bytes: &[u8]) -> Result<Option<chrono::DateTime<chron
         }
     };
 
-    // Helpers to retrieve ASCII values and to search all IFDs if PRIMARY is missing
-    let get_ascii = |tag: exif::Tag| -> Option<String> {
-        exif.get_field(tag, exif::In::PRIMARY)
-            .or_else(|| exif.fields().find(|f| f.tag == tag))
-            .and_then(|f| match &f.value {
-                exif::Value::Ascii(v) if !v.is_empty() => std::str::from_utf8(&v[0])
-                    .ok()
-                    .map(|s| s.trim().to_string()),
-                _ => None,
-            })
-    };
-
-    // Build a base timestamp string from tags
-    let mut base =
-        match get_ascii(exif::Tag::DateTimeOriginal).or_else(|| get_ascii(exif::Tag::DateTime)) {
-            Some(s) => s,
-            None => return Ok(None),
-        };
-
-    // Append subseconds if present
-    if let Some(sub) = get_ascii(exif::Tag::SubSecTimeOriginal)
-        .or_else(|| get_ascii(exif::Tag::SubSecTime))
-        .filter(|s| !s.is_empty())
-    {
-        base.push('.');
-        base.push_str(sub.trim());
-    }
-    // Append normalized timezone offset if present
-    if let Some(off_raw) =
-        get_ascii(exif::Tag::OffsetTimeOriginal).or_else(|| get_ascii(exif::Tag::OffsetTime))
-    {
-        let mut off = off_raw.trim().replace(' ', "");
-        // Normalize +HHMM -> +HH:MM
-        if off.len() == 5
-            && (off.starts_with('+') || off.starts_with('-'))
-            && off.chars().skip(1).all(|c| c.is_ascii_digit())
-        {
-            off = format!("{}{}:{}", &off[0..2], &off[2..4], &off[4..5]);
-        }
-        // If already like +HH:MM, keep as-is
-        if !off.is_empty() {
-            base.push_str(off.as_str());
-        }
-    }
-
-    // Try parsing with several formats
-    if let Ok(dt) = chrono::DateTime::parse_from_str(&base, "%Y:%m:%d %H:%M:%S%.f%:z") {
-        return Ok(Some(dt.with_timezone(&chrono::Utc)));
-    }
-    if let Ok(dt) = chrono::DateTime::parse_from_str(&base, "%Y:%m:%d %H:%M:%S%:z") {
-        return Ok(Some(dt.with_timezone(&chrono::Utc)));
-    }
-    if let Ok(naive) = chrono::NaiveDateTime::parse_from_str(&base, "%Y:%m:%d %H:%M:%S%.f") {
-        // Interpret as local time if no offset is present, then convert to UTC for storage
-        let local = chrono::Local.from_local_datetime(&naive).earliest();
-        return Ok(local.map(|ldt| ldt.with_timezone(&chrono::Utc)));
-    }
-    if let Ok(naive) = chrono::NaiveDateTime::parse_from_str(&base, "%Y:%m:%d %H:%M:%S") {
-        let local = chrono::Local.from_local_datetime(&naive).earliest();
-        return Ok(local.map(|ldt| ldt.with_timezone(&chrono::Utc)));
-    }
-    Ok(None)
+    Ok(exif_date_taken(&exif))
 }
 
 /// Extract raw EXIF blob to re-embed when saving intermediates.
@@ -273,6 +291,40 @@ fn extract_exif_blob(bytes: &[u8]) -> Result<Option<Vec<u8>>> {
     Ok(decoder.exif_metadata().ok().flatten())
 }
 
+/// Read the capture time of an image file by parsing only its EXIF container.
+pub fn read_file_date_taken(path: &std::path::Path) -> Option<chrono::DateTime<chrono::Utc>> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut reader = std::io::BufReader::new(file);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+    exif_date_taken(&exif)
+}
+
+/// Render a small JPEG preview of a source image (EXIF orientation applied), fitting
+/// within `max_dim` on both axes.
+pub async fn render_thumbnail(meta: &ImageMeta, max_dim: u32) -> Result<Vec<u8>> {
+    let bytes = match &meta.data {
+        SourceData::Path(p) => fs::read(p).await?,
+        SourceData::Bytes(b) => b.clone(),
+    };
+    let img = decode_oriented(&bytes)?;
+    let thumb = DynamicImage::ImageRgb8(img.thumbnail(max_dim, max_dim).to_rgb8());
+    let mut out = Vec::new();
+    thumb.write_to(
+        &mut std::io::Cursor::new(&mut out),
+        image::ImageFormat::Jpeg,
+    )?;
+    Ok(out)
+}
+
+/// Decode an image and apply its EXIF orientation, so it comes out the way it is viewed.
+pub fn decode_oriented(bytes: &[u8]) -> Result<DynamicImage> {
+    let img = image::load_from_memory(bytes)?;
+    Ok(match extract_exif_orientation(bytes).ok().flatten() {
+        Some(orient) => apply_exif_orientation(img, orient),
+        None => img,
+    })
+}
+
 /// Apply orientation transform producing a correctly oriented image in view coordinates.
 fn apply_exif_orientation(mut img: DynamicImage, orient: Orientation) -> DynamicImage {
     img.apply_orientation(orient);
@@ -317,6 +369,18 @@ async fn store_base(
     }
 }
 
+/// Source and asset id of the image currently shown on a frame, from
+/// `<frame_id>_metadata.json`.
+pub async fn current_asset(frame_id: &str) -> Option<(String, String)> {
+    let bytes = tokio::fs::read(format!("{frame_id}_metadata.json"))
+        .await
+        .ok()?;
+    let doc: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
+    let source_id = doc.get("source_id")?.as_str()?.to_string();
+    let asset_id = doc.get("asset_id")?.as_str()?.to_string();
+    Some((source_id, asset_id))
+}
+
 /// Save metadata about the fetched image as JSON.
 async fn store_metadata(frame_id: &str, meta: &ImageMeta) {
     let path = PathBuf::from(format!("{frame_id}_metadata.json"));
@@ -897,3 +961,47 @@ async fn read_exif_from_base_png(frame_id: &str) -> Option<Vec<u8>> {
     let mut decoder = reader.into_decoder().ok()?;
     decoder.exif_metadata().ok().flatten()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_patch_keeps_other_exif_tags() {
+        let orientation = exif::Field {
+            tag: exif::Tag::Orientation,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Short(vec![6]),
+        };
+        let model = exif::Field {
+            tag: exif::Tag::Model,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Ascii(vec![b"Camera".to_vec()]),
+        };
+        let original = crate::sources::test_support::exif_blob(&[orientation, model]);
+
+        let date = chrono::DateTime::parse_from_rfc3339("2020-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let patched = exif_with_date(Some(&original), date).unwrap();
+        assert_eq!(
+            extract_exif_date_taken_from_blob(&patched).unwrap(),
+            Some(date)
+        );
+        let exif = exif::Reader::new().read_raw(patched).unwrap();
+        let field = exif
+            .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+            .unwrap();
+        assert_eq!(field.value.get_uint(0), Some(6));
+        assert!(
+            exif.get_field(exif::Tag::Model, exif::In::PRIMARY)
+                .is_some()
+        );
+
+        let fresh = exif_with_date(None, date).unwrap();
+        assert_eq!(
+            extract_exif_date_taken_from_blob(&fresh).unwrap(),
+            Some(date)
+        );
+    }
+}
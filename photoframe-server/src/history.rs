@@ -0,0 +1,203 @@
+//! Per-frame display history persisted as `<frame_id>_history.json`.
+
+use crate::sources::{ImageMeta, SourceData};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tokio::sync::Mutex;
+
+/// Oldest entries are dropped once a frame's history grows beyond this many items.
+const MAX_HISTORY_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub source_id: Option<String>,
+    pub asset_id: Option<String>,
+    /// Local file path for path-backed sources.
+    pub path: Option<String>,
+    pub shown_at: DateTime<Utc>,
+    /// Perceptual hash of the image as 16 hex digits (see [`crate::phash`]), used to
+    /// recognize near-identical shots. Missing if the image couldn't be decoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dhash: Option<String>,
+}
+
+// Serializes read-modify-write cycles on history files within this process.
+static HISTORY_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+fn history_lock() -> &'static Mutex<()> {
+    HISTORY_LOCK.get_or_init(|| Mutex::new(()))
+}
+
+fn history_path(frame_id: &str) -> PathBuf {
+    PathBuf::from(format!("{frame_id}_history.json"))
+}
+
+async fn read_entries(frame_id: &str) -> Vec<HistoryEntry> {
+    match tokio::fs::read(history_path(frame_id)).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            tracing::warn!(frame=%frame_id, error=%e, "ignoring unreadable history file");
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Load the full history for a frame, oldest first.
+pub async fn load(frame_id: &str) -> Vec<HistoryEntry> {
+    let _guard = history_lock().lock().await;
+    read_entries(frame_id).await
+}
+
+/// Append a displayed image and its perceptual hash to the frame's history.
+pub async fn record(frame_id: &str, meta: &ImageMeta, dhash: Option<u64>) -> Result<()> {
+    let entry = HistoryEntry {
+        source_id: meta.source_id.clone(),
+        asset_id: meta.id.clone(),
+        path: match &meta.data {
+            SourceData::Path(p) => Some(p.to_string_lossy().to_string()),
+            SourceData::Bytes(_) => None,
+        },
+        shown_at: Utc::now(),
+        dhash: dhash.map(|h| format!("{h:016x}")),
+    };
+    let _guard = history_lock().lock().await;
+    let mut entries = read_entries(frame_id).await;
+    push_capped(&mut entries, entry, MAX_HISTORY_ENTRIES);
+    let path = history_path(frame_id);
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(&entries)?)
+        .await
+        .with_context(|| format!("writing {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .with_context(|| format!("renaming {}", path.display()))?;
+    Ok(())
+}
+
+/// Append `entry`, dropping the oldest entries beyond `max`.
+fn push_capped(entries: &mut Vec<HistoryEntry>, entry: HistoryEntry, max: usize) {
+    entries.push(entry);
+    if entries.len() > max {
+        let excess = entries.len() - max;
+        entries.drain(..excess);
+    }
+}
+
+/// One page of `entries` (stored oldest first) in newest-first order.
+pub fn newest_first_page(
+    entries: Vec<HistoryEntry>,
+    offset: usize,
+    limit: usize,
+) -> Vec<HistoryEntry> {
+    entries.into_iter().rev().skip(offset).take(limit).collect()
+}
+
+/// Asset ids shown within the last `days` days, relative to `now`.
+pub fn recent_asset_ids(
+    entries: &[HistoryEntry],
+    days: u32,
+    now: DateTime<Utc>,
+) -> HashSet<String> {
+    let cutoff = now - Duration::days(days as i64);
+    entries
+        .iter()
+        .filter(|e| e.shown_at >= cutoff)
+        .filter_map(|e| e.asset_id.clone())
+        .collect()
+}
+
+/// Perceptual hashes of images shown within the last `days` days, relative to `now`.
+pub fn recent_dhashes(entries: &[HistoryEntry], days: u32, now: DateTime<Utc>) -> Vec<u64> {
+    let cutoff = now - Duration::days(days as i64);
+    entries
+        .iter()
+        .filter(|e| e.shown_at >= cutoff)
+        .filter_map(|e| u64::from_str_radix(e.dhash.as_deref()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(asset_id: &str, shown_at: DateTime<Utc>) -> HistoryEntry {
+        HistoryEntry {
+            source_id: Some("src".to_string()),
+            asset_id: Some(asset_id.to_string()),
+            path: None,
+            shown_at,
+            dhash: None,
+        }
+    }
+
+    fn ids(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|e| e.asset_id.as_deref().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn record_appends_entries() {
+        let frame = format!("history_test_{}", std::process::id());
+        let meta = |id: &str| ImageMeta {
+            data: SourceData::Path(PathBuf::from(format!("/photos/{id}.jpg"))),
+            orientation: Default::default(),
+            date_taken: None,
+            exif_blob: None,
+            id: Some(id.to_string()),
+            source_id: Some("src".to_string()),
+            asset_metadata: None,
+        };
+        record(&frame, &meta("a"), Some(0xabc)).await.unwrap();
+        record(&frame, &meta("b"), None).await.unwrap();
+        let entries = load(&frame).await;
+        let _ = std::fs::remove_file(history_path(&frame));
+
+        assert_eq!(ids(&entries), ["a", "b"]);
+        assert_eq!(entries[0].path.as_deref(), Some("/photos/a.jpg"));
+        assert_eq!(entries[0].dhash.as_deref(), Some("0000000000000abc"));
+        assert_eq!(entries[1].dhash, None);
+    }
+
+    #[test]
+    fn push_capped_drops_oldest() {
+        let now = Utc::now();
+        let mut entries = Vec::new();
+        for id in ["a", "b", "c", "d"] {
+            push_capped(&mut entries, entry(id, now), 3);
+        }
+        assert_eq!(ids(&entries), ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn recent_asset_ids_respects_window() {
+        let now = Utc::now();
+        let entries = vec![
+            entry("old", now - Duration::days(10)),
+            entry("edge", now - Duration::days(3)),
+            entry("new", now - Duration::hours(1)),
+        ];
+        let recent = recent_asset_ids(&entries, 3, now);
+        assert_eq!(recent.len(), 2);
+        assert!(recent.contains("edge") && recent.contains("new"));
+        assert!(recent_asset_ids(&entries, 0, now).is_empty());
+    }
+
+    #[test]
+    fn pages_newest_first() {
+        let now = Utc::now();
+        let entries: Vec<_> = ["a", "b", "c", "d", "e"]
+            .into_iter()
+            .map(|id| entry(id, now))
+            .collect();
+        assert_eq!(ids(&newest_first_page(entries.clone(), 0, 2)), ["e", "d"]);
+        assert_eq!(ids(&newest_first_page(entries.clone(), 2, 2)), ["c", "b"]);
+        assert_eq!(ids(&newest_first_page(entries.clone(), 4, 2)), ["a"]);
+        assert!(newest_first_page(entries, 9, 2).is_empty());
+    }
+}
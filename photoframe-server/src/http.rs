@@ -2,11 +2,11 @@ use std::net::SocketAddr;
 
 use axum::{
     Json, Router,
-    extract::{DefaultBodyLimit, Multipart, Path, State},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
     http::{StatusCode, header},
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{get, patch, post},
+    routing::{delete, get, patch, post},
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
@@ -16,6 +16,7 @@ use tower_http::{
 };
 use tracing::{Level, instrument};
 
+use crate::blacklist;
 use crate::frame;
 #[cfg(feature = "embed_ui")]
 use crate::ui;
@@ -98,6 +99,12 @@ pub struct UploadResponse {
     pub frame_id: String,
     pub width: u32,
     pub height: u32,
+    /// Id of the inbox item when the upload was also saved with `save_to`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inbox_item: Option<String>,
+    /// End of the display override when the upload set `duration` or `until`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub override_until: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 // Logs all 4xx/5xx responses with method, URI, status and latency.
@@ -257,6 +264,7 @@ pub fn router(state: AppState) -> Router {
         .route("/frames/{id}/clear", post(clear_frame))
         .route("/frames/{id}/palette", get(frame_palette))
         .route("/frames/{id}/metadata", get(get_frame_metadata))
+        .route("/frames/{id}/history", get(get_frame_history))
         .route("/frames/{id}/intermediate", get(get_intermediate_image))
         .route(
             "/frames/{id}/upload",
@@ -271,12 +279,40 @@ pub fn router(state: AppState) -> Router {
             post(set_immich_credentials),
         )
         .route("/sources/{id}/immich/filters", post(set_immich_filters))
+        .route(
+            "/sources/{id}/google/device",
+            post(start_google_device_flow).get(google_device_flow_status),
+        )
+        .route("/sources/{id}/google/picker", post(create_google_picker))
         .route("/sources/{id}/refresh", post(refresh_source))
+        .route("/sources/{id}/health", get(source_health))
+        .route("/sources/{id}/assets", get(list_source_assets))
+        .route(
+            "/sources/{id}/assets/{asset}/thumbnail",
+            get(source_asset_thumbnail),
+        )
+        .route("/frames/{id}/show", post(show_frame_asset))
+        .route(
+            "/frames/{id}/override",
+            get(get_frame_override).delete(clear_frame_override),
+        )
+        .route("/frames/{id}/current/actions", post(current_asset_action))
+        .route("/sources/{id}/inbox", get(list_inbox))
+        .route("/sources/{id}/inbox/{item}", delete(delete_inbox_item))
         .route("/sources/reload", post(reload_sources))
         .route(
             "/frames/{id}/sources/blacklist",
             post(blacklist_source_asset),
         )
+        .route("/sources/{id}/blacklist", get(list_source_blacklist))
+        .route(
+            "/sources/{id}/blacklist/{asset}",
+            delete(remove_source_blacklist_item),
+        )
+        .route(
+            "/sources/{id}/blacklist/{asset}/thumbnail",
+            get(source_blacklist_thumbnail),
+        )
         .route("/health", get(health_check))
         .route("/live", get(liveness_probe))
         .with_state(state.clone())
@@ -394,21 +430,39 @@ pub async fn upload_frame(
     mut multipart: Multipart,
 ) -> Result<Json<UploadResponse>, StatusCode> {
     let mut data: Option<Vec<u8>> = None;
+    let mut save_to: Option<String> = None;
+    let mut uploader: Option<String> = None;
+    let mut duration: Option<String> = None;
+    let mut until: Option<String> = None;
     while let Some(field) = multipart
         .next_field()
         .await
         .map_err(|_| StatusCode::BAD_REQUEST)?
     {
         let name = field.name().map(|s| s.to_string());
-        if name.as_deref() == Some("file") {
-            data = Some(
-                field
-                    .bytes()
-                    .await
-                    .map_err(|_| StatusCode::BAD_REQUEST)?
-                    .to_vec(),
-            );
-            break;
+        match name.as_deref() {
+            Some("file") => {
+                data = Some(
+                    field
+                        .bytes()
+                        .await
+                        .map_err(|_| StatusCode::BAD_REQUEST)?
+                        .to_vec(),
+                );
+            }
+            Some("save_to") => {
+                save_to = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+            }
+            Some("uploader") => {
+                uploader = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+            }
+            Some("duration") => {
+                duration = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+            }
+            Some("until") => {
+                until = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+            }
+            _ => {}
         }
     }
     let data = data.ok_or(StatusCode::BAD_REQUEST)?;
@@ -419,20 +473,175 @@ pub async fn upload_frame(
         .photoframes
         .get(&frame_id)
         .ok_or(StatusCode::NOT_FOUND)?;
+    // Resolve the inbox and override before touching the frame so bad fields change nothing.
+    let override_until = override_end(duration.as_deref(), until.as_deref())?;
+    let inbox = match save_to.as_deref().filter(|s| !s.is_empty()) {
+        Some(source_id) => Some((source_id, inbox_dir_for(&cfg, source_id)?)),
+        None => None,
+    };
+    // Save to the inbox first: if that fails the frame must be left as it was.
+    let mut saved = None;
+    if let Some((source_id, dir)) = &inbox {
+        image::guess_format(&data).map_err(|_| StatusCode::BAD_REQUEST)?;
+        let item = crate::sources::save_inbox_upload(dir, &data, uploader.as_deref())
+            .map_err(|e| {
+                tracing::warn!(source = %source_id, error = %format!("{e:#}"), "saving upload to inbox failed");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        saved = Some(item.id);
+    }
     let limits = cfg.image_limits.as_ref();
-    match crate::frame::handle_direct_upload(&frame_id, frame_cfg, &data, limits).await {
-        Ok(prepared) => {
-            if let Err(e) = crate::frame::save_prepared(&frame_id, &prepared) {
-                tracing::warn!(frame = %frame_id, error = %e, "saving uploaded file failed");
+    let prepared = match crate::frame::handle_direct_upload(&frame_id, frame_cfg, &data, limits)
+        .await
+    {
+        Ok(prepared) => prepared,
+        Err(_) => {
+            // Not an image the frame can show; don't keep it in the inbox either.
+            if let (Some((source_id, dir)), Some(id)) = (&inbox, &saved)
+                && let Err(e) = crate::sources::delete_inbox_item(dir, id)
+            {
+                tracing::warn!(source = %source_id, error = %e, "removing rejected upload from inbox failed");
             }
-            Ok(Json(UploadResponse {
-                frame_id,
-                width: prepared.width,
-                height: prepared.height,
-            }))
+            return Err(StatusCode::BAD_REQUEST);
         }
-        Err(_) => Err(StatusCode::BAD_REQUEST),
+    };
+    if let Err(e) = crate::frame::save_prepared(&frame_id, &prepared) {
+        tracing::warn!(frame = %frame_id, error = %e, "saving uploaded file failed");
+    }
+    let mut inbox_item = None;
+    let mut inbox_source = None;
+    if let Some((source_id, _)) = inbox {
+        if let Some(src) = state.scheduler.source(source_id).await
+            && let Err(e) = src.refresh().await
+        {
+            tracing::warn!(source = %source_id, error = %e, "inbox refresh after upload failed");
+        }
+        inbox_item = saved;
+        inbox_source = Some(source_id.to_string());
+    }
+    if let Some(until) = override_until {
+        state
+            .scheduler
+            .set_override(&frame_id, until, inbox_source, inbox_item.clone())
+            .await
+            .map_err(|e| {
+                tracing::warn!(frame = %frame_id, error = %e, "setting display override failed");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
     }
+    Ok(Json(UploadResponse {
+        frame_id,
+        width: prepared.width,
+        height: prepared.height,
+        inbox_item,
+        override_until,
+    }))
+}
+
+/// End time of a display override from a `duration` (e.g. `3h`) or an `until` time
+/// (RFC 3339 or local `HH:MM`). Giving both is an error; neither means no override.
+fn override_end(
+    duration: Option<&str>,
+    until: Option<&str>,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, StatusCode> {
+    let now = chrono::Utc::now();
+    let end = match (
+        duration.filter(|s| !s.is_empty()),
+        until.filter(|s| !s.is_empty()),
+    ) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => return Err(StatusCode::BAD_REQUEST),
+        (Some(d), None) => crate::overrides::end_after(d, now),
+        (None, Some(u)) => crate::overrides::parse_until(u, now, scheduler::scheduler_timezone()),
+    };
+    match end {
+        Ok(end) if end > now => Ok(Some(end)),
+        _ => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+/// The frame's active display override.
+pub async fn get_frame_override(
+    Path(frame_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<crate::overrides::DisplayOverride>, StatusCode> {
+    state
+        .scheduler
+        .active_override(&frame_id)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// End the frame's display override early and return it to its schedule.
+#[instrument(err, skip_all)]
+pub async fn clear_frame_override(
+    Path(frame_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, StatusCode> {
+    let cleared = state
+        .scheduler
+        .clear_override(&frame_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !cleared {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Directory of the configured inbox source `source_id`; 404 when there is no such
+/// source and 400 when it is not an inbox.
+fn inbox_dir_for(cfg: &config::Config, source_id: &str) -> Result<std::path::PathBuf, StatusCode> {
+    match cfg.sources.get(source_id) {
+        Some(config::Source::Inbox { inbox }) => Ok(crate::sources::inbox_dir(
+            source_id,
+            &inbox.clone().unwrap_or_default(),
+        )),
+        Some(_) => Err(StatusCode::BAD_REQUEST),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Items of an inbox source, oldest first, with who uploaded them and when.
+pub async fn list_inbox(
+    Path(source_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<crate::sources::InboxItem>>, StatusCode> {
+    let cfg = config::ConfigManager::to_struct(&state.cfg)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let dir = inbox_dir_for(&cfg, &source_id)?;
+    let items = crate::sources::list_inbox_items(&dir).map_err(|e| {
+        tracing::warn!(source = %source_id, error = %e, "listing inbox failed");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(items))
+}
+
+/// Delete an inbox item and drop it from the source's rotation.
+#[instrument(err, skip_all)]
+pub async fn delete_inbox_item(
+    Path((source_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, StatusCode> {
+    let cfg = config::ConfigManager::to_struct(&state.cfg)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let dir = inbox_dir_for(&cfg, &source_id)?;
+    let deleted = crate::sources::delete_inbox_item(&dir, &item_id).map_err(|e| {
+        tracing::warn!(source = %source_id, item = %item_id, error = %e, "deleting inbox item failed");
+        StatusCode::BAD_REQUEST
+    })?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state
+        .scheduler
+        .remove_asset_from_cache_for_source(&source_id, &item_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[derive(Deserialize)]
@@ -497,41 +706,197 @@ pub async fn set_immich_filters(
     Ok(StatusCode::OK)
 }
 
+/// Configuration of a Google Photos source, or 404 if `source_id` isn't one.
+async fn google_photos_config(
+    state: &AppState,
+    source_id: &str,
+) -> Result<config::GooglePhotosSource, StatusCode> {
+    let cfg = config::ConfigManager::to_struct(&state.cfg)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    match cfg.sources.get(source_id) {
+        Some(config::Source::GooglePhotos { google_photos }) => {
+            Ok(google_photos.clone().unwrap_or_default())
+        }
+        _ => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[derive(Serialize)]
+pub struct GoogleDeviceFlowResponse {
+    pub user_code: String,
+    pub verification_url: String,
+    pub expires_in: u64,
+}
+
+/// Start the OAuth2 device flow for a Google Photos source. The returned code is entered
+/// at `verification_url`; approval is awaited in the background, after which the
+/// refresh token is saved and sources are reloaded. Progress is reported by the GET
+/// route.
+#[instrument(err, skip_all)]
+pub async fn start_google_device_flow(
+    Path(source_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<GoogleDeviceFlowResponse>, StatusCode> {
+    let gp = google_photos_config(&state, &source_id).await?;
+    let endpoints = crate::sources::GoogleEndpoints::default();
+    let details = crate::sources::start_device_flow(&gp, &endpoints)
+        .await
+        .map_err(|e| {
+            tracing::warn!(source=%source_id, error=%format!("{e:#}"), "google device flow failed to start");
+            StatusCode::BAD_GATEWAY
+        })?;
+    let response = GoogleDeviceFlowResponse {
+        user_code: details.user_code().secret().clone(),
+        verification_url: details.verification_uri().to_string(),
+        expires_in: details.expires_in().as_secs(),
+    };
+    crate::sources::set_device_flow_status(
+        &source_id,
+        crate::sources::DeviceFlowStatus::Pending {
+            user_code: response.user_code.clone(),
+            verification_url: response.verification_url.clone(),
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds(response.expires_in as i64),
+        },
+    );
+
+    tokio::spawn(async move {
+        let result = async {
+            let token = crate::sources::finish_device_flow(&gp, &endpoints, &details).await?;
+            config::ConfigManager::set_google_refresh_token(&state.cfg, &source_id, &token).await?;
+            config::ConfigManager::save(&state.cfg).await?;
+            state.scheduler.reload_sources().await
+        }
+        .await;
+        let status = match result {
+            Ok(()) => {
+                tracing::info!(source=%source_id, "google photos authorized");
+                crate::sources::DeviceFlowStatus::Authorized
+            }
+            Err(e) => {
+                tracing::warn!(source=%source_id, error=%format!("{e:#}"), "google device flow failed");
+                crate::sources::DeviceFlowStatus::Failed {
+                    error: format!("{e:#}"),
+                }
+            }
+        };
+        crate::sources::set_device_flow_status(&source_id, status);
+    });
+
+    Ok(Json(response))
+}
+
+pub async fn google_device_flow_status(
+    Path(source_id): Path<String>,
+) -> Result<Json<crate::sources::DeviceFlowStatus>, StatusCode> {
+    crate::sources::device_flow_status(&source_id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Create a Photos Picker session for a Google Photos source and show its picks from
+/// now on. Open `picker_uri` to choose the photos before `expire_time`; they are copied
+/// as soon as the user is done.
+#[instrument(err, skip_all)]
+pub async fn create_google_picker(
+    Path(source_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<crate::sources::PickerSession>, StatusCode> {
+    let gp = google_photos_config(&state, &source_id).await?;
+    let session =
+        crate::sources::create_picker_session(&gp, &crate::sources::GoogleEndpoints::default())
+            .await
+            .map_err(|e| {
+                tracing::warn!(source=%source_id, error=%format!("{e:#}"), "creating google picker session failed");
+                StatusCode::BAD_GATEWAY
+            })?;
+    config::ConfigManager::set_google_picker_session(&state.cfg, &source_id, &session.id)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    config::ConfigManager::save(&state.cfg)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    state
+        .scheduler
+        .reload_sources()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tokio::spawn(watch_google_picker(
+        state.scheduler.clone(),
+        source_id,
+        session.clone(),
+    ));
+    Ok(Json(session))
+}
+
+/// Check a new picker session at the interval Google asks for until the source has
+/// copied its photos or the session expires.
+async fn watch_google_picker(
+    scheduler: std::sync::Arc<scheduler::FrameScheduler>,
+    source_id: String,
+    session: crate::sources::PickerSession,
+) {
+    let interval = session
+        .poll_interval
+        .unwrap_or(std::time::Duration::from_secs(10));
+    let deadline = session
+        .expire_time
+        .unwrap_or_else(|| chrono::Utc::now() + chrono::Duration::hours(1));
+    while chrono::Utc::now() < deadline {
+        tokio::time::sleep(interval).await;
+        let Some(src) = scheduler.source(&source_id).await else {
+            return;
+        };
+        if let Err(e) = src.refresh().await {
+            tracing::debug!(source=%source_id, error=%format!("{e:#}"), "picker session check failed");
+        }
+        if src.health().await.healthy {
+            tracing::info!(source=%source_id, "google photos picker session copied");
+            return;
+        }
+    }
+    tracing::warn!(source=%source_id, session=%session.id, "google photos picker session expired before photos were picked");
+}
+
 #[derive(Deserialize)]
 pub struct BlacklistSourceAssetPayload {
     pub asset_id: String,
     pub source_id: String, // The source ID to which the asset belongs
 }
 
+/// 404 unless `source_id` names a configured source. Blacklists are stored in files
+/// named after the source, so ids from requests must be checked before use.
+async fn require_source(state: &AppState, source_id: &str) -> Result<(), StatusCode> {
+    let cfg = config::ConfigManager::to_struct(&state.cfg)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if cfg.sources.contains_key(source_id) {
+        Ok(())
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
 #[instrument(err, skip_all)]
 pub async fn blacklist_source_asset(
     Path(frame_id): Path<String>, // frame_id is used to trigger next_frame, not for blacklisting itself
     State(state): State<AppState>,
     Json(payload): Json<BlacklistSourceAssetPayload>,
 ) -> Result<StatusCode, StatusCode> {
-    // Attempt to add to blacklist. The config manager returns Ok(false) when the
-    // asset was already present in the blacklist.
-    let added = config::ConfigManager::add_immich_blacklist_item(
-        &state.cfg,
-        &payload.source_id,
-        &payload.asset_id,
-    )
-    .await
-    .map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_source(&state, &payload.source_id).await?;
+    // Assets shown through a composite source are blacklisted in the member listing them.
+    let added = state
+        .scheduler
+        .blacklist_asset(&frame_id, &payload.source_id, &payload.asset_id)
+        .await
+        .map_err(|e| {
+            tracing::warn!(source = %payload.source_id, asset_id = %payload.asset_id, error = %e, "blacklisting failed");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
     if !added {
         // Already blacklisted
         return Err(StatusCode::CONFLICT);
     }
-    config::ConfigManager::save(&state.cfg)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Remove the asset from the scheduler's in-memory Immich cache for the specific source
-    state
-        .scheduler
-        .remove_asset_from_cache_for_source(&payload.source_id, &payload.asset_id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Trigger next image for the frame
     state
@@ -543,6 +908,110 @@ pub async fn blacklist_source_asset(
     Ok(StatusCode::OK)
 }
 
+#[derive(Serialize)]
+pub struct BlacklistItem {
+    pub asset_id: String,
+    pub date_taken: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the asset was blacklisted; unknown for entries from the config file.
+    pub added_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Listed in the source's `immich.blacklist` setting rather than blacklisted from a frame.
+    pub from_config: bool,
+}
+
+/// Blacklisted assets of a source, oldest first, including those from its `immich.blacklist`
+/// setting. Thumbnails are served by `/sources/{id}/blacklist/{asset}/thumbnail`.
+pub async fn list_source_blacklist(
+    Path(source_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<BlacklistItem>>, StatusCode> {
+    let cfg = config::ConfigManager::to_struct(&state.cfg)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let src = cfg.sources.get(&source_id).ok_or(StatusCode::NOT_FOUND)?;
+    let mut items: Vec<BlacklistItem> = Vec::new();
+    if let config::Source::Immich {
+        immich: Some(immich),
+    } = src
+    {
+        items.extend(immich.blacklist.iter().map(|id| BlacklistItem {
+            asset_id: id.clone(),
+            date_taken: None,
+            added_at: None,
+            from_config: true,
+        }));
+    }
+    for entry in blacklist::load(&source_id).await {
+        if items.iter().any(|i| i.asset_id == entry.asset_id) {
+            continue;
+        }
+        items.push(BlacklistItem {
+            asset_id: entry.asset_id,
+            date_taken: entry.date_taken,
+            added_at: Some(entry.added_at),
+            from_config: false,
+        });
+    }
+    Ok(Json(items))
+}
+
+/// Un-blacklist an asset so the source offers it again.
+#[instrument(err, skip_all)]
+pub async fn remove_source_blacklist_item(
+    Path((source_id, asset_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, StatusCode> {
+    require_source(&state, &source_id).await?;
+    let removed = blacklist::remove(&source_id, &asset_id).await.map_err(|e| {
+        tracing::warn!(source = %source_id, asset_id = %asset_id, error = %e, "removing blacklist entry failed");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let from_config =
+        config::ConfigManager::remove_immich_blacklist_item(&state.cfg, &source_id, &asset_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if from_config {
+        config::ConfigManager::save(&state.cfg)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // Immich sources read their blacklist setting when built.
+        state
+            .scheduler
+            .reload_sources()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    } else if removed {
+        // Blacklisting dropped the asset from the source's cache; list it again.
+        if let Some(src) = state.scheduler.source(&source_id).await
+            && let Err(e) = src.refresh().await
+        {
+            tracing::warn!(source = %source_id, error = %e, "refresh after un-blacklisting failed");
+        }
+    } else {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Thumbnail of a blacklisted asset as stored when it was blacklisted. Entries from the
+/// config file have none stored, so theirs is rendered by the source.
+pub async fn source_blacklist_thumbnail(
+    Path((source_id, asset_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    require_source(&state, &source_id).await?;
+    if let Some(bytes) = blacklist::thumbnail(&source_id, &asset_id).await {
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "image/jpeg".to_string()),
+                (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+            ],
+            bytes,
+        )
+            .into_response());
+    }
+    source_asset_thumbnail(Path((source_id, asset_id)), State(state)).await
+}
+
 /// Serve the HTTP API. If `bind` is Some it is parsed as a socket address, otherwise
 /// defaults to 0.0.0.0:8080.
 pub async fn serve(app: Router, bind: Option<String>) -> anyhow::Result<()> {
@@ -723,18 +1192,73 @@ pub async fn get_frame_metadata(
     Ok(Json(value))
 }
 
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryResponse {
+    pub frame_id: String,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    /// Newest entries first.
+    pub items: Vec<crate::history::HistoryEntry>,
+}
+
+/// Return a page of a frame's display history, newest first.
+pub async fn get_frame_history(
+    Path(frame_id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<HistoryResponse>, StatusCode> {
+    if frame_id.contains('/') || frame_id.contains("..") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let entries = crate::history::load(&frame_id).await;
+    let total = entries.len();
+    let items = crate::history::newest_first_page(entries, query.offset, limit);
+    Ok(Json(HistoryResponse {
+        frame_id,
+        total,
+        offset: query.offset,
+        limit,
+        items,
+    }))
+}
+
 pub async fn refresh_source(
     Path(source_id): Path<String>,
     State(state): State<AppState>,
 ) -> Result<StatusCode, StatusCode> {
-    state
+    let src = state
         .scheduler
-        .refresh_source(&source_id)
+        .source(&source_id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if let Err(e) = src.refresh().await {
+        tracing::warn!(source = %source_id, error = %e, "source refresh failed");
+        return Err(StatusCode::BAD_GATEWAY);
+    }
     Ok(StatusCode::ACCEPTED)
 }
 
+/// Report whether a source is currently usable.
+pub async fn source_health(
+    Path(source_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<crate::sources::SourceHealth>, StatusCode> {
+    let src = state
+        .scheduler
+        .source(&source_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(src.health().await))
+}
+
 #[instrument(err, skip_all)]
 pub async fn reload_sources(State(state): State<AppState>) -> Result<StatusCode, StatusCode> {
     state
@@ -782,3 +1306,402 @@ pub async fn next_frame(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(StatusCode::ACCEPTED)
 }
+
+#[derive(Deserialize)]
+pub struct AssetListQuery {
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub orientation: Option<config::Orientation>,
+    /// RFC 3339 timestamp or `YYYY-MM-DD` (UTC midnight).
+    pub taken_after: Option<String>,
+    /// RFC 3339 timestamp or `YYYY-MM-DD` (UTC midnight).
+    pub taken_before: Option<String>,
+}
+
+fn parse_query_date(raw: &str) -> Result<chrono::DateTime<chrono::Utc>, StatusCode> {
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(raw) {
+        return Ok(dt.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .map(|d| d.and_time(chrono::NaiveTime::MIN).and_utc())
+        .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+#[derive(Serialize)]
+pub struct AssetListItem {
+    #[serde(flatten)]
+    pub asset: crate::sources::AssetSummary,
+    pub thumbnail_url: String,
+}
+
+#[derive(Serialize)]
+pub struct AssetListResponse {
+    pub source_id: String,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub items: Vec<AssetListItem>,
+}
+
+/// Browse a source's assets. Asset ids may contain `/` (file paths), so they are
+/// percent-encoded in the returned thumbnail URLs.
+pub async fn list_source_assets(
+    Path(source_id): Path<String>,
+    Query(query): Query<AssetListQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<AssetListResponse>, StatusCode> {
+    let src = state
+        .scheduler
+        .source(&source_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let list_query = crate::sources::ListQuery {
+        offset: query.offset,
+        limit,
+        orientation: query.orientation,
+        taken_after: query
+            .taken_after
+            .as_deref()
+            .map(parse_query_date)
+            .transpose()?,
+        taken_before: query
+            .taken_before
+            .as_deref()
+            .map(parse_query_date)
+            .transpose()?,
+    };
+    let page = src.list(&list_query).await.map_err(|e| {
+        tracing::warn!(source = %source_id, error = %e, "listing source assets failed");
+        StatusCode::BAD_GATEWAY
+    })?;
+    let items = page
+        .items
+        .into_iter()
+        .map(|asset| AssetListItem {
+            thumbnail_url: format!(
+                "/api/sources/{}/assets/{}/thumbnail",
+                urlencoding::encode(&source_id),
+                urlencoding::encode(&asset.id)
+            ),
+            asset,
+        })
+        .collect();
+    Ok(Json(AssetListResponse {
+        source_id,
+        total: page.total,
+        offset: query.offset,
+        limit,
+        items,
+    }))
+}
+
+pub async fn source_asset_thumbnail(
+    Path((source_id, asset_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    let src = state
+        .scheduler
+        .source(&source_id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let thumb = src
+        .thumbnail(&asset_id)
+        .await
+        .map_err(|e| {
+            tracing::warn!(source = %source_id, asset_id = %asset_id, error = %e, "thumbnail failed");
+            StatusCode::BAD_GATEWAY
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, thumb.content_type),
+            (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+        ],
+        thumb.bytes,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub struct ShowAssetPayload {
+    pub source_id: String,
+    pub asset_id: String,
+    /// Keep the asset on the frame for this long (e.g. `3h`), skipping scheduled updates.
+    pub duration: Option<String>,
+    /// Keep the asset on the frame until this time (RFC 3339 or local `HH:MM`).
+    pub until: Option<String>,
+}
+
+/// Show a specific asset on the frame now instead of the next scheduled pick.
+#[instrument(err, skip_all)]
+pub async fn show_frame_asset(
+    Path(frame_id): Path<String>,
+    State(state): State<AppState>,
+    Json(payload): Json<ShowAssetPayload>,
+) -> Result<StatusCode, StatusCode> {
+    let override_until = override_end(payload.duration.as_deref(), payload.until.as_deref())?;
+    let shown = state
+        .scheduler
+        .show_asset(&frame_id, &payload.source_id, &payload.asset_id)
+        .await
+        .map_err(|e| {
+            tracing::warn!(frame = %frame_id, error = %e, "showing asset failed");
+            StatusCode::BAD_GATEWAY
+        })?;
+    if !shown {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if let Some(until) = override_until {
+        state
+            .scheduler
+            .set_override(
+                &frame_id,
+                until,
+                Some(payload.source_id),
+                Some(payload.asset_id),
+            )
+            .await
+            .map_err(|e| {
+                tracing::warn!(frame = %frame_id, error = %e, "setting display override failed");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Apply a write-back action (favorite, add to album, archive) to the asset currently
+/// shown on the frame.
+pub async fn current_asset_action(
+    Path(frame_id): Path<String>,
+    State(state): State<AppState>,
+    Json(action): Json<crate::sources::AssetAction>,
+) -> Result<StatusCode, StatusCode> {
+    if frame_id.contains('/') || frame_id.contains("..") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let outcome = state
+        .scheduler
+        .apply_current_asset_action(&frame_id, &action)
+        .await
+        .map_err(|e| {
+            tracing::warn!(frame = %frame_id, ?action, error = %format!("{e:#}"), "asset action failed");
+            StatusCode::BAD_GATEWAY
+        })?;
+    match outcome {
+        crate::scheduler::ActionOutcome::Applied => Ok(StatusCode::OK),
+        crate::scheduler::ActionOutcome::NotFound => Err(StatusCode::NOT_FOUND),
+        crate::scheduler::ActionOutcome::Unsupported => Err(StatusCode::UNPROCESSABLE_ENTITY),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+
+    /// Serve the API over a filesystem source holding one landscape and one portrait image.
+    async fn serve_fs_source(dir: &std::path::Path) -> String {
+        image::RgbImage::new(8, 4)
+            .save(dir.join("wide.png"))
+            .unwrap();
+        image::RgbImage::new(4, 8)
+            .save(dir.join("tall.png"))
+            .unwrap();
+        let cfg_path = dir.join("photoframe.toml");
+        let toml = format!(
+            "[sources.fs]\nkind = \"filesystem\"\n\n[sources.fs.filesystem]\nglob = \"{}/*.png\"\n",
+            dir.display()
+        );
+        std::fs::write(&cfg_path, toml).unwrap();
+        let cfg = config::ConfigManager::load(Some(cfg_path)).await.unwrap();
+        let scheduler = scheduler::FrameScheduler::new(cfg.clone()).await.unwrap();
+        let app = router(AppState {
+            cfg,
+            scheduler: std::sync::Arc::new(scheduler),
+        });
+        let addr = test_support::serve(app).await;
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn lists_and_filters_source_assets() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = serve_fs_source(dir.path()).await;
+        let client = reqwest::Client::new();
+
+        let all: JsonValue = client
+            .get(format!("{base}/api/sources/fs/assets"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(all["total"], 2);
+
+        let portrait: JsonValue = client
+            .get(format!("{base}/api/sources/fs/assets?orientation=portrait"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(portrait["total"], 1);
+        let item = &portrait["items"][0];
+        assert!(item["id"].as_str().unwrap().ends_with("tall.png"));
+        // File paths are percent-encoded into a single path segment.
+        let thumbnail_url = item["thumbnail_url"].as_str().unwrap();
+        assert!(thumbnail_url.contains("%2F"));
+
+        let thumb = client
+            .get(format!("{base}{thumbnail_url}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(thumb.status(), StatusCode::OK);
+        assert_eq!(thumb.headers()[header::CONTENT_TYPE], "image/jpeg");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_sources_assets_and_bad_dates() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = serve_fs_source(dir.path()).await;
+        let client = reqwest::Client::new();
+        let status = |path: String| {
+            let client = client.clone();
+            async move { client.get(path).send().await.unwrap().status() }
+        };
+
+        assert_eq!(
+            status(format!("{base}/api/sources/missing/assets")).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(format!(
+                "{base}/api/sources/fs/assets/%2Fetc%2Fpasswd/thumbnail"
+            ))
+            .await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(format!(
+                "{base}/api/sources/fs/assets?taken_after=yesterday"
+            ))
+            .await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(format!(
+                "{base}/api/sources/fs/assets?taken_after=2020-01-01"
+            ))
+            .await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn blacklist_routes_refuse_unknown_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = serve_fs_source(dir.path()).await;
+        let client = reqwest::Client::new();
+        // Would otherwise resolve to a blacklist file outside the working directory.
+        let name = format!("victim_{}", std::process::id());
+        let escaped = format!("..%2F{name}");
+        let victim = std::path::PathBuf::from(format!("../{name}_blacklist.json"));
+        let entry = blacklist::BlacklistEntry {
+            asset_id: "a".into(),
+            content_hash: None,
+            date_taken: None,
+            added_at: chrono::Utc::now(),
+        };
+        let original = serde_json::to_vec(&[entry]).unwrap();
+        std::fs::write(&victim, &original).unwrap();
+
+        let resp = client
+            .delete(format!("{base}/api/sources/{escaped}/blacklist/a"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = client
+            .get(format!(
+                "{base}/api/sources/{escaped}/blacklist/a/thumbnail"
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = client
+            .post(format!("{base}/api/frames/f/sources/blacklist"))
+            .json(&serde_json::json!({"source_id": format!("../{name}"), "asset_id": "b"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(std::fs::read(&victim).unwrap(), original);
+        let _ = std::fs::remove_file(&victim);
+    }
+
+    #[tokio::test]
+    async fn failed_inbox_upload_leaves_frame_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let frame = format!("upload_test_{}", std::process::id());
+        // A file where the inbox directory should be, so saving to it fails.
+        let blocked = dir.path().join("blocked");
+        std::fs::write(&blocked, b"").unwrap();
+        let inbox = dir.path().join("inbox");
+        let cfg_path = dir.path().join("photoframe.toml");
+        let toml = format!(
+            "[sources.blocked]\nkind = \"inbox\"\n\n[sources.blocked.inbox]\ndir = \"{}\"\n\n\
+             [sources.box]\nkind = \"inbox\"\n\n[sources.box.inbox]\ndir = \"{}\"\n\n\
+             [photoframes.{frame}]\ndummy = true\npanel_width = 8\npanel_height = 4\n",
+            blocked.display(),
+            inbox.display()
+        );
+        std::fs::write(&cfg_path, toml).unwrap();
+        let cfg = config::ConfigManager::load(Some(cfg_path)).await.unwrap();
+        let scheduler = scheduler::FrameScheduler::new(cfg.clone()).await.unwrap();
+        let addr = test_support::serve(router(AppState {
+            cfg,
+            scheduler: std::sync::Arc::new(scheduler),
+        }))
+        .await;
+        let client = reqwest::Client::new();
+        let upload = |bytes: Vec<u8>, save_to: &str| {
+            let form = reqwest::multipart::Form::new()
+                .part(
+                    "file",
+                    reqwest::multipart::Part::bytes(bytes).file_name("a.png"),
+                )
+                .text("save_to", save_to.to_string());
+            client
+                .post(format!("http://{addr}/api/frames/{frame}/upload"))
+                .multipart(form)
+                .send()
+        };
+
+        let resp = upload(test_support::png(8, 4), "blocked").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!std::path::Path::new(&format!("{frame}_base.png")).exists());
+
+        // Uploads the frame rejects are not kept in the inbox either.
+        let resp = upload(b"not an image".to_vec(), "box").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let png = test_support::png(8, 4);
+        let truncated = png[..png.len() - 20].to_vec();
+        let resp = upload(truncated, "box").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(
+            crate::sources::list_inbox_items(&inbox)
+                .unwrap_or_default()
+                .is_empty()
+        );
+
+        for suffix in ["base.png", "intermediate.png", "metadata.json"] {
+            let _ = std::fs::remove_file(format!("{frame}_{suffix}"));
+        }
+    }
+}
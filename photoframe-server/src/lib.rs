@@ -1,9 +1,14 @@
+pub mod blacklist;
 pub mod config;
 pub mod dither;
 pub mod frame;
+pub mod history;
 pub mod http;
+pub mod overrides;
+pub mod phash;
 pub mod pipeline;
 pub mod scheduler;
+pub mod smtp;
 pub mod sources;
 pub mod timestamp;
 #[cfg(feature = "embed_ui")]
@@ -1,5 +1,5 @@
 use photoframe_server as _;
-use photoframe_server::{config, http, scheduler};
+use photoframe_server::{config, http, scheduler, smtp};
 use tracing_subscriber::{EnvFilter, fmt};
 
 #[tokio::main]
@@ -22,6 +22,15 @@ async fn main() -> anyhow::Result<()> {
     scheduler.populate().await?;
     scheduler.start().await?;
 
+    if let Some(smtp) = cfg_snapshot.smtp.clone() {
+        let (cfg, scheduler) = (shared.clone(), scheduler.clone());
+        tokio::spawn(async move {
+            if let Err(e) = smtp::serve(cfg, scheduler, smtp).await {
+                tracing::error!(error = %format!("{e:#}"), "SMTP listener stopped");
+            }
+        });
+    }
+
     let state = http::AppState {
         cfg: shared,
         scheduler: scheduler.clone(),
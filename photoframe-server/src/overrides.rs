@@ -0,0 +1,169 @@
+//! Temporary per-frame display overrides persisted as `<frame_id>_override.json`. While
+//! one is active, scheduled updates leave the frame on the image it was set for.
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayOverride {
+    pub until: DateTime<Utc>,
+    pub set_at: DateTime<Utc>,
+    /// What the override shows; both are `None` for direct uploads that weren't saved.
+    pub source_id: Option<String>,
+    pub asset_id: Option<String>,
+}
+
+impl DisplayOverride {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        now < self.until
+    }
+}
+
+fn override_path(frame_id: &str) -> PathBuf {
+    PathBuf::from(format!("{frame_id}_override.json"))
+}
+
+/// The stored override for a frame, whether or not it has expired.
+pub async fn load(frame_id: &str) -> Option<DisplayOverride> {
+    let bytes = tokio::fs::read(override_path(frame_id)).await.ok()?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| tracing::warn!(frame=%frame_id, error=%e, "ignoring unreadable override file"))
+        .ok()
+}
+
+pub async fn save(frame_id: &str, ov: &DisplayOverride) -> Result<()> {
+    let path = override_path(frame_id);
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(ov)?)
+        .await
+        .with_context(|| format!("writing {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .with_context(|| format!("renaming {}", path.display()))?;
+    Ok(())
+}
+
+pub async fn clear(frame_id: &str) -> Result<()> {
+    match tokio::fs::remove_file(override_path(frame_id)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Parse a duration like `3h`, `1h30m`, `90m`, `2d` or a plain number of seconds.
+/// Values too large to represent are an error.
+pub fn parse_duration(raw: &str) -> Result<Duration> {
+    let raw = raw.trim();
+    let too_long = || anyhow::anyhow!("duration {raw:?} is too long");
+    if let Ok(secs) = raw.parse::<i64>() {
+        if secs <= 0 {
+            bail!("duration must be positive");
+        }
+        return Duration::try_seconds(secs).ok_or_else(too_long);
+    }
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in raw.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let n: i64 = digits
+            .parse()
+            .with_context(|| format!("invalid duration {raw:?}"))?;
+        digits.clear();
+        let part = match c {
+            'd' => Duration::try_days(n),
+            'h' => Duration::try_hours(n),
+            'm' => Duration::try_minutes(n),
+            's' => Duration::try_seconds(n),
+            _ => bail!("invalid duration unit {c:?} in {raw:?}"),
+        };
+        total = part
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(too_long)?;
+    }
+    if !digits.is_empty() || total <= Duration::zero() {
+        bail!("invalid duration {raw:?}");
+    }
+    Ok(total)
+}
+
+/// End time of an override lasting `raw` (see [`parse_duration`]) from `now`.
+pub fn end_after(raw: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let duration = parse_duration(raw)?;
+    now.checked_add_signed(duration)
+        .with_context(|| format!("duration {raw:?} ends too far in the future"))
+}
+
+/// Parse an end time: an RFC 3339 timestamp, or a local `HH:MM` meaning its next
+/// occurrence in `tz` (so `08:00` in the evening is tomorrow morning).
+pub fn parse_until(raw: &str, now: DateTime<Utc>, tz: Tz) -> Result<DateTime<Utc>> {
+    let raw = raw.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    let time = NaiveTime::parse_from_str(raw, "%H:%M")
+        .with_context(|| format!("invalid end time {raw:?}"))?;
+    let today = now.with_timezone(&tz).date_naive();
+    for date in [today, today + Duration::days(1)] {
+        // `earliest` skips times that don't exist on DST change days.
+        if let Some(local) = tz.from_local_datetime(&date.and_time(time)).earliest() {
+            let utc = local.with_timezone(&Utc);
+            if utc > now {
+                return Ok(utc);
+            }
+        }
+    }
+    bail!("no upcoming {raw} in {tz}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("3h").unwrap(), Duration::hours(3));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::days(2));
+        assert_eq!(parse_duration("600").unwrap(), Duration::seconds(600));
+        for bad in ["", "0", "-5", "h", "3x", "3h5"] {
+            assert!(parse_duration(bad).is_err(), "{bad:?} parsed");
+        }
+    }
+
+    #[test]
+    fn oversized_durations_are_errors() {
+        for huge in [
+            "9223372036854775807",
+            "99999999999999d",
+            "9223372036854775807s",
+            "99999999999999999999h",
+            "106751991167d106751991167d",
+        ] {
+            assert!(parse_duration(huge).is_err(), "{huge:?} parsed");
+        }
+        let now = Utc::now();
+        assert!(end_after("9999999999d", now).is_err());
+        assert!(end_after("9999999999", now).is_ok());
+        assert_eq!(end_after("3h", now).unwrap(), now + Duration::hours(3));
+    }
+
+    #[test]
+    fn until_picks_next_local_occurrence() {
+        let tz: Tz = "Europe/Helsinki".parse().unwrap();
+        // 20:00 local (UTC+3 in summer).
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 17, 0, 0).unwrap();
+        let morning = parse_until("08:00", now, tz).unwrap();
+        assert_eq!(morning, Utc.with_ymd_and_hms(2024, 6, 2, 5, 0, 0).unwrap());
+        let later = parse_until("22:30", now, tz).unwrap();
+        assert_eq!(later, Utc.with_ymd_and_hms(2024, 6, 1, 19, 30, 0).unwrap());
+        let exact = parse_until("2024-06-03T12:00:00Z", now, tz).unwrap();
+        assert_eq!(exact, Utc.with_ymd_and_hms(2024, 6, 3, 12, 0, 0).unwrap());
+        assert!(parse_until("tomorrow", now, tz).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Perceptual image hashes (dHash) for recognizing near-identical photos such as burst
+//! shots or lightly edited copies, which differ in bytes but not in what they show.
+
+use crate::frame;
+use crate::sources::SourceData;
+use anyhow::Result;
+use image::DynamicImage;
+use image::imageops::FilterType;
+
+/// Hamming distance at or below which two hashes count as the same scene by default.
+pub const DEFAULT_MAX_DISTANCE: u32 = 10;
+
+/// 64-bit difference hash: shrink to 9x8 grey pixels and record, row by row, whether
+/// each pixel is brighter than its right neighbour.
+pub fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+/// Number of differing bits between two hashes.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Hashes of images shown recently on a frame, and how close a candidate may come.
+#[derive(Debug, Clone, Default)]
+pub struct RecentHashes {
+    pub hashes: Vec<u64>,
+    pub max_distance: u32,
+}
+
+impl RecentHashes {
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Whether `hash` is within `max_distance` of any recent hash.
+    pub fn is_similar(&self, hash: u64) -> bool {
+        self.hashes
+            .iter()
+            .any(|&h| distance(h, hash) <= self.max_distance)
+    }
+}
+
+/// [`dhash`] of an image as delivered by a source, after applying its EXIF orientation
+/// so originals with a rotation flag match pre-rotated thumbnails of the same photo.
+/// Decoding runs on a blocking thread.
+pub async fn data_dhash(data: &SourceData) -> Result<u64> {
+    let data = data.clone();
+    tokio::task::spawn_blocking(move || {
+        let img = match &data {
+            SourceData::Path(p) => frame::decode_oriented(&std::fs::read(p)?)?,
+            SourceData::Bytes(b) => frame::decode_oriented(b)?,
+        };
+        Ok(dhash(&img))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+    use image::{Rgb, RgbImage};
+
+    /// Smooth light/dark pattern defined on normalized coordinates, so it looks the
+    /// same at any size.
+    fn scene(width: u32, height: u32, fx: f32, fy: f32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let u = x as f32 / width as f32;
+            let v = y as f32 / height as f32;
+            let l = ((u * fx).sin() * (v * fy).cos() * 100.0 + 128.0) as u8;
+            Rgb([l, l / 2, 255 - l])
+        }))
+    }
+
+    #[test]
+    fn near_copies_hash_close_and_other_images_far() {
+        let original = scene(640, 480, 7.0, 5.0);
+        // Same scene at another size and slightly brighter, as after an export or edit.
+        let edited = scene(320, 240, 7.0, 5.0).brighten(12);
+        let different = scene(640, 480, 3.0, 9.0);
+
+        let h = dhash(&original);
+        assert_eq!(distance(h, dhash(&original)), 0);
+        assert!(distance(h, dhash(&edited)) <= DEFAULT_MAX_DISTANCE);
+        assert!(distance(h, dhash(&different)) > DEFAULT_MAX_DISTANCE);
+    }
+
+    #[tokio::test]
+    async fn orientation_flag_is_applied_before_hashing() {
+        let viewed = scene(480, 640, 7.0, 5.0);
+        // Stored sideways with a flag saying to rotate 90° clockwise for viewing, as a
+        // camera original is; a server-rendered thumbnail arrives already upright.
+        let stored = viewed.rotate270();
+        let flag = exif::Field {
+            tag: exif::Tag::Orientation,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Short(vec![6]),
+        };
+        let original =
+            test_support::encode_jpeg_with_exif(&stored, &test_support::exif_blob(&[flag]));
+        let mut thumbnail = Vec::new();
+        viewed
+            .write_to(
+                &mut std::io::Cursor::new(&mut thumbnail),
+                image::ImageFormat::Jpeg,
+            )
+            .unwrap();
+
+        let a = data_dhash(&SourceData::Bytes(original)).await.unwrap();
+        let b = data_dhash(&SourceData::Bytes(thumbnail)).await.unwrap();
+        assert!(distance(a, b) <= DEFAULT_MAX_DISTANCE);
+        assert!(distance(a, dhash(&stored)) > DEFAULT_MAX_DISTANCE);
+    }
+}
@@ -1,60 +1,65 @@
-use crate::{config, frame, sources};
+use crate::blacklist::{self, Blacklist, BlacklistEntry};
+use crate::overrides::{self, DisplayOverride};
+use crate::phash::{self, RecentHashes};
+use crate::{config, frame, history, sources};
 use anyhow::Result;
 use chrono_tz::Tz;
-use rand::rng;
-use rand::seq::SliceRandom;
+use rand::{Rng, rng};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use tracing::info;
 
-type SharedImageSource = Arc<Box<dyn sources::ImageSource>>;
+pub use crate::sources::SharedImageSource;
 type SourcesMap = HashMap<String, SharedImageSource>;
 type SharedSourcesMap = Arc<RwLock<SourcesMap>>;
+type SharedOverrides = Arc<RwLock<HashMap<String, DisplayOverride>>>;
+
+/// How many times a single source is re-asked when it returns an excluded asset or one
+/// too similar to a recently shown image.
+const EXCLUDED_RETRIES: usize = 3;
 
 pub struct FrameScheduler {
     sched: JobScheduler,
     cfg: config::SharedConfig,
     pub(crate) sources: SharedSourcesMap,
+    overrides: SharedOverrides,
 }
 
 impl FrameScheduler {
     pub async fn new(cfg: config::SharedConfig) -> Result<Self> {
         let sched = JobScheduler::new().await?;
         let sources_map = Self::build_sources_map(&cfg).await?;
+        let mut restored = HashMap::new();
+        for frame_id in config::ConfigManager::to_struct(&cfg)
+            .await?
+            .photoframes
+            .keys()
+        {
+            if let Some(ov) = overrides::load(frame_id).await {
+                restored.insert(frame_id.clone(), ov);
+            }
+        }
         Ok(Self {
             sched,
             cfg,
             sources: Arc::new(RwLock::new(sources_map)),
+            overrides: Arc::new(RwLock::new(restored)),
         })
     }
 
-    /// Parse timezone from TZ environment variable, fallback to UTC
-    fn get_timezone() -> Result<Tz> {
-        if let Ok(tz_str) = std::env::var("TZ") {
-            match tz_str.parse::<Tz>() {
-                Ok(tz) => {
-                    tracing::info!(timezone = %tz, "using timezone from TZ environment variable");
-                    Ok(tz)
-                }
-                Err(e) => {
-                    tracing::warn!(tz = %tz_str, error = %e, "invalid timezone in TZ environment variable, falling back to UTC");
-                    Ok(chrono_tz::UTC)
-                }
-            }
-        } else {
-            tracing::info!("no TZ environment variable set, using UTC");
-            Ok(chrono_tz::UTC)
-        }
-    }
-
     /// Build the sources map from the current configuration
     async fn build_sources_map(cfg: &config::SharedConfig) -> Result<SourcesMap> {
         let snapshot = config::ConfigManager::to_struct(cfg).await?;
         let mut map: SourcesMap = HashMap::new();
+        let mut composites = Vec::new();
         for (id, src_cfg) in snapshot.sources.iter() {
-            match sources::build_source(src_cfg) {
+            if let config::Source::Composite { composite } = src_cfg {
+                composites.push((id.clone(), composite.clone().unwrap_or_default()));
+                continue;
+            }
+            match sources::build_source(id, src_cfg) {
                 Ok(built) => {
                     map.insert(id.clone(), Arc::new(built));
                 }
@@ -63,6 +68,36 @@ impl FrameScheduler {
                 }
             }
         }
+        // Composites may include other composites, so build them once all their members
+        // exist. Whatever is left when no progress is made has a missing member or a cycle.
+        while !composites.is_empty() {
+            let pending = composites.len();
+            composites.retain(|(id, c)| {
+                if !c.sources.iter().all(|m| map.contains_key(m)) {
+                    return true;
+                }
+                let members = c
+                    .sources
+                    .iter()
+                    .map(|m| (m.clone(), Arc::clone(&map[m])))
+                    .collect();
+                match sources::CompositeImageSource::new(id, c, members) {
+                    Ok(built) => {
+                        map.insert(id.clone(), Arc::new(Box::new(built)));
+                    }
+                    Err(e) => {
+                        tracing::warn!(source = %id, error = %e, "failed to build source");
+                    }
+                }
+                false
+            });
+            if composites.len() == pending {
+                for (id, c) in &composites {
+                    tracing::warn!(source = %id, members = ?c.sources, "composite source has a missing member or a cycle");
+                }
+                break;
+            }
+        }
         Ok(map)
     }
 
@@ -83,22 +118,25 @@ impl FrameScheduler {
 
     pub async fn populate(&self) -> Result<()> {
         let cfg_snapshot = config::ConfigManager::to_struct(&self.cfg).await?;
-        let timezone = Self::get_timezone()?;
+        let timezone = scheduler_timezone();
 
         for (frame_id, frame) in cfg_snapshot.photoframes.iter() {
             if let Some(cron) = &frame.update_cron {
                 let frame_id_clone = frame_id.clone();
                 let shared = Arc::clone(&self.cfg);
                 let sources_map = Arc::clone(&self.sources);
+                let overrides_map = Arc::clone(&self.overrides);
                 let cron_expr = cron.to_string();
                 let job = Job::new_async_tz(cron_expr.as_str(), timezone, move |_uuid, _l| {
                     let frame_id = frame_id_clone.clone();
                     let shared = Arc::clone(&shared);
                     let sources_map = Arc::clone(&sources_map);
+                    let overrides_map = Arc::clone(&overrides_map);
                     Box::pin(async move {
                         if let Err(e) = FrameScheduler::run_frame_update(
                             &shared,
                             &sources_map,
+                            &overrides_map,
                             &frame_id,
                             false,
                         )
@@ -116,6 +154,17 @@ impl FrameScheduler {
 
     pub async fn start(&self) -> Result<()> {
         self.sched.start().await?;
+        // Overrides restored from disk resume their countdown; expired ones end right away.
+        let restored: Vec<(String, DisplayOverride)> = self
+            .overrides
+            .read()
+            .await
+            .iter()
+            .map(|(id, ov)| (id.clone(), ov.clone()))
+            .collect();
+        for (frame_id, ov) in restored {
+            self.spawn_override_expiry(frame_id, ov);
+        }
         Ok(())
     }
 
@@ -123,6 +172,7 @@ impl FrameScheduler {
     async fn run_frame_update(
         cfg: &config::SharedConfig,
         sources_map: &SharedSourcesMap,
+        overrides_map: &SharedOverrides,
         frame_id: &str,
         ignore_pause: bool,
     ) -> Result<()> {
@@ -135,6 +185,15 @@ impl FrameScheduler {
             tracing::info!(frame=%frame_id, "frame paused; skipping scheduled update");
             return Ok(());
         }
+        if ignore_pause {
+            // A manual trigger ends any override in favour of a fresh pick.
+            Self::end_override(overrides_map, frame_id).await;
+        } else if let Some(ov) = overrides_map.read().await.get(frame_id)
+            && ov.is_active(chrono::Utc::now())
+        {
+            tracing::info!(frame=%frame_id, until=%ov.until, "display override active; skipping scheduled update");
+            return Ok(());
+        }
         let cwd = std::env::current_dir()
             .map(|p| p.display().to_string())
             .unwrap_or_else(|_| "<err>".into());
@@ -154,38 +213,18 @@ impl FrameScheduler {
             }
         }
 
-        let mut selected: Option<sources::ImageMeta> = None;
-
-        // Shuffle configured sources before probing to select a source at random
-        let mut sids: Vec<String> = f.source_ids.to_vec();
-        {
-            let mut rng = rng();
-            sids.shuffle(&mut rng);
-        }
-
-        // Process each source ID sequentially
-        for sid in &sids {
-            // Get a clone of the Arc for this specific source
-            let source_arc = {
-                let sources_guard = sources_map.read().await;
-                sources_guard.get(sid).cloned()
-            };
-
-            if let Some(src) = source_arc
-                && let Ok(Some(mut meta)) = src.next(desired).await
-            {
-                meta.source_id = Some(sid.clone());
-                selected = Some(meta);
-                break;
-            }
-        }
+        let selected =
+            Self::select_image(sources_map, frame_id, f, cfg_now.image_limits.as_ref()).await;
         if f.source_ids.is_empty() {
             tracing::warn!(frame = %frame_id, "no sources configured for frame");
         }
         if let Some(meta) = &selected {
             let limits = cfg_now.image_limits.as_ref();
-            if let Err(e) = crate::frame::process_and_push(frame_id, f, meta, limits).await {
-                tracing::warn!(frame = %frame_id, error = %e, "failed to push image to frame");
+            match crate::frame::process_and_push(frame_id, f, meta, limits).await {
+                Ok(()) => Self::record_shown(frame_id, f, meta).await,
+                Err(e) => {
+                    tracing::warn!(frame = %frame_id, error = %e, "failed to push image to frame")
+                }
             }
         }
         if selected.is_none() {
@@ -206,24 +245,363 @@ impl FrameScheduler {
         Ok(())
     }
 
+    /// Pick the next image for a frame from its configured sources, honoring the frame's
+    /// no-repeat and similarity windows. Falls back to allowing repeats when every
+    /// candidate was shown (or looks like one shown) recently, so a small library never
+    /// leaves the frame without an image.
+    async fn select_image(
+        sources_map: &SharedSourcesMap,
+        frame_id: &str,
+        f: &config::PhotoFrame,
+        limits: Option<&config::ImageLimits>,
+    ) -> Option<sources::ImageMeta> {
+        let mut ctx = sources::SelectionContext::for_frame(frame_id, f, limits);
+        let mut recent = RecentHashes {
+            hashes: Vec::new(),
+            max_distance: f
+                .similar_max_distance
+                .unwrap_or(phash::DEFAULT_MAX_DISTANCE),
+        };
+        let no_repeat_days = f.no_repeat_days.filter(|d| *d > 0);
+        let similar_days = f.skip_similar_days.filter(|d| *d > 0);
+        if no_repeat_days.is_some() || similar_days.is_some() {
+            let entries = history::load(frame_id).await;
+            let now = chrono::Utc::now();
+            if let Some(days) = no_repeat_days {
+                ctx.exclude = history::recent_asset_ids(&entries, days, now);
+            }
+            if let Some(days) = similar_days {
+                recent.hashes = history::recent_dhashes(&entries, days, now);
+            }
+        }
+        if let Some(meta) = Self::probe_sources(sources_map, f, &ctx, &recent).await {
+            return Some(meta);
+        }
+        if ctx.exclude.is_empty() && recent.is_empty() {
+            return None;
+        }
+        tracing::warn!(frame = %frame_id, excluded = ctx.exclude.len(), recent_hashes = recent.hashes.len(), "no image outside the no-repeat or similarity window; allowing repeats");
+        ctx.exclude.clear();
+        recent.hashes.clear();
+        Self::probe_sources(sources_map, f, &ctx, &recent).await
+    }
+
+    /// Compute the selection weight of each of the frame's sources.
+    async fn source_weights(
+        sources_map: &SharedSourcesMap,
+        f: &config::PhotoFrame,
+    ) -> Vec<(String, f64)> {
+        match f.source_selection.unwrap_or_default() {
+            config::SourceSelection::Uniform => {
+                f.source_ids.iter().map(|sid| (sid.clone(), 1.0)).collect()
+            }
+            config::SourceSelection::Weighted => f
+                .source_ids
+                .iter()
+                .map(|sid| {
+                    (
+                        sid.clone(),
+                        f.source_weights.get(sid).copied().unwrap_or(1.0),
+                    )
+                })
+                .collect(),
+            config::SourceSelection::Proportional => {
+                let orientation = f.orientation.unwrap_or_default();
+                let sources_guard = sources_map.read().await;
+                f.source_ids
+                    .iter()
+                    .map(|sid| {
+                        let matching = sources_guard.get(sid).map_or(0, |src| {
+                            let st = src.stats();
+                            match orientation {
+                                config::Orientation::Landscape => st.landscape,
+                                config::Orientation::Portrait => st.portrait,
+                            }
+                        });
+                        // Sources that haven't listed yet still get a chance to be probed.
+                        (sid.clone(), matching.max(1) as f64)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Probe sources in weighted random order and return the first acceptable image.
+    async fn probe_sources(
+        sources_map: &SharedSourcesMap,
+        f: &config::PhotoFrame,
+        ctx: &sources::SelectionContext,
+        recent: &RecentHashes,
+    ) -> Option<sources::ImageMeta> {
+        let weights = Self::source_weights(sources_map, f).await;
+        let sids = {
+            let mut rng = rng();
+            weighted_order(&weights, &mut rng)
+        };
+
+        for sid in &sids {
+            // Get a clone of the Arc for this specific source
+            let source_arc = {
+                let sources_guard = sources_map.read().await;
+                sources_guard.get(sid).cloned()
+            };
+            let Some(src) = source_arc else {
+                continue;
+            };
+            let blacklist = Self::source_blacklist(sources_map, sid).await;
+            let ctx = &sources::SelectionContext {
+                blacklist: blacklist.asset_ids,
+                ..ctx.clone()
+            };
+            // Sources should already skip excluded ids; re-check so the window holds for all of them.
+            for _ in 0..EXCLUDED_RETRIES {
+                match src.next(ctx).await {
+                    Ok(Some(mut meta)) => {
+                        if meta.id.as_ref().is_some_and(|id| {
+                            ctx.exclude.contains(id) || ctx.blacklist.contains(id)
+                        }) {
+                            continue;
+                        }
+                        if !blacklist.content_hashes.is_empty()
+                            && let Ok(hash) = blacklist::data_hash(&meta.data).await
+                            && blacklist.content_hashes.contains(&hash)
+                        {
+                            tracing::debug!(frame = %ctx.frame_id, source = %sid, asset_id = ?meta.id, "skipping copy of a blacklisted image");
+                            continue;
+                        }
+                        if !recent.is_empty() {
+                            match phash::data_dhash(&meta.data).await {
+                                Ok(hash) if recent.is_similar(hash) => {
+                                    tracing::debug!(frame = %ctx.frame_id, source = %sid, asset_id = ?meta.id, "skipping image similar to one shown recently");
+                                    continue;
+                                }
+                                Ok(_) => {}
+                                Err(e) => {
+                                    tracing::debug!(frame = %ctx.frame_id, source = %sid, asset_id = ?meta.id, error = %e, "could not hash candidate image")
+                                }
+                            }
+                        }
+                        meta.source_id = Some(sid.clone());
+                        return Some(meta);
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::debug!(frame = %ctx.frame_id, source = %sid, error = %e, "source failed to produce an image");
+                        break;
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Blacklist entries that apply to a source: its own and those of every member source
+    /// it combines, since composites pick from their members' listings.
+    async fn source_blacklist(sources_map: &SharedSourcesMap, source_id: &str) -> Blacklist {
+        let mut out = Blacklist::default();
+        let mut pending = vec![source_id.to_string()];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(sid) = pending.pop() {
+            if !seen.insert(sid.clone()) {
+                continue;
+            }
+            out.extend(&blacklist::load(&sid).await);
+            if let Some(src) = sources_map.read().await.get(&sid) {
+                pending.extend(src.member_ids());
+            }
+        }
+        out
+    }
+
+    /// Append a shown image to the frame's history, with its perceptual hash when the
+    /// frame skips similar images (decoding just for the hash is wasted work otherwise).
+    async fn record_shown(frame_id: &str, f: &config::PhotoFrame, meta: &sources::ImageMeta) {
+        let dhash = match f.skip_similar_days.filter(|d| *d > 0) {
+            Some(_) => phash::data_dhash(&meta.data)
+                .await
+                .map_err(
+                    |e| tracing::debug!(frame = %frame_id, error = %e, "could not hash shown image"),
+                )
+                .ok(),
+            None => None,
+        };
+        if let Err(e) = history::record(frame_id, meta, dhash).await {
+            tracing::warn!(frame = %frame_id, error = %e, "failed to record display history");
+        }
+    }
+
     /// Public method to manually trigger a schedule update for a frame id.
     /// This behaves exactly like the scheduled cron jobs - always fetches next image from sources.
     pub async fn manual_schedule_trigger(&self, frame_id: &str) -> Result<()> {
-        Self::run_frame_update(&self.cfg, &self.sources, frame_id, true).await
+        Self::run_frame_update(&self.cfg, &self.sources, &self.overrides, frame_id, true).await
     }
 
-    pub async fn refresh_source(&self, source_id: &str) -> Result<()> {
-        let source_arc = {
-            let sources_guard = self.sources.read().await;
-            sources_guard.get(source_id).cloned()
+    /// Keep the frame's current image until `until`: scheduled updates are skipped until
+    /// then, after which the frame is updated once and returns to its normal schedule.
+    pub async fn set_override(
+        &self,
+        frame_id: &str,
+        until: chrono::DateTime<chrono::Utc>,
+        source_id: Option<String>,
+        asset_id: Option<String>,
+    ) -> Result<DisplayOverride> {
+        let ov = DisplayOverride {
+            until,
+            set_at: chrono::Utc::now(),
+            source_id,
+            asset_id,
         };
-        if let Some(src) = source_arc
-            && let Some(im) =
-                (src.as_ref() as &dyn std::any::Any).downcast_ref::<sources::ImmichImageSource>()
-        {
-            im.refresh().await.ok();
+        overrides::save(frame_id, &ov).await?;
+        self.overrides
+            .write()
+            .await
+            .insert(frame_id.to_string(), ov.clone());
+        info!(frame = %frame_id, until = %ov.until, "display override set");
+        self.spawn_override_expiry(frame_id.to_string(), ov.clone());
+        Ok(ov)
+    }
+
+    /// The frame's override, if one is active.
+    pub async fn active_override(&self, frame_id: &str) -> Option<DisplayOverride> {
+        self.overrides
+            .read()
+            .await
+            .get(frame_id)
+            .filter(|ov| ov.is_active(chrono::Utc::now()))
+            .cloned()
+    }
+
+    /// End a frame's override now and update the frame as its expiry would.
+    /// Returns Ok(false) if no override was active.
+    pub async fn clear_override(&self, frame_id: &str) -> Result<bool> {
+        if self.active_override(frame_id).await.is_none() {
+            return Ok(false);
         }
-        Ok(())
+        Self::end_override(&self.overrides, frame_id).await;
+        Self::run_frame_update(&self.cfg, &self.sources, &self.overrides, frame_id, false).await?;
+        Ok(true)
+    }
+
+    async fn end_override(overrides_map: &SharedOverrides, frame_id: &str) {
+        if overrides_map.write().await.remove(frame_id).is_none() {
+            return;
+        }
+        if let Err(e) = overrides::clear(frame_id).await {
+            tracing::warn!(frame = %frame_id, error = %e, "failed to remove override file");
+        }
+    }
+
+    /// Wait for `ov` to expire, then end it and run a regular update so the frame doesn't
+    /// keep the override image until its next cron tick. Does nothing if the override was
+    /// replaced or cleared in the meantime.
+    fn spawn_override_expiry(&self, frame_id: String, ov: DisplayOverride) {
+        let cfg = Arc::clone(&self.cfg);
+        let sources_map = Arc::clone(&self.sources);
+        let overrides_map = Arc::clone(&self.overrides);
+        tokio::spawn(async move {
+            let remaining = (ov.until - chrono::Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(remaining).await;
+            {
+                let mut guard = overrides_map.write().await;
+                if guard.get(&frame_id) != Some(&ov) {
+                    return;
+                }
+                guard.remove(&frame_id);
+            }
+            if let Err(e) = overrides::clear(&frame_id).await {
+                tracing::warn!(frame = %frame_id, error = %e, "failed to remove override file");
+            }
+            info!(frame = %frame_id, "display override expired; resuming schedule");
+            if let Err(e) =
+                Self::run_frame_update(&cfg, &sources_map, &overrides_map, &frame_id, false).await
+            {
+                tracing::warn!(frame = %frame_id, error = %e, "frame update after override failed");
+            }
+        });
+    }
+
+    /// Look up a built source by id.
+    pub async fn source(&self, source_id: &str) -> Option<SharedImageSource> {
+        self.sources.read().await.get(source_id).cloned()
+    }
+
+    /// The source that actually provides `asset_id` when it is shown through `source_id`,
+    /// following composite sources down to their members.
+    pub async fn asset_source(&self, source_id: &str, asset_id: &str) -> String {
+        let mut current = source_id.to_string();
+        // Bounded in case of a misbehaving source; composites can't form cycles.
+        for _ in 0..16 {
+            let Some(owner) = self
+                .source(&current)
+                .await
+                .and_then(|src| src.asset_owner(asset_id))
+            else {
+                break;
+            };
+            current = owner;
+        }
+        current
+    }
+
+    /// Blacklist an asset shown on a frame through `source_id` so no frame picks it again.
+    /// The entry is kept by the source that provides the asset (a composite's member) with
+    /// a thumbnail for reviewing the blacklist later. For sources serving original bytes it
+    /// also records the image's content hash, so copies of it elsewhere in that source are
+    /// skipped too; a hash of a rendition sized for one frame would not match on others.
+    /// Returns Ok(false) if the asset was already blacklisted.
+    pub async fn blacklist_asset(
+        &self,
+        frame_id: &str,
+        source_id: &str,
+        asset_id: &str,
+    ) -> Result<bool> {
+        let owner = self.asset_source(source_id, asset_id).await;
+        let cfg_now = config::ConfigManager::to_struct(&self.cfg).await?;
+        // Fetch the rendition the frame would get for the thumbnail.
+        let ctx = match cfg_now.photoframes.get(frame_id) {
+            Some(f) => {
+                sources::SelectionContext::for_frame(frame_id, f, cfg_now.image_limits.as_ref())
+            }
+            None => sources::SelectionContext::default(),
+        };
+        let (meta, serves_originals) = match self.source(&owner).await {
+            Some(src) => (
+                src.get(asset_id, &ctx).await.unwrap_or_else(|e| {
+                    tracing::warn!(source = %owner, asset_id = %asset_id, error = %e, "failed to fetch asset being blacklisted");
+                    None
+                }),
+                src.serves_originals(),
+            ),
+            None => (None, false),
+        };
+        let mut entry = BlacklistEntry {
+            asset_id: asset_id.to_string(),
+            content_hash: None,
+            date_taken: None,
+            added_at: chrono::Utc::now(),
+        };
+        let mut thumbnail = None;
+        if let Some(meta) = &meta {
+            entry.date_taken = meta.date_taken;
+            if serves_originals {
+                entry.content_hash = blacklist::data_hash(&meta.data).await.ok();
+            }
+            thumbnail = frame::render_thumbnail(meta, sources::THUMBNAIL_SIZE)
+                .await
+                .map_err(|e| tracing::warn!(source = %owner, asset_id = %asset_id, error = %e, "failed to render blacklist thumbnail"))
+                .ok();
+        }
+        if !blacklist::add(&owner, entry, thumbnail.as_deref()).await? {
+            return Ok(false);
+        }
+        info!(frame = %frame_id, source_id = %owner, asset_id = %asset_id, "blacklisted asset");
+        self.remove_asset_from_cache_for_source(source_id, asset_id)
+            .await?;
+        if owner != source_id {
+            self.remove_asset_from_cache_for_source(&owner, asset_id)
+                .await?;
+        }
+        Ok(true)
     }
 
     /// Remove an asset id from a source's in-memory cache (if present).
@@ -263,29 +641,8 @@ impl FrameScheduler {
         };
         let desired = f.orientation.unwrap_or_default();
 
-        let mut selected: Option<sources::ImageMeta> = None;
-        // Shuffle configured sources before probing to select a source at random
-        let mut sids: Vec<String> = f.source_ids.to_vec();
-        {
-            let mut rng = rng();
-            sids.shuffle(&mut rng);
-        }
-
-        for sid in &sids {
-            // Get a clone of the Arc for this specific source
-            let source_arc = {
-                let sources_guard = self.sources.read().await;
-                sources_guard.get(sid).cloned()
-            };
-
-            if let Some(src) = source_arc
-                && let Ok(Some(mut meta)) = src.next(desired).await
-            {
-                meta.source_id = Some(sid.clone());
-                selected = Some(meta);
-                break;
-            }
-        }
+        let selected =
+            Self::select_image(&self.sources, frame_id, f, cfg_now.image_limits.as_ref()).await;
 
         // Log stats to help diagnose empty selections.
         {
@@ -306,6 +663,7 @@ impl FrameScheduler {
         // Load and store base, and also write intermediate snapshot for UI toggle.
         let limits = cfg_now.image_limits.as_ref();
         let base = frame::load_and_store_base(frame_id, &meta, f, limits).await?;
+        Self::record_shown(frame_id, f, &meta).await;
         if let Err(e) = frame::save_intermediate_from_base(frame_id, f, &base).await {
             tracing::warn!(frame=%frame_id, error=%e, "failed saving intermediate image (prime)");
         }
@@ -321,6 +679,69 @@ impl FrameScheduler {
         Ok(())
     }
 
+    /// Apply a write-back action to the asset currently shown on a frame, as recorded in
+    /// `<frame_id>_metadata.json`. Archiving also moves the frame on to the next image.
+    pub async fn apply_current_asset_action(
+        &self,
+        frame_id: &str,
+        action: &sources::AssetAction,
+    ) -> Result<ActionOutcome> {
+        let Some((source_id, asset_id)) = frame::current_asset(frame_id).await else {
+            return Ok(ActionOutcome::NotFound);
+        };
+        let Some(src) = self.source(&source_id).await else {
+            return Ok(ActionOutcome::NotFound);
+        };
+        if !src.apply_action(&asset_id, action).await? {
+            return Ok(ActionOutcome::Unsupported);
+        }
+        info!(frame = %frame_id, source_id = %source_id, asset_id = %asset_id, ?action, "applied asset action");
+        if matches!(action, sources::AssetAction::Archive) {
+            self.prime_next_image(frame_id).await?;
+        }
+        Ok(ActionOutcome::Applied)
+    }
+
+    /// Show a specific asset on a frame right away, bypassing source selection. The asset
+    /// goes through the same load/process/push path as scheduled updates.
+    /// Returns Ok(false) if the frame, source or asset doesn't exist.
+    pub async fn show_asset(
+        &self,
+        frame_id: &str,
+        source_id: &str,
+        asset_id: &str,
+    ) -> Result<bool> {
+        let cfg_now = config::ConfigManager::to_struct(&self.cfg).await?;
+        let Some(f) = cfg_now.photoframes.get(frame_id) else {
+            return Ok(false);
+        };
+        let Some(src) = self.source(source_id).await else {
+            return Ok(false);
+        };
+        let limits = cfg_now.image_limits.as_ref();
+        let ctx = sources::SelectionContext::for_frame(frame_id, f, limits);
+        let Some(mut meta) = src.get(asset_id, &ctx).await? else {
+            return Ok(false);
+        };
+        meta.source_id = Some(source_id.to_string());
+        frame::process_and_push(frame_id, f, &meta, limits).await?;
+        Self::record_shown(frame_id, f, &meta).await;
+        info!(frame = %frame_id, source_id = %source_id, asset_id = %asset_id, "showing requested asset");
+        Ok(true)
+    }
+
+    /// Show an image that doesn't belong to a configured source (e.g. an emailed photo) on
+    /// a frame right away. Returns Ok(false) if the frame doesn't exist.
+    pub async fn show_image(&self, frame_id: &str, meta: &sources::ImageMeta) -> Result<bool> {
+        let cfg_now = config::ConfigManager::to_struct(&self.cfg).await?;
+        let Some(f) = cfg_now.photoframes.get(frame_id) else {
+            return Ok(false);
+        };
+        frame::process_and_push(frame_id, f, meta, cfg_now.image_limits.as_ref()).await?;
+        Self::record_shown(frame_id, f, meta).await;
+        Ok(true)
+    }
+
     /// Push the currently cached base image to the device, if any; otherwise no-op.
     pub async fn push_cached_base(&self, frame_id: &str) -> Result<()> {
         let cfg_now = config::ConfigManager::to_struct(&self.cfg).await?;
@@ -336,3 +757,206 @@ impl FrameScheduler {
         Ok(())
     }
 }
+
+/// Result of [`FrameScheduler::apply_current_asset_action`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionOutcome {
+    Applied,
+    /// The frame shows nothing from a known source (e.g. a direct upload).
+    NotFound,
+    /// The current asset's source doesn't support the action.
+    Unsupported,
+}
+
+/// Timezone cron schedules run in: `TZ` if it names a valid zone, otherwise UTC. Read
+/// once per process.
+pub fn scheduler_timezone() -> Tz {
+    static TIMEZONE: std::sync::OnceLock<Tz> = std::sync::OnceLock::new();
+    *TIMEZONE.get_or_init(|| match std::env::var("TZ") {
+        Ok(tz_str) => match tz_str.parse::<Tz>() {
+            Ok(tz) => {
+                tracing::info!(timezone = %tz, "using timezone from TZ environment variable");
+                tz
+            }
+            Err(e) => {
+                tracing::warn!(tz = %tz_str, error = %e, "invalid timezone in TZ environment variable, falling back to UTC");
+                chrono_tz::UTC
+            }
+        },
+        Err(_) => {
+            tracing::info!("no TZ environment variable set, using UTC");
+            chrono_tz::UTC
+        }
+    })
+}
+
+/// Order ids by weighted random sampling without replacement: heavier ids tend to come
+/// first, and the first id is picked with probability proportional to its weight.
+/// Ids with a non-positive or non-finite weight are left out.
+pub fn weighted_order<R: Rng + ?Sized>(weights: &[(String, f64)], rng: &mut R) -> Vec<String> {
+    // Efraimidis–Spirakis: key = ln(u) / w, sorted descending.
+    let mut keyed: Vec<(f64, &String)> = weights
+        .iter()
+        .filter(|(_, w)| w.is_finite() && *w > 0.0)
+        .map(|(id, w)| {
+            let u: f64 = rng.random_range(f64::MIN_POSITIVE..1.0);
+            (u.ln() / w, id)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed.into_iter().map(|(_, id)| id.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn weights(pairs: &[(&str, f64)]) -> Vec<(String, f64)> {
+        pairs.iter().map(|(id, w)| (id.to_string(), *w)).collect()
+    }
+
+    #[test]
+    fn weighted_order_is_deterministic_under_seed() {
+        let w = weights(&[("a", 1.0), ("b", 2.0), ("c", 3.0)]);
+        let first = weighted_order(&w, &mut StdRng::seed_from_u64(42));
+        let second = weighted_order(&w, &mut StdRng::seed_from_u64(42));
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 3);
+    }
+
+    #[test]
+    fn weighted_order_favors_heavier_sources() {
+        let w = weights(&[("kids_drawings", 20.0), ("immich", 30_000.0)]);
+        let mut rng = StdRng::seed_from_u64(7);
+        let trials = 10_000;
+        let immich_first = (0..trials)
+            .filter(|_| weighted_order(&w, &mut rng)[0] == "immich")
+            .count();
+        // Expected share is 30000/30020 ≈ 99.9%.
+        assert!(
+            immich_first > trials * 99 / 100,
+            "immich first {immich_first} times"
+        );
+    }
+
+    #[test]
+    fn weighted_order_matches_weight_ratio() {
+        let w = weights(&[("a", 3.0), ("b", 1.0)]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let trials = 20_000;
+        let a_first = (0..trials)
+            .filter(|_| weighted_order(&w, &mut rng)[0] == "a")
+            .count();
+        let share = a_first as f64 / trials as f64;
+        assert!((share - 0.75).abs() < 0.02, "share {share}");
+    }
+
+    /// Source handing out a fixed sequence of images over and over.
+    struct Scripted(parking_lot::Mutex<Vec<sources::ImageMeta>>);
+
+    #[async_trait::async_trait]
+    impl sources::ImageSource for Scripted {
+        async fn next(
+            &self,
+            _ctx: &sources::SelectionContext,
+        ) -> Result<Option<sources::ImageMeta>> {
+            let mut queue = self.0.lock();
+            queue.rotate_left(1);
+            Ok(queue.last().cloned())
+        }
+
+        async fn list(&self, query: &sources::ListQuery) -> Result<sources::AssetPage> {
+            Ok(sources::AssetPage::from_summaries(Vec::new(), query))
+        }
+
+        async fn get(
+            &self,
+            _id: &str,
+            _ctx: &sources::SelectionContext,
+        ) -> Result<Option<sources::ImageMeta>> {
+            Ok(None)
+        }
+
+        async fn health(&self) -> sources::SourceHealth {
+            sources::SourceHealth::healthy(None)
+        }
+    }
+
+    /// PNG of a horizontal grey ramp, brighter towards the left or the right.
+    fn ramp(id: &str, bright_left: bool, lift: u8) -> sources::ImageMeta {
+        let img = image::GrayImage::from_fn(64, 48, |x, _| {
+            let l = (x * 3) as u8;
+            image::Luma([if bright_left { 200 - l } else { l } + lift])
+        });
+        let mut png = Vec::new();
+        image::DynamicImage::ImageLuma8(img)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        sources::ImageMeta {
+            data: sources::SourceData::Bytes(png),
+            orientation: config::Orientation::Landscape,
+            date_taken: None,
+            exif_blob: None,
+            id: Some(id.to_string()),
+            source_id: None,
+            asset_metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn select_image_skips_images_similar_to_recent_ones() {
+        let frame_id = format!("similar_test_{}", std::process::id());
+        let frame = |skip: Option<u32>| -> config::PhotoFrame {
+            serde_json::from_value(serde_json::json!({
+                "source_ids": ["scripted"],
+                "skip_similar_days": skip,
+            }))
+            .unwrap()
+        };
+        let sources_for = |queue: Vec<sources::ImageMeta>| -> SharedSourcesMap {
+            let src: sources::SharedImageSource =
+                Arc::new(Box::new(Scripted(parking_lot::Mutex::new(queue))));
+            Arc::new(RwLock::new(HashMap::from([("scripted".to_string(), src)])))
+        };
+        let queue = || vec![ramp("burst_2", true, 10), ramp("other", false, 0)];
+
+        // Without the feature nothing is hashed and the near copy is fine.
+        let off = frame(None);
+        FrameScheduler::record_shown(&frame_id, &off, &ramp("burst_1", true, 0)).await;
+        assert!(history::load(&frame_id).await[0].dhash.is_none());
+        let picked = FrameScheduler::select_image(&sources_for(queue()), &frame_id, &off, None)
+            .await
+            .unwrap();
+        assert_eq!(picked.id.as_deref(), Some("burst_2"));
+
+        let on = frame(Some(7));
+        FrameScheduler::record_shown(&frame_id, &on, &ramp("burst_1", true, 0)).await;
+        let picked = FrameScheduler::select_image(&sources_for(queue()), &frame_id, &on, None)
+            .await
+            .unwrap();
+        assert_eq!(picked.id.as_deref(), Some("other"));
+        assert_eq!(picked.source_id.as_deref(), Some("scripted"));
+
+        // With only near copies left, repeats are allowed rather than showing nothing.
+        let picked = FrameScheduler::select_image(
+            &sources_for(vec![ramp("burst_2", true, 10), ramp("burst_3", true, 20)]),
+            &frame_id,
+            &on,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(picked.id.is_some_and(|id| id.starts_with("burst_")));
+
+        let _ = std::fs::remove_file(format!("{frame_id}_history.json"));
+    }
+
+    #[test]
+    fn weighted_order_drops_zero_weights() {
+        let w = weights(&[("a", 0.0), ("b", 1.0), ("c", f64::NAN)]);
+        let order = weighted_order(&w, &mut StdRng::seed_from_u64(3));
+        assert_eq!(order, vec!["b".to_string()]);
+    }
+}
@@ -0,0 +1,581 @@
+//! Embedded SMTP listener for emailing photos to frames. It speaks just enough plain
+//! SMTP (no TLS or AUTH) for mail clients and relays on the local network: mail from an
+//! allowed sender to `<mailbox>+<secret>@<domain>` has its image attachments shown on a
+//! frame or saved into a directory, depending on the mailbox. The envelope sender is
+//! trivially forged, so the shared secret in the recipient address is what gates delivery.
+
+use crate::config::{self, SmtpMailbox, SmtpServer};
+use crate::scheduler::FrameScheduler;
+use crate::sources::{ImageMeta, SourceData};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use mail_parser::{MessageParser, MimeHeaders};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+const DEFAULT_BIND: &str = "127.0.0.1:2525";
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 25 * 1024 * 1024;
+/// Longest command or message line read before the client is disconnected.
+const MAX_LINE_BYTES: u64 = 64 * 1024;
+const MAX_RECIPIENTS: usize = 32;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A message accepted by a session, addressed to one or more mailboxes.
+#[derive(Debug, Clone)]
+pub struct IncomingMail {
+    /// Envelope sender (`MAIL FROM`).
+    pub sender: String,
+    /// Local parts of the accepted recipients.
+    pub mailboxes: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+/// Where accepted mail goes. Kept separate from the protocol so sessions can be tested
+/// without frames or sources.
+#[async_trait]
+pub trait MailHandler: Send + Sync {
+    /// Whether `<mailbox>@...` is a known recipient.
+    async fn accepts_mailbox(&self, mailbox: &str) -> bool;
+    /// Deliver a message and return how many images it contained. Errors are reported to
+    /// the client as temporary failures so it retries later.
+    async fn deliver(&self, mail: IncomingMail) -> Result<usize>;
+}
+
+/// An image attachment of a message.
+#[derive(Debug, Clone)]
+pub struct MailImage {
+    pub name: Option<String>,
+    pub format: image::ImageFormat,
+    pub bytes: Vec<u8>,
+}
+
+/// Image attachments (including inline ones) of a raw message that the server can decode.
+pub fn extract_images(data: &[u8]) -> Vec<MailImage> {
+    let Some(message) = MessageParser::default().parse(data) else {
+        return Vec::new();
+    };
+    message
+        .attachments()
+        .filter_map(|part| {
+            let bytes = part.contents();
+            let format = image::guess_format(bytes).ok()?;
+            format.reading_enabled().then(|| MailImage {
+                name: part.attachment_name().map(str::to_string),
+                format,
+                bytes: bytes.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// Whether `sender` matches an allowlist entry: a full address or `@domain`.
+pub fn sender_allowed(allowed: &[String], sender: &str) -> bool {
+    let sender = sender.to_ascii_lowercase();
+    allowed.iter().any(|entry| {
+        let entry = entry.trim().to_ascii_lowercase();
+        if entry.starts_with('@') {
+            sender.ends_with(&entry)
+        } else {
+            !entry.is_empty() && sender == entry
+        }
+    })
+}
+
+/// The mailbox of a recipient local part `<mailbox>+<secret>`, if it carries `secret`.
+/// Nothing is accepted while no secret is configured.
+pub fn mailbox_with_secret<'a>(local: &'a str, secret: Option<&str>) -> Option<&'a str> {
+    let secret = secret.filter(|s| !s.is_empty())?;
+    let (mailbox, given) = local.rsplit_once('+')?;
+    // Compare without an early exit so response timing doesn't reveal matching prefixes.
+    let same = given.len() == secret.len()
+        && given
+            .bytes()
+            .zip(secret.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+    (same && !mailbox.is_empty()).then_some(mailbox)
+}
+
+/// The address inside `<...>` of a `MAIL FROM:`/`RCPT TO:` argument, plus its parameters.
+fn parse_path(arg: &str) -> Option<(&str, &str)> {
+    let arg = arg.trim_start();
+    let rest = arg.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    Some((rest[..end].trim(), rest[end + 1..].trim()))
+}
+
+/// Strip a case-insensitive command prefix such as `MAIL FROM:`.
+fn strip_command<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = line.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &line[prefix.len()..])
+}
+
+async fn reply<W: AsyncWrite + Unpin>(w: &mut W, line: &str) -> std::io::Result<()> {
+    w.write_all(line.as_bytes()).await?;
+    w.write_all(b"\r\n").await?;
+    w.flush().await
+}
+
+/// Read one line with its terminator. `None` at end of stream; an error when the line is
+/// longer than [`MAX_LINE_BYTES`] or the client goes idle.
+async fn read_line<R: AsyncRead + Unpin>(r: &mut BufReader<R>) -> Result<Option<Vec<u8>>> {
+    let mut buf = Vec::new();
+    let n = tokio::time::timeout(
+        IDLE_TIMEOUT,
+        (&mut *r).take(MAX_LINE_BYTES).read_until(b'\n', &mut buf),
+    )
+    .await
+    .context("client idle")??;
+    if n == 0 {
+        return Ok(None);
+    }
+    if !buf.ends_with(b"\n") && n as u64 == MAX_LINE_BYTES {
+        anyhow::bail!("line too long");
+    }
+    Ok(Some(buf))
+}
+
+/// Run one SMTP session on `stream` until the client quits or disconnects.
+pub async fn handle_session<S>(
+    stream: S,
+    settings: &SmtpServer,
+    handler: &dyn MailHandler,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (r, mut w) = tokio::io::split(stream);
+    let mut r = BufReader::new(r);
+    let host = settings.domain.as_deref().unwrap_or("photoframe");
+    let max_bytes = settings
+        .max_message_bytes
+        .unwrap_or(DEFAULT_MAX_MESSAGE_BYTES);
+    let mut sender: Option<String> = None;
+    let mut mailboxes: Vec<String> = Vec::new();
+
+    reply(&mut w, &format!("220 {host} photoframe ESMTP ready")).await?;
+    while let Some(raw) = read_line(&mut r).await? {
+        let line = String::from_utf8_lossy(&raw);
+        let line = line.trim_end_matches(['\r', '\n']);
+        let verb = line
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        match verb.as_str() {
+            "EHLO" => {
+                reply(&mut w, &format!("250-{host}")).await?;
+                reply(&mut w, &format!("250-SIZE {max_bytes}")).await?;
+                reply(&mut w, "250-8BITMIME").await?;
+                reply(&mut w, "250 OK").await?;
+            }
+            "HELO" => reply(&mut w, &format!("250 {host}")).await?,
+            "MAIL" => {
+                let Some((addr, params)) = strip_command(line, "MAIL FROM:").and_then(parse_path)
+                else {
+                    reply(&mut w, "501 5.5.4 Syntax: MAIL FROM:<address>").await?;
+                    continue;
+                };
+                let declared_size = params
+                    .split_whitespace()
+                    .find_map(|p| strip_command(p, "SIZE=").and_then(|n| n.parse::<usize>().ok()));
+                if declared_size.is_some_and(|n| n > max_bytes) {
+                    reply(&mut w, "552 5.3.4 Message too big").await?;
+                } else if !sender_allowed(&settings.allowed_senders, addr) {
+                    tracing::warn!(sender = %addr, "refusing mail from sender not on allowlist");
+                    reply(&mut w, "550 5.7.1 Sender not allowed").await?;
+                } else {
+                    sender = Some(addr.to_string());
+                    mailboxes.clear();
+                    reply(&mut w, "250 OK").await?;
+                }
+            }
+            "RCPT" => {
+                if sender.is_none() {
+                    reply(&mut w, "503 5.5.1 MAIL first").await?;
+                    continue;
+                }
+                let Some((addr, _)) = strip_command(line, "RCPT TO:").and_then(parse_path) else {
+                    reply(&mut w, "501 5.5.4 Syntax: RCPT TO:<address>").await?;
+                    continue;
+                };
+                let Some((local, domain)) = addr.rsplit_once('@') else {
+                    reply(&mut w, "501 5.1.3 Bad recipient address").await?;
+                    continue;
+                };
+                if settings
+                    .domain
+                    .as_deref()
+                    .is_some_and(|d| !d.eq_ignore_ascii_case(domain))
+                {
+                    reply(&mut w, "550 5.1.2 Domain not served here").await?;
+                    continue;
+                }
+                let Some(mailbox) = mailbox_with_secret(local, settings.secret.as_deref()) else {
+                    reply(&mut w, "550 5.7.1 Recipient not allowed").await?;
+                    continue;
+                };
+                if mailboxes.len() >= MAX_RECIPIENTS {
+                    reply(&mut w, "452 4.5.3 Too many recipients").await?;
+                } else if !handler.accepts_mailbox(mailbox).await {
+                    reply(&mut w, "550 5.1.1 No such mailbox").await?;
+                } else {
+                    if !mailboxes.iter().any(|m| m == mailbox) {
+                        mailboxes.push(mailbox.to_string());
+                    }
+                    reply(&mut w, "250 OK").await?;
+                }
+            }
+            "DATA" => {
+                let Some(from) = sender.clone().filter(|_| !mailboxes.is_empty()) else {
+                    reply(&mut w, "503 5.5.1 RCPT first").await?;
+                    continue;
+                };
+                reply(&mut w, "354 End data with <CR><LF>.<CR><LF>").await?;
+                let mut data = Vec::new();
+                let mut too_big = false;
+                loop {
+                    let Some(raw) = read_line(&mut r).await? else {
+                        return Ok(());
+                    };
+                    if raw == b".\r\n" || raw == b".\n" {
+                        break;
+                    }
+                    // Undo dot-stuffing.
+                    let raw = raw.strip_prefix(b".").unwrap_or(&raw);
+                    if data.len() + raw.len() > max_bytes {
+                        too_big = true;
+                    } else if !too_big {
+                        data.extend_from_slice(raw);
+                    }
+                }
+                let mail = IncomingMail {
+                    sender: from,
+                    mailboxes: std::mem::take(&mut mailboxes),
+                    data,
+                };
+                sender = None;
+                if too_big {
+                    reply(&mut w, "552 5.3.4 Message too big").await?;
+                    continue;
+                }
+                match handler.deliver(mail).await {
+                    Ok(0) => reply(&mut w, "554 5.6.0 No image attachments found").await?,
+                    Ok(_) => reply(&mut w, "250 OK").await?,
+                    Err(e) => {
+                        tracing::warn!(error = %format!("{e:#}"), "delivering mail failed");
+                        reply(&mut w, "451 4.3.0 Delivery failed, try again later").await?;
+                    }
+                }
+            }
+            "RSET" => {
+                sender = None;
+                mailboxes.clear();
+                reply(&mut w, "250 OK").await?;
+            }
+            "NOOP" => reply(&mut w, "250 OK").await?,
+            "VRFY" => reply(&mut w, "252 2.5.0 Cannot verify").await?,
+            "QUIT" => {
+                reply(&mut w, &format!("221 {host} closing")).await?;
+                return Ok(());
+            }
+            _ => reply(&mut w, "502 5.5.2 Command not implemented").await?,
+        }
+    }
+    Ok(())
+}
+
+/// Delivers mail to frames and directories according to the `[smtp]` config.
+pub struct FrameMailHandler {
+    pub cfg: config::SharedConfig,
+    pub scheduler: Arc<FrameScheduler>,
+}
+
+impl FrameMailHandler {
+    /// Mailbox settings for `name`: its `[smtp.mailboxes]` entry, or a bare mailbox
+    /// showing on the frame of the same id.
+    async fn mailbox(&self, name: &str) -> Option<SmtpMailbox> {
+        let cfg = config::ConfigManager::to_struct(&self.cfg).await.ok()?;
+        if let Some(mb) = cfg.smtp.as_ref().and_then(|s| s.mailboxes.get(name)) {
+            return Some(mb.clone());
+        }
+        cfg.photoframes
+            .contains_key(name)
+            .then(SmtpMailbox::default)
+    }
+
+    async fn save_images(
+        &self,
+        mailbox: &str,
+        mb: &SmtpMailbox,
+        dir: &str,
+        images: &[MailImage],
+    ) -> Result<()> {
+        let dir = std::path::Path::new(dir);
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("creating {}", dir.display()))?;
+        let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+        for (i, img) in images.iter().enumerate() {
+            let stem: String = img
+                .name
+                .as_deref()
+                .and_then(|n| std::path::Path::new(n).file_stem())
+                .map(|s| s.to_string_lossy())
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+                .take(40)
+                .collect();
+            let ext = img
+                .format
+                .extensions_str()
+                .first()
+                .copied()
+                .unwrap_or("img");
+            let path = dir.join(format!("{stamp}_{i}_{stem}.{ext}"));
+            tokio::fs::write(&path, &img.bytes)
+                .await
+                .with_context(|| format!("writing {}", path.display()))?;
+            tracing::info!(mailbox = %mailbox, path = %path.display(), "saved emailed image");
+        }
+        if let Some(source_id) = &mb.refresh_source {
+            match self.scheduler.source(source_id).await {
+                Some(src) => {
+                    if let Err(e) = src.refresh().await {
+                        tracing::warn!(mailbox = %mailbox, source = %source_id, error = %e, "refreshing source after email failed");
+                    }
+                }
+                None => {
+                    tracing::warn!(mailbox = %mailbox, source = %source_id, "refresh_source names an unknown source")
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn show_image(
+        &self,
+        mailbox: &str,
+        mb: &SmtpMailbox,
+        image: &MailImage,
+        metadata: &serde_json::Value,
+    ) -> Result<()> {
+        let frame_id = mb.frame.as_deref().unwrap_or(mailbox);
+        let (w, h) =
+            image::ImageReader::with_format(std::io::Cursor::new(&image.bytes), image.format)
+                .into_dimensions()?;
+        let meta = ImageMeta {
+            data: SourceData::Bytes(image.bytes.clone()),
+            orientation: config::Orientation::from_dims(w, h),
+            date_taken: crate::frame::extract_exif_date_taken(&image.bytes)
+                .ok()
+                .flatten(),
+            exif_blob: None, // Extracted from the bytes when loading
+            id: None,
+            source_id: None,
+            asset_metadata: Some(metadata.clone()),
+        };
+        if !self.scheduler.show_image(frame_id, &meta).await? {
+            anyhow::bail!("mailbox {mailbox} points at unknown frame {frame_id}");
+        }
+        tracing::info!(mailbox = %mailbox, frame = %frame_id, "showing emailed image");
+        if let Some(raw) = &mb.duration {
+            match crate::overrides::end_after(raw, chrono::Utc::now()) {
+                Ok(until) => {
+                    self.scheduler
+                        .set_override(frame_id, until, None, None)
+                        .await?;
+                }
+                Err(e) => {
+                    tracing::warn!(mailbox = %mailbox, error = %e, "invalid mailbox duration")
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MailHandler for FrameMailHandler {
+    async fn accepts_mailbox(&self, mailbox: &str) -> bool {
+        self.mailbox(mailbox).await.is_some()
+    }
+
+    async fn deliver(&self, mail: IncomingMail) -> Result<usize> {
+        let images = extract_images(&mail.data);
+        let subject = MessageParser::default()
+            .parse_headers(&mail.data)
+            .and_then(|m| m.subject().map(str::to_string));
+        tracing::info!(sender = %mail.sender, mailboxes = ?mail.mailboxes, subject = ?subject, images = images.len(), "received mail");
+        if images.is_empty() {
+            return Ok(0);
+        }
+        let metadata = serde_json::json!({ "from": mail.sender, "subject": subject });
+        for name in &mail.mailboxes {
+            let Some(mb) = self.mailbox(name).await else {
+                tracing::warn!(mailbox = %name, "mailbox disappeared before delivery");
+                continue;
+            };
+            if let Some(dir) = &mb.save_dir {
+                self.save_images(name, &mb, dir, &images).await?;
+            } else {
+                if images.len() > 1 {
+                    tracing::info!(mailbox = %name, ignored = images.len() - 1, "only the first emailed image is shown");
+                }
+                self.show_image(name, &mb, &images[0], &metadata).await?;
+            }
+        }
+        Ok(images.len())
+    }
+}
+
+/// Accept SMTP connections until the listener fails. Settings other than the bind
+/// address are re-read from the config for every connection.
+pub async fn serve(
+    cfg: config::SharedConfig,
+    scheduler: Arc<FrameScheduler>,
+    settings: SmtpServer,
+) -> Result<()> {
+    let bind = settings.bind_address.as_deref().unwrap_or(DEFAULT_BIND);
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("binding SMTP listener to {bind}"))?;
+    if settings.secret.as_deref().is_none_or(str::is_empty) {
+        tracing::warn!("smtp.secret is not set; all mail will be refused");
+    }
+    if settings.allowed_senders.is_empty() {
+        tracing::warn!("smtp.allowed_senders is empty; all mail will be refused");
+    }
+    tracing::info!(addr = %bind, "SMTP listener started");
+    let handler = Arc::new(FrameMailHandler {
+        cfg: Arc::clone(&cfg),
+        scheduler,
+    });
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let handler = Arc::clone(&handler);
+        let cfg = Arc::clone(&cfg);
+        let fallback = settings.clone();
+        tokio::spawn(async move {
+            let settings = config::ConfigManager::to_struct(&cfg)
+                .await
+                .ok()
+                .and_then(|c| c.smtp)
+                .unwrap_or(fallback);
+            tracing::debug!(peer = %peer, "SMTP connection");
+            if let Err(e) = handle_session(stream, &settings, handler.as_ref()).await {
+                tracing::debug!(peer = %peer, error = %e, "SMTP session ended with error");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(parking_lot::Mutex<Vec<IncomingMail>>);
+
+    #[async_trait]
+    impl MailHandler for Recorder {
+        async fn accepts_mailbox(&self, mailbox: &str) -> bool {
+            mailbox == "livingroom"
+        }
+
+        async fn deliver(&self, mail: IncomingMail) -> Result<usize> {
+            let n = extract_images(&mail.data).len();
+            self.0.lock().push(mail);
+            Ok(n)
+        }
+    }
+
+    fn png_base64() -> String {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD.encode(crate::sources::test_support::png(4, 3))
+    }
+
+    #[tokio::test]
+    async fn session_accepts_allowed_mail_with_image() {
+        let settings = SmtpServer {
+            domain: Some("frames.local".into()),
+            secret: Some("s3cret".into()),
+            allowed_senders: vec!["grandma@example.com".into(), "@family.example".into()],
+            ..Default::default()
+        };
+        let handler = Recorder::default();
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let message = format!(
+            "From: Grandma <grandma@example.com>\r\nSubject: Garden\r\nMIME-Version: 1.0\r\n\
+             Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n--b\r\n\
+             Content-Type: text/plain\r\n\r\n..look at this\r\n--b\r\n\
+             Content-Type: image/png; name=\"garden.png\"\r\n\
+             Content-Disposition: attachment; filename=\"garden.png\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n{}\r\n--b--\r\n",
+            png_base64()
+        );
+        let script = format!(
+            "EHLO client\r\nMAIL FROM:<eve@example.com>\r\nMAIL FROM:<Grandma@Example.com> SIZE=100\r\n\
+             RCPT TO:<kitchen+s3cret@frames.local>\r\nRCPT TO:<livingroom+s3cret@elsewhere.org>\r\n\
+             RCPT TO:<livingroom@frames.local>\r\nRCPT TO:<livingroom+guess@frames.local>\r\n\
+             RCPT TO:<livingroom+s3cret@frames.local>\r\nDATA\r\n{message}.\r\nQUIT\r\n"
+        );
+        let (mut cr, mut cw) = tokio::io::split(client);
+        let session = handle_session(server, &settings, &handler);
+        let talk = async {
+            cw.write_all(script.as_bytes()).await.unwrap();
+            let mut out = String::new();
+            cr.read_to_string(&mut out).await.unwrap();
+            out
+        };
+        let (result, out) = tokio::join!(session, talk);
+        result.unwrap();
+        let codes: Vec<&str> = out.lines().map(|l| &l[..3]).collect();
+        assert_eq!(
+            codes,
+            [
+                "220", "250", "250", "250", "250", "550", "250", "550", "550", "550", "550", "250",
+                "354", "250", "221"
+            ]
+        );
+
+        let mails = handler.0.lock();
+        assert_eq!(mails.len(), 1);
+        assert_eq!(mails[0].sender, "Grandma@Example.com");
+        assert_eq!(mails[0].mailboxes, ["livingroom"]);
+        let images = extract_images(&mails[0].data);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].name.as_deref(), Some("garden.png"));
+        assert_eq!(images[0].format, image::ImageFormat::Png);
+        // Dot-stuffed body lines are restored.
+        assert!(String::from_utf8_lossy(&mails[0].data).contains("\r\n.look at this"));
+    }
+
+    #[test]
+    fn recipient_needs_the_secret() {
+        let secret = Some("s3cret");
+        assert_eq!(
+            mailbox_with_secret("livingroom+s3cret", secret),
+            Some("livingroom")
+        );
+        assert_eq!(mailbox_with_secret("a+b+s3cret", secret), Some("a+b"));
+        assert_eq!(mailbox_with_secret("livingroom", secret), None);
+        assert_eq!(mailbox_with_secret("livingroom+s3cre", secret), None);
+        assert_eq!(mailbox_with_secret("+s3cret", secret), None);
+        assert_eq!(mailbox_with_secret("livingroom+", None), None);
+        assert_eq!(mailbox_with_secret("livingroom+", Some("")), None);
+    }
+
+    #[test]
+    fn matches_sender_allowlist() {
+        let allowed = vec!["Grandma@example.com".to_string(), "@family.example".into()];
+        assert!(sender_allowed(&allowed, "grandma@EXAMPLE.com"));
+        assert!(sender_allowed(&allowed, "uncle@family.example"));
+        assert!(!sender_allowed(&allowed, "uncle@notfamily.example.org"));
+        assert!(!sender_allowed(&allowed, "eve@example.com"));
+        assert!(!sender_allowed(&[], "grandma@example.com"));
+    }
+}
@@ -1,16 +1,45 @@
-use crate::config::{FilesystemSource, ImmichSource, OrderKind, Orientation, Source};
+use crate::config::{FilesystemSource, ImageLimits, OrderKind, Orientation, PhotoFrame, Source};
 use anyhow::{Context, Result, bail};
 use async_trait::async_trait;
 use glob::glob;
-use image::ImageDecoder;
+use rand::rng;
 use rand::seq::{IndexedRandom, SliceRandom};
-use rand::{Rng, rng};
-use std::any::Any;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicU64;
 use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
-use std::time::{SystemTime, UNIX_EPOCH};
+
+mod command;
+mod composite;
+mod google_photos;
+mod http;
+mod immich;
+mod inbox;
+mod photoprism;
+mod remote;
+mod s3;
+mod shuffle;
+#[cfg(test)]
+pub(crate) mod test_support;
+mod webdav;
+pub use command::CommandImageSource;
+pub use composite::{CompositeEntry, CompositeImageSource, combine};
+pub use google_photos::{
+    DeviceFlowStatus, GoogleEndpoints, GooglePhotosImageSource, PickerSession,
+    create_picker_session, device_flow_status, finish_device_flow, set_device_flow_status,
+    start_device_flow,
+};
+pub use http::HttpImageSource;
+pub use immich::{
+    ImmichImageSource, immich_asset_date, immich_asset_orientation, immich_exif_date_taken,
+};
+pub use inbox::{
+    InboxImageSource, InboxItem, delete_inbox_item, inbox_dir, list_inbox_items, save_inbox_upload,
+};
+pub use photoprism::PhotoprismImageSource;
+pub use s3::S3ImageSource;
+pub use shuffle::{ShuffleBag, ShuffleBagStore};
+pub use webdav::WebdavImageSource;
 
 #[derive(Debug, Clone)]
 pub enum SourceData {
@@ -64,30 +93,221 @@ pub struct SourceStats {
 
 impl SourceStats {
     pub fn from_entries(entries: &[ImageMeta]) -> Self {
-        let total = entries.len();
-        let mut landscape = 0;
-        let mut portrait = 0;
-        for e in entries {
-            match e.orientation {
-                Orientation::Landscape => landscape += 1,
-                Orientation::Portrait => portrait += 1,
+        Self::from_orientations(entries.iter().map(|e| e.orientation))
+    }
+
+    /// Counts for a source that knows its images' orientations without loading them.
+    pub fn from_orientations(orientations: impl IntoIterator<Item = Orientation>) -> Self {
+        let mut stats = SourceStats::default();
+        for orientation in orientations {
+            stats.total += 1;
+            match orientation {
+                Orientation::Landscape => stats.landscape += 1,
+                Orientation::Portrait => stats.portrait += 1,
             }
         }
+        stats
+    }
+}
+
+/// Per-call context describing which frame is asking for an image and what it needs.
+#[derive(Debug, Clone, Default)]
+pub struct SelectionContext {
+    pub frame_id: String,
+    pub orientation: Orientation,
+    /// Asset ids that must not be returned (e.g. shown recently on this frame).
+    pub exclude: std::collections::HashSet<String>,
+    /// Blacklisted asset ids. Unlike `exclude` these are never relaxed.
+    pub blacklist: std::collections::HashSet<String>,
+    /// Longest image edge in pixels the frame can make use of, if known. Sources with
+    /// several renditions use it to avoid fetching more (or fewer) pixels than needed.
+    pub max_dimension: Option<u32>,
+}
+
+impl SelectionContext {
+    /// Context for `frame_id` with no exclusions. The useful size is the panel's long
+    /// edge, capped by `image_limits` when both of its dimensions are set.
+    pub fn for_frame(frame_id: &str, f: &PhotoFrame, limits: Option<&ImageLimits>) -> Self {
+        let panel = f.panel_width.max(f.panel_height);
+        let limit = limits.and_then(|l| Some(l.max_width?.max(l.max_height?)));
+        let max_dimension = match (panel, limit) {
+            (Some(p), Some(l)) => Some(p.min(l)),
+            (p, _) => p,
+        };
+        Self {
+            frame_id: frame_id.to_string(),
+            orientation: f.orientation.unwrap_or_default(),
+            exclude: std::collections::HashSet::new(),
+            blacklist: std::collections::HashSet::new(),
+            max_dimension,
+        }
+    }
+
+    /// Whether an asset with this id and orientation may be returned for this request.
+    pub fn accepts(&self, id: &str, orientation: Orientation) -> bool {
+        orientation == self.orientation
+            && !self.exclude.contains(id)
+            && !self.blacklist.contains(id)
+    }
+}
+
+/// Lightweight description of one asset in a source listing.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetSummary {
+    pub id: String,
+    pub orientation: Orientation,
+    pub date_taken: Option<chrono::DateTime<chrono::Utc>>,
+    /// Content checksum, for sources whose API reports one (Immich). Lets composite
+    /// sources recognize the same photo across sources.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+/// Pagination window and filters for [`ImageSource::list`].
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    pub offset: usize,
+    pub limit: usize,
+    pub orientation: Option<Orientation>,
+    /// Only assets taken at or after this instant (assets without a date are excluded).
+    pub taken_after: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only assets taken before this instant (assets without a date are excluded).
+    pub taken_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ListQuery {
+    pub fn matches(&self, asset: &AssetSummary) -> bool {
+        if self.orientation.is_some_and(|o| o != asset.orientation) {
+            return false;
+        }
+        if self.taken_after.is_none() && self.taken_before.is_none() {
+            return true;
+        }
+        let Some(taken) = asset.date_taken else {
+            return false;
+        };
+        self.taken_after.is_none_or(|after| taken >= after)
+            && self.taken_before.is_none_or(|before| taken < before)
+    }
+}
 
-        SourceStats {
-            total,
-            landscape,
-            portrait,
+/// One page of a source listing plus the total number of assets available.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetPage {
+    pub total: usize,
+    pub items: Vec<AssetSummary>,
+}
+
+impl AssetPage {
+    /// Filter and slice an already materialized listing according to `query`.
+    pub fn from_summaries(all: Vec<AssetSummary>, query: &ListQuery) -> Self {
+        let matching: Vec<AssetSummary> = all.into_iter().filter(|a| query.matches(a)).collect();
+        let total = matching.len();
+        let items = matching
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit)
+            .collect();
+        Self { total, items }
+    }
+}
+
+/// Longest edge of thumbnails rendered for browsing.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Encoded preview image for browsing UIs.
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    pub bytes: Vec<u8>,
+    pub content_type: String,
+}
+
+/// Result of a source health probe.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceHealth {
+    pub healthy: bool,
+    /// Human readable explanation, mostly useful when unhealthy.
+    pub detail: Option<String>,
+    /// When the source last refreshed its listing, if it keeps one.
+    pub last_refresh: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl SourceHealth {
+    pub fn healthy(last_refresh: Option<chrono::DateTime<chrono::Utc>>) -> Self {
+        Self {
+            healthy: true,
+            detail: None,
+            last_refresh,
+        }
+    }
+
+    pub fn unhealthy(
+        detail: impl Into<String>,
+        last_refresh: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Self {
+        Self {
+            healthy: false,
+            detail: Some(detail.into()),
+            last_refresh,
         }
     }
 }
 
+/// Write-back action on an asset in the library it came from.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AssetAction {
+    Favorite,
+    /// Add to the named album (created if missing); defaults to "Frame favorites".
+    AddToAlbum {
+        #[serde(default)]
+        album: Option<String>,
+    },
+    Archive,
+}
+
+/// A built source shared between the scheduler and composite sources.
+pub type SharedImageSource = std::sync::Arc<Box<dyn ImageSource>>;
+
 /// Unified trait for any image source.
 #[async_trait]
-pub trait ImageSource: Send + Sync + Any {
-    /// Return the next image whose orientation matches or `Ok(None)` if none
-    /// can be produced right now. Implementors should make a bounded effort per call.
-    async fn next(&self, desired: Orientation) -> Result<Option<ImageMeta>>;
+pub trait ImageSource: Send + Sync {
+    /// Return the next image for the requesting frame whose orientation matches, or
+    /// `Ok(None)` if none can be produced right now. Implementors should make a bounded
+    /// effort per call.
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>>;
+    /// Re-read the source's listing now instead of waiting for its own refresh cadence.
+    async fn refresh(&self) -> Result<()> {
+        Ok(())
+    }
+    /// Enumerate the source's assets in a stable order.
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage>;
+    /// Fetch one specific asset by id, or `Ok(None)` if the source doesn't know it. `ctx`
+    /// describes the requesting frame; orientation and exclusions are not enforced.
+    async fn get(&self, id: &str, ctx: &SelectionContext) -> Result<Option<ImageMeta>>;
+    /// Check whether the source is currently usable (reachable, credentials valid, ...).
+    async fn health(&self) -> SourceHealth;
+    /// Small preview of an asset for browsing. The default fetches the asset via `get`
+    /// and renders a JPEG thumbnail locally.
+    async fn thumbnail(&self, id: &str) -> Result<Option<Thumbnail>> {
+        let ctx = SelectionContext {
+            max_dimension: Some(THUMBNAIL_SIZE),
+            ..Default::default()
+        };
+        let Some(meta) = self.get(id, &ctx).await? else {
+            return Ok(None);
+        };
+        let bytes = crate::frame::render_thumbnail(&meta, THUMBNAIL_SIZE).await?;
+        Ok(Some(Thumbnail {
+            bytes,
+            content_type: "image/jpeg".to_string(),
+        }))
+    }
+    /// Apply a write-back action to an asset upstream. Returns `Ok(false)` when the
+    /// source doesn't support it.
+    async fn apply_action(&self, _id: &str, _action: &AssetAction) -> Result<bool> {
+        Ok(false)
+    }
     /// Lightweight stat snapshot (override where meaningful).
     fn stats(&self) -> SourceStats {
         SourceStats::default()
@@ -98,19 +318,51 @@ pub trait ImageSource: Send + Sync + Any {
     fn remove_asset_from_cache(&self, _asset_id: &str) -> Result<bool> {
         Ok(false)
     }
+    /// For sources combining others, the id of the member source an asset comes from.
+    fn asset_owner(&self, _asset_id: &str) -> Option<String> {
+        None
+    }
+    /// For sources combining others, the ids of the member sources.
+    fn member_ids(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// Whether `get` and `next` deliver the same bytes for an asset whatever the requesting
+    /// frame's size, so a content hash taken for one frame matches on every other. Sources
+    /// that download a rendition sized for the frame return false.
+    fn serves_originals(&self) -> bool {
+        false
+    }
 }
 
-/// Filesystem implementation (simple, scans once then picks according to order).
+/// Filesystem implementation (scans the glob up front, rescans on refresh, then picks
+/// according to order).
 pub struct FilesystemImageSource {
-    pub entries: Vec<ImageMeta>,
+    pub cfg: FilesystemSource,
+    pub entries: parking_lot::RwLock<Vec<ImageMeta>>,
     pub order: OrderKind,
     pub cursor: AtomicUsize,
+    last_scan: parking_lot::RwLock<Option<chrono::DateTime<chrono::Utc>>>,
+    bags: ShuffleBagStore,
 }
 
 impl FilesystemImageSource {
     /// Build a filesystem image source by eagerly expanding the configured glob
     /// and caching dimension-derived orientation metadata in memory.
-    pub fn new(cfg: &FilesystemSource) -> Result<Self> {
+    pub fn new(id: &str, cfg: &FilesystemSource) -> Result<Self> {
+        let order = cfg.order.unwrap_or_default();
+        let entries = Self::scan(cfg)?;
+        Ok(Self {
+            cfg: cfg.clone(),
+            entries: parking_lot::RwLock::new(entries),
+            order,
+            cursor: AtomicUsize::new(0),
+            last_scan: parking_lot::RwLock::new(Some(chrono::Utc::now())),
+            bags: ShuffleBagStore::new(id),
+        })
+    }
+
+    /// Expand the glob and read image dimensions for every match.
+    fn scan(cfg: &FilesystemSource) -> Result<Vec<ImageMeta>> {
         let glob_pat = cfg
             .glob
             .clone()
@@ -125,8 +377,9 @@ impl FilesystemImageSource {
                         entries.push(ImageMeta {
                             data: SourceData::Path(path.clone()),
                             orientation: orient,
-                            date_taken: None, // Filesystem source doesn't extract EXIF during listing
-                            exif_blob: None,  // Will be extracted when loading the file
+                            // Only the EXIF header is parsed here; the full blob is read on load.
+                            date_taken: crate::frame::read_file_date_taken(&path),
+                            exif_blob: None, // Will be extracted when loading the file
                             id: Some(path.to_string_lossy().to_string()),
                             source_id: None, // Will be set when returning from next()
                             asset_metadata: None,
@@ -150,33 +403,29 @@ impl FilesystemImageSource {
             }
         }
         tracing::info!(pattern = %glob_pat, total = entries.len(), landscape = l, portrait = p, "filesystem source loaded");
-        let order = cfg.order.unwrap_or_default();
-        if matches!(order, OrderKind::Random) {
+        if matches!(cfg.order.unwrap_or_default(), OrderKind::Random) {
             let mut rng = rng();
             entries.shuffle(&mut rng);
         }
-        Ok(Self {
-            entries,
-            order,
-            cursor: AtomicUsize::new(0),
-        })
+        Ok(entries)
     }
 }
 
 #[async_trait]
 impl ImageSource for FilesystemImageSource {
-    async fn next(&self, desired: Orientation) -> Result<Option<ImageMeta>> {
-        if self.entries.is_empty() {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        if self.entries.read().is_empty() {
             return Ok(None);
         }
         match self.order {
             OrderKind::Sequential => {
-                let total = self.entries.len();
+                let entries = self.entries.read();
+                let total = entries.len();
                 let start = self.cursor.fetch_add(1, AtomicOrdering::Relaxed);
                 for offset in 0..total {
                     let idx = (start + offset) % total;
-                    let item = &self.entries[idx];
-                    if item.orientation == desired {
+                    let item = &entries[idx];
+                    if ctx.accepts(item.id.as_deref().unwrap_or_default(), item.orientation) {
                         // advance cursor to after this idx (already incremented once above, so add remaining offset)
                         if offset > 0 {
                             self.cursor.fetch_add(offset, AtomicOrdering::Relaxed);
@@ -188,473 +437,253 @@ impl ImageSource for FilesystemImageSource {
             }
             OrderKind::Random => {
                 // random sample until match or attempts exhausted
+                let entries = self.entries.read();
                 let mut rng = rng();
-                for _ in 0..std::cmp::min(32, self.entries.len()) {
-                    if let Some(item) = self
-                        .entries
+                for _ in 0..std::cmp::min(32, entries.len()) {
+                    if let Some(item) = entries
                         .choose(&mut rng)
-                        .filter(|i| i.orientation == desired)
+                        .filter(|i| ctx.accepts(i.id.as_deref().unwrap_or_default(), i.orientation))
                     {
                         return Ok(Some(item.clone()));
                     }
                 }
                 Ok(None)
             }
+            OrderKind::Shuffle => {
+                // The listing lock can't be held while the bag is loaded.
+                let orientations: std::collections::HashMap<String, Orientation> = self
+                    .entries
+                    .read()
+                    .iter()
+                    .filter_map(|e| e.id.clone().map(|id| (id, e.orientation)))
+                    .collect();
+                let ids: Vec<&str> = orientations.keys().map(String::as_str).collect();
+                let drawn = self
+                    .bags
+                    .draw(&ctx.frame_id, &ids, |id| {
+                        orientations.get(id).is_some_and(|o| ctx.accepts(id, *o))
+                    })
+                    .await;
+                Ok(drawn.and_then(|id| {
+                    self.entries
+                        .read()
+                        .iter()
+                        .find(|e| e.id.as_deref() == Some(id.as_str()))
+                        .cloned()
+                }))
+            }
         }
     }
 
-    fn stats(&self) -> SourceStats {
-        SourceStats::from_entries(&self.entries)
+    async fn refresh(&self) -> Result<()> {
+        let cfg = self.cfg.clone();
+        // Reading dimensions touches every file; keep it off the async workers.
+        let entries = tokio::task::spawn_blocking(move || Self::scan(&cfg)).await??;
+        *self.entries.write() = entries;
+        *self.last_scan.write() = Some(chrono::Utc::now());
+        Ok(())
     }
-}
-
-pub struct ImmichImageSource {
-    pub cfg: ImmichSource,
-    pub entries: parking_lot::RwLock<Vec<(String, Orientation, serde_json::Value)>>, // asset_id + orientation + full metadata
-    pub last_list: AtomicU64, // unix seconds of last listing, 0 = never
-    pub cursor: AtomicUsize,  // for sequential order
-}
 
-impl ImmichImageSource {
-    pub fn new(cfg: &ImmichSource) -> Result<Self> {
-        Ok(Self {
-            cfg: cfg.clone(),
-            entries: parking_lot::RwLock::new(Vec::new()),
-            last_list: AtomicU64::new(0),
-            cursor: AtomicUsize::new(0),
-        })
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        let mut all: Vec<AssetSummary> = self
+            .entries
+            .read()
+            .iter()
+            .filter_map(|e| {
+                Some(AssetSummary {
+                    checksum: None,
+                    id: e.id.clone()?,
+                    orientation: e.orientation,
+                    date_taken: e.date_taken,
+                })
+            })
+            .collect();
+        all.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(AssetPage::from_summaries(all, query))
     }
 
-    pub async fn refresh(&self) -> Result<()> {
-        // force next call to list to actually list now
-        self.last_list
-            .store(0, std::sync::atomic::Ordering::Relaxed);
-        self.list_if_needed().await
+    async fn get(&self, id: &str, _ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        Ok(self
+            .entries
+            .read()
+            .iter()
+            .find(|e| e.id.as_deref() == Some(id))
+            .cloned())
     }
 
-    async fn list_if_needed(&self) -> Result<()> {
-        if self.cfg.base_url.is_none() {
-            return Ok(());
-        }
-        let mut should = false;
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        const IMMICH_REFRESH_INTERVAL_SECS: u64 = 86_400; // 24h
-        let last = self.last_list.load(std::sync::atomic::Ordering::Relaxed);
-        if last == 0 || now.saturating_sub(last) > IMMICH_REFRESH_INTERVAL_SECS {
-            should = true;
-        }
-        if !should {
-            return Ok(());
-        }
-
-        let client = reqwest::Client::new();
-        let base = self.cfg.base_url.clone().unwrap();
-        let url = format!("{}/api/search/metadata", base.trim_end_matches('/'));
-
-        // Handle multiple filters by performing multiple searches and deduplicating results
-        let empty_filters = vec![];
-        let filters_list = self.cfg.filters.as_ref().unwrap_or(&empty_filters);
-
-        let mut all_entries: Vec<(String, Orientation, serde_json::Value)> = Vec::new();
-        let mut seen_ids = std::collections::HashSet::new();
-
-        // If no filters are configured, perform a single search with just type=IMAGE
-        let searches = if filters_list.is_empty() {
-            vec![serde_json::Value::Object(serde_json::Map::new())]
-        } else {
-            filters_list.clone()
-        };
-
-        for (filter_idx, filter) in searches.iter().enumerate() {
-            tracing::debug!(?filter, filter_idx, "Starting Immich search for filter");
-
-            // Build filters body: merge user-provided filters (object) + enforced type=IMAGE.
-            // We'll loop over pages and update the page field each iteration.
-            let mut base = serde_json::Map::new();
-            if let Some(obj) = filter.as_object() {
-                for (k, v) in obj.iter() {
-                    base.insert(k.clone(), v.clone());
-                }
-            }
-            base.insert(
-                "type".to_string(),
-                serde_json::Value::String("IMAGE".to_string()),
-            );
-
-            // Defaults
-            // Use a generic page token to support cursor-based pagination (nextPage: String|null).
-            // If the filter specified an explicit page, start from there; otherwise omit 'page' on first call.
-            let mut page_token: Option<serde_json::Value> = base.get("page").cloned();
-            let size: u32 = base.get("size").and_then(|v| v.as_u64()).unwrap_or(1000) as u32;
-            if !base.contains_key("withExif") {
-                base.insert("withExif".to_string(), serde_json::Value::Bool(true));
-            }
-
-            let max_pages = self.cfg.max_pages.unwrap_or(1).max(1);
-            let mut fetched_pages: u32 = 0;
-            let mut total_assets_for_filter = 0;
-
-            loop {
-                if fetched_pages >= max_pages {
-                    tracing::trace!(
-                        filter_idx,
-                        fetched_pages,
-                        max_pages,
-                        "Reached maximum pages limit for filter"
-                    );
-                    break;
-                }
-                let mut body_map = base.clone();
-                // Only include 'page' when we have a token; otherwise let API start from first page.
-                if let Some(tok) = &page_token {
-                    body_map.insert("page".to_string(), tok.clone());
-                } else {
-                    body_map.remove("page");
-                }
-                body_map.insert("size".to_string(), serde_json::Value::Number(size.into()));
-                let body = serde_json::Value::Object(body_map);
-
-                tracing::trace!(
-                    filter_idx,
-                    page_num = fetched_pages + 1,
-                    ?page_token,
-                    size,
-                    total_assets_found = all_entries.len(),
-                    "Fetching Immich assets page"
-                );
-
-                let resp = client
-                    .post(&url)
-                    .header("x-api-key", self.cfg.api_key.clone().unwrap_or_default())
-                    .json(&body)
-                    .send()
-                    .await
-                    .context("immich search assets")?;
-
-                if !resp.status().is_success() {
-                    let status = resp.status();
-                    let text = resp.text().await.unwrap_or_default();
-                    tracing::warn!(%status, body=%text, "immich search assets failed for filter");
-                    break; // stop paging for this filter
-                }
-
-                let items = resp.json::<serde_json::Value>().await.unwrap_or_default();
-                let assets_obj = items.get("assets");
-                let arr = assets_obj
-                    .and_then(|v| v.get("items"))
-                    .and_then(|v| v.as_array())
-                    .cloned()
-                    .unwrap_or_else(|| items.as_array().cloned().unwrap_or_default());
-
-                tracing::trace!(
-                    filter_idx,
-                    page_num = fetched_pages + 1,
-                    assets_in_page = arr.len(),
-                    "Received Immich assets page"
-                );
-
-                if arr.is_empty() {
-                    tracing::trace!(
-                        filter_idx,
-                        page_num = fetched_pages + 1,
-                        "No more assets in page, stopping pagination"
-                    );
-                    break; // no more pages
-                }
-
-                let mut new_assets_this_page = 0;
-                let blacklist = &self.cfg.blacklist;
-
-                for item in &arr {
-                    let id = item.get("id").and_then(|v| v.as_str()).unwrap_or("");
-                    if id.is_empty() || seen_ids.contains(id) {
-                        continue; // Skip duplicates
-                    }
-                    if blacklist.contains(&id.to_string()) {
-                        tracing::trace!(asset_id = %id, "Skipping blacklisted asset");
-                        continue; // Skip blacklisted assets
-                    }
-                    seen_ids.insert(id.to_string());
-
-                    let exif = item.get("exifInfo");
-                    let raw_w = exif
-                        .and_then(|m| m.get("exifImageWidth"))
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(0) as u32;
-                    let raw_h = exif
-                        .and_then(|m| m.get("exifImageHeight"))
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(0) as u32;
-                    let exif_orientation = exif
-                        .and_then(|m| m.get("orientation"))
-                        .and_then(|v| {
-                            if let Some(n) = v.as_u64() {
-                                Some(n)
-                            } else if let Some(s) = v.as_str() {
-                                s.trim().parse::<u64>().ok()
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap_or(1);
-                    let (w, h) = match exif_orientation {
-                        6 | 8 => (raw_h, raw_w),
-                        _ => (raw_w, raw_h),
-                    };
-
-                    let orient = if w > 0 && h > 0 {
-                        Orientation::from_dims(w, h)
-                    } else {
-                        Orientation::Landscape
-                    };
-                    // Keep full item metadata so we can avoid fetching it later
-                    all_entries.push((id.to_string(), orient, item.clone()));
-                    new_assets_this_page += 1;
-                    total_assets_for_filter += 1;
-                }
-
-                tracing::debug!(
-                    filter_idx,
-                    page_num = fetched_pages + 1,
-                    new_assets_this_page,
-                    total_unique_assets = all_entries.len(),
-                    total_assets_for_filter,
-                    duplicates_skipped = arr.len() - new_assets_this_page,
-                    "Processed Immich assets page"
-                );
-
-                fetched_pages += 1;
-                // Advance using nextPage token when available; stop if null/missing.
-                let next_page_val = assets_obj
-                    .and_then(|v| v.get("nextPage"))
-                    .cloned()
-                    .or_else(|| items.get("nextPage").cloned());
-                match next_page_val {
-                    Some(v) if !v.is_null() => {
-                        page_token = Some(v);
-                        tracing::trace!(
-                            filter_idx,
-                            page_num = fetched_pages + 1,
-                            ?page_token,
-                            "Found next page token, continuing pagination"
-                        );
-                    }
-                    _ => {
-                        tracing::trace!(
-                            filter_idx,
-                            page_num = fetched_pages + 1,
-                            "No next page token found, stopping pagination"
-                        );
-                        break;
-                    }
-                }
-            }
-
-            tracing::trace!(
-                filter_idx,
-                total_pages_fetched = fetched_pages,
-                total_assets_for_filter,
-                "Completed Immich search for filter"
-            );
+    async fn health(&self) -> SourceHealth {
+        let last_scan = *self.last_scan.read();
+        if self.entries.read().is_empty() {
+            let pattern = self.cfg.glob.clone().unwrap_or_default();
+            return SourceHealth::unhealthy(format!("no images match glob {pattern}"), last_scan);
         }
-
-        tracing::debug!(
-            total_filters = searches.len(),
-            total_unique_assets = all_entries.len(),
-            "Completed all Immich metadata searches"
-        );
-
-        // Only update last_list timestamp on successful completion of all searches
-        *self.entries.write() = all_entries;
-        self.last_list
-            .store(now, std::sync::atomic::Ordering::Relaxed);
-        Ok(())
+        SourceHealth::healthy(last_scan)
     }
-}
 
-#[async_trait]
-impl ImageSource for ImmichImageSource {
-    async fn next(&self, desired: Orientation) -> Result<Option<ImageMeta>> {
-        self.list_if_needed().await.ok();
-        let snapshot: Vec<(String, Orientation, serde_json::Value)> =
-            { self.entries.read().clone() };
-        if snapshot.is_empty() {
-            return Ok(None);
-        }
-        let order = self.cfg.order.unwrap_or_default();
-        match order {
-            OrderKind::Random => {
-                for _ in 0..32 {
-                    let idx = {
-                        let mut rng = rng();
-                        rng.random_range(0..snapshot.len())
-                    };
-                    let (asset_id, orient, metadata) = snapshot[idx].clone();
-                    if orient != desired {
-                        continue;
-                    }
-                    if let Some(meta) = self.fetch_asset(&asset_id, orient, metadata).await? {
-                        return Ok(Some(meta));
-                    }
-                }
-                Ok(None)
-            }
-            OrderKind::Sequential => {
-                let total = snapshot.len();
-                let start = self.cursor.fetch_add(1, AtomicOrdering::Relaxed);
-                for offset in 0..total {
-                    let idx = (start + offset) % total;
-                    let (asset_id, orient, metadata) = &snapshot[idx];
-                    if *orient != desired {
-                        continue;
-                    }
-                    if let Some(meta) = self
-                        .fetch_asset(asset_id, *orient, metadata.clone())
-                        .await?
-                    {
-                        if offset > 0 {
-                            self.cursor.fetch_add(offset, AtomicOrdering::Relaxed);
-                        }
-                        return Ok(Some(meta));
-                    }
-                }
-                Ok(None)
-            }
-        }
+    fn stats(&self) -> SourceStats {
+        SourceStats::from_entries(&self.entries.read())
     }
 
-    fn stats(&self) -> SourceStats {
-        let g = self.entries.read();
-        let metas: Vec<ImageMeta> = g
-            .iter()
-            .map(|(id, o, _meta)| ImageMeta {
-                data: SourceData::Path(PathBuf::from("remote")),
-                orientation: *o,
-                date_taken: None, // Stats don't need actual date data
-                exif_blob: None,  // Stats don't need EXIF data
-                id: Some(id.clone()),
-                source_id: None,
-                asset_metadata: None,
-            })
-            .collect();
-        SourceStats::from_entries(&metas)
+    fn serves_originals(&self) -> bool {
+        true
     }
 
     fn remove_asset_from_cache(&self, asset_id: &str) -> Result<bool> {
         let mut entries = self.entries.write();
         let before = entries.len();
-        entries.retain(|(id, _orient, _meta)| id != asset_id);
-        let after = entries.len();
-        Ok(before != after)
+        entries.retain(|e| e.id.as_deref() != Some(asset_id));
+        Ok(before != entries.len())
     }
 }
 
-impl ImmichImageSource {
-    async fn fetch_asset(
-        &self,
-        asset_id: &str,
-        orient: Orientation,
-        asset_metadata: serde_json::Value,
-    ) -> Result<Option<ImageMeta>> {
-        let client = reqwest::Client::new();
-        let base = self.cfg.base_url.clone().unwrap_or_default();
-
-        // Fetch thumbnail for image data
-        let thumb_url = format!(
-            "{}/api/assets/{}/thumbnail?size=preview",
-            base.trim_end_matches('/'),
-            asset_id
-        );
-        let thumb_resp = client
-            .get(&thumb_url)
-            .header("x-api-key", self.cfg.api_key.clone().unwrap_or_default())
-            .send()
-            .await?;
-
-        if !thumb_resp.status().is_success() {
-            return Ok(None);
+/// Factory creating concrete sources from config enum.
+/// Factory creating a concrete boxed `ImageSource` from a typed config enum value.
+pub fn build_source(id: &str, src: &Source) -> Result<Box<dyn ImageSource>> {
+    match src {
+        Source::Filesystem { filesystem } => {
+            let cfg = filesystem.clone().unwrap_or_default();
+            Ok(Box::new(FilesystemImageSource::new(id, &cfg)?))
+        }
+        Source::Immich { immich } => {
+            let cfg = immich.as_deref().cloned().unwrap_or_default();
+            Ok(Box::new(ImmichImageSource::new(id, &cfg)?))
+        }
+        Source::Webdav { webdav } => {
+            let cfg = webdav.clone().unwrap_or_default();
+            Ok(Box::new(WebdavImageSource::new(id, &cfg)?))
+        }
+        Source::S3 { s3 } => {
+            let cfg = s3.clone().unwrap_or_default();
+            Ok(Box::new(S3ImageSource::new(id, &cfg)?))
+        }
+        Source::Photoprism { photoprism } => {
+            let cfg = photoprism.clone().unwrap_or_default();
+            Ok(Box::new(PhotoprismImageSource::new(id, &cfg)?))
         }
+        Source::GooglePhotos { google_photos } => {
+            let cfg = google_photos.clone().unwrap_or_default();
+            Ok(Box::new(GooglePhotosImageSource::new(id, &cfg)?))
+        }
+        Source::Http { http } => {
+            let cfg = http.clone().unwrap_or_default();
+            Ok(Box::new(HttpImageSource::new(id, &cfg)?))
+        }
+        Source::Command { command } => {
+            let cfg = command.clone().unwrap_or_default();
+            Ok(Box::new(CommandImageSource::new(id, &cfg)?))
+        }
+        Source::Inbox { inbox } => {
+            let cfg = inbox.clone().unwrap_or_default();
+            Ok(Box::new(InboxImageSource::new(id, &cfg)?))
+        }
+        Source::Composite { .. } => {
+            bail!("composite sources are built from their members by the scheduler")
+        }
+        Source::Unknown => bail!("unknown source kind"),
+    }
+}
 
-        let thumb_bytes = thumb_resp.bytes().await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Extract EXIF metadata from original asset (memory-efficient)
-        let (date_taken, exif_blob) = self
-            .extract_exif_metadata(asset_id)
-            .await
-            .unwrap_or((None, None));
+    fn write_png(path: &std::path::Path, w: u32, h: u32) {
+        image::RgbImage::new(w, h).save(path).unwrap();
+    }
 
-        Ok(Some(ImageMeta {
-            data: SourceData::Bytes(thumb_bytes.to_vec()),
-            orientation: orient,
+    #[test]
+    fn list_query_filters_orientation_and_dates() {
+        let day = |d: u32| {
+            chrono::NaiveDate::from_ymd_opt(2024, 1, d)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        let asset = |id: &str, orientation, date_taken| AssetSummary {
+            id: id.to_string(),
+            orientation,
             date_taken,
-            exif_blob,
-            id: Some(asset_id.to_string()),
-            source_id: None, // Will be set when returning from next()
-            asset_metadata: Some(asset_metadata),
-        }))
-    }
+            checksum: None,
+        };
+        let all = vec![
+            asset("a", Orientation::Landscape, Some(day(1))),
+            asset("b", Orientation::Portrait, Some(day(5))),
+            asset("c", Orientation::Landscape, Some(day(10))),
+            asset("undated", Orientation::Landscape, None),
+        ];
+        let ids = |query: ListQuery| {
+            AssetPage::from_summaries(all.clone(), &query)
+                .items
+                .into_iter()
+                .map(|a| a.id)
+                .collect::<Vec<_>>()
+        };
 
-    /// Memory-efficiently extract EXIF metadata from original asset without loading entire image
-    async fn extract_exif_metadata(
-        &self,
-        asset_id: &str,
-    ) -> Result<(Option<chrono::DateTime<chrono::Utc>>, Option<Vec<u8>>)> {
-        let client = reqwest::Client::new();
-        let base = self.cfg.base_url.clone().unwrap_or_default();
-        let original_url = format!(
-            "{}/api/assets/{}/original",
-            base.trim_end_matches('/'),
-            asset_id
+        let landscape = ListQuery {
+            limit: 10,
+            orientation: Some(Orientation::Landscape),
+            ..Default::default()
+        };
+        assert_eq!(ids(landscape), ["a", "c", "undated"]);
+        // Date bounds are [after, before) and leave out undated assets.
+        let window = ListQuery {
+            limit: 10,
+            taken_after: Some(day(5)),
+            taken_before: Some(day(10)),
+            ..Default::default()
+        };
+        assert_eq!(ids(window), ["b"]);
+        let page = AssetPage::from_summaries(
+            all.clone(),
+            &ListQuery {
+                offset: 1,
+                limit: 2,
+                ..Default::default()
+            },
         );
+        assert_eq!(page.total, 4);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.items[0].id, "b");
+    }
 
-        let resp = client
-            .get(&original_url)
-            .header("x-api-key", self.cfg.api_key.clone().unwrap_or_default())
-            .send()
-            .await?;
-
-        if !resp.status().is_success() {
-            return Ok((None, None));
-        }
-
-        // Stream response and extract EXIF without loading entire image
-        use std::io::Cursor;
-        let bytes = resp.bytes().await?;
-        let cursor = Cursor::new(&bytes[..]);
-
-        // Use image crate's ImageReader to extract EXIF metadata efficiently
-        let reader = image::ImageReader::new(cursor).with_guessed_format()?;
-        let mut decoder = reader.into_decoder()?;
-
-        // Extract EXIF metadata without decoding the image pixels
-        if let Some(exif_bytes) = decoder.exif_metadata()? {
-            // Parse EXIF to get DateTimeOriginal while preserving the full blob
-            let exif_vec = exif_bytes.to_vec();
-            let date_taken = crate::frame::extract_exif_date_taken_from_blob(&exif_vec)
-                .ok()
-                .flatten();
-            return Ok((date_taken, Some(exif_vec)));
-        }
-
-        Ok((None, None))
+    #[tokio::test]
+    async fn filesystem_refresh_picks_up_new_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_png(&dir.path().join("a.png"), 4, 2);
+        let cfg = FilesystemSource {
+            glob: Some(format!("{}/*.png", dir.path().display())),
+            ..Default::default()
+        };
+        let src = FilesystemImageSource::new("fs", &cfg).unwrap();
+        assert_eq!(src.stats().total, 1);
+        assert!(src.health().await.healthy);
+
+        write_png(&dir.path().join("b.png"), 2, 4);
+        src.refresh().await.unwrap();
+        let page = src
+            .list(&ListQuery {
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        let portrait = &page.items[1];
+        assert_eq!(portrait.orientation, Orientation::Portrait);
+        let ctx = SelectionContext::default();
+        assert!(src.get(&portrait.id, &ctx).await.unwrap().is_some());
+        assert!(src.get("missing.png", &ctx).await.unwrap().is_none());
     }
-}
 
-/// Factory creating concrete sources from config enum.
-/// Factory creating a concrete boxed `ImageSource` from a typed config enum value.
-pub fn build_source(src: &Source) -> Result<Box<dyn ImageSource>> {
-    match src {
-        Source::Filesystem { filesystem } => {
-            let cfg = filesystem.clone().unwrap_or_default();
-            Ok(Box::new(FilesystemImageSource::new(&cfg)?))
-        }
-        Source::Immich { immich } => {
-            let cfg = immich.clone().unwrap_or_default();
-            Ok(Box::new(ImmichImageSource::new(&cfg)?))
-        }
-        Source::Unknown => bail!("unknown source kind"),
+    #[tokio::test]
+    async fn filesystem_without_matches_is_unhealthy() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = FilesystemSource {
+            glob: Some(format!("{}/*.png", dir.path().display())),
+            ..Default::default()
+        };
+        let src = FilesystemImageSource::new("fs", &cfg).unwrap();
+        assert!(!src.health().await.healthy);
     }
 }
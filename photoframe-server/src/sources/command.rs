@@ -0,0 +1,346 @@
+//! Source backed by an external executable, run once per selection. The command gets
+//! the frame id, desired orientation and useful image size through `{placeholders}` in
+//! its arguments and `PHOTOFRAME_*` environment variables, and answers on stdout with
+//! one of:
+//!
+//! - the image bytes themselves,
+//! - a JSON object `{"path": ..., "id": ..., "orientation": ..., "date_taken": ...,
+//!   "metadata": ...}` where only `path` is required,
+//! - a plain image path.
+//!
+//! Empty output with exit code 0 means there is nothing to show right now. A non-zero
+//! exit code or running past the timeout is a failure reported through `health`.
+//! Everything written to stderr is logged.
+
+use super::{
+    AssetPage, AssetSummary, ImageMeta, ImageSource, ListQuery, SelectionContext, SourceData,
+    SourceHealth, SourceStats,
+};
+use crate::config::{CommandSource, Orientation};
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+/// Default seconds before the command is killed.
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// Images kept so recent output can still be listed and shown again.
+const RECENT_OUTPUTS: usize = 16;
+
+/// The JSON form of the command's output.
+#[derive(Debug, Deserialize)]
+struct CommandOutput {
+    path: PathBuf,
+    id: Option<String>,
+    orientation: Option<Orientation>,
+    date_taken: Option<DateTime<Utc>>,
+    metadata: Option<serde_json::Value>,
+}
+
+pub struct CommandImageSource {
+    id: String,
+    cfg: CommandSource,
+    /// Latest outputs, newest last.
+    recent: parking_lot::Mutex<VecDeque<ImageMeta>>,
+    last_run: parking_lot::Mutex<Option<DateTime<Utc>>>,
+    /// Why the last run failed, reported through `health`.
+    last_error: parking_lot::Mutex<Option<String>>,
+}
+
+impl CommandImageSource {
+    pub fn new(id: &str, cfg: &CommandSource) -> Result<Self> {
+        Ok(Self {
+            id: id.to_string(),
+            cfg: cfg.clone(),
+            recent: parking_lot::Mutex::new(VecDeque::new()),
+            last_run: parking_lot::Mutex::new(None),
+            last_error: parking_lot::Mutex::new(None),
+        })
+    }
+
+    /// Run the command for `ctx` and interpret its output.
+    async fn run(&self, program: &str, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        let orientation = match ctx.orientation {
+            Orientation::Landscape => "landscape",
+            Orientation::Portrait => "portrait",
+        };
+        let max_dimension = ctx.max_dimension.map(|d| d.to_string()).unwrap_or_default();
+        let args = self.cfg.args.iter().map(|a| {
+            a.replace("{frame_id}", &ctx.frame_id)
+                .replace("{orientation}", orientation)
+                .replace("{max_dimension}", &max_dimension)
+        });
+        let mut cmd = tokio::process::Command::new(program);
+        cmd.args(args)
+            .env("PHOTOFRAME_SOURCE_ID", &self.id)
+            .env("PHOTOFRAME_FRAME_ID", &ctx.frame_id)
+            .env("PHOTOFRAME_ORIENTATION", orientation)
+            .env("PHOTOFRAME_MAX_DIMENSION", &max_dimension)
+            .envs(&self.cfg.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.cfg.working_dir {
+            cmd.current_dir(dir);
+        }
+
+        let timeout = self.cfg.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS);
+        let child = cmd.spawn().with_context(|| format!("starting {program}"))?;
+        // Dropping the child on timeout kills it.
+        let output = tokio::time::timeout(Duration::from_secs(timeout), child.wait_with_output())
+            .await
+            .map_err(|_| anyhow!("{program} timed out after {timeout}s"))?
+            .with_context(|| format!("running {program}"))?;
+        *self.last_run.lock() = Some(Utc::now());
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for line in stderr.lines().filter(|l| !l.trim().is_empty()) {
+            tracing::info!(source_id = %self.id, "{program}: {line}");
+        }
+        if !output.status.success() {
+            let last_line = stderr.lines().rev().find(|l| !l.trim().is_empty());
+            match (output.status.code(), last_line) {
+                (Some(code), Some(line)) => bail!("{program} exited with code {code}: {line}"),
+                (Some(code), None) => bail!("{program} exited with code {code}"),
+                (None, _) => bail!("{program} was killed by a signal"),
+            }
+        }
+        self.interpret(output.stdout)
+    }
+
+    fn interpret(&self, stdout: Vec<u8>) -> Result<Option<ImageMeta>> {
+        if image::guess_format(&stdout).is_ok() {
+            let (w, h) = image::ImageReader::new(std::io::Cursor::new(&stdout))
+                .with_guessed_format()?
+                .into_dimensions()
+                .context("reading dimensions of command output")?;
+            let digest = Sha256::digest(&stdout);
+            let id = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+            return Ok(Some(ImageMeta {
+                data: SourceData::Bytes(stdout),
+                orientation: Orientation::from_dims(w, h),
+                date_taken: None, // Extracted from the bytes when loading
+                exif_blob: None,
+                id: Some(id),
+                source_id: None, // Will be set when returning from next()
+                asset_metadata: None,
+            }));
+        }
+
+        let text =
+            String::from_utf8(stdout).context("command output is neither an image nor text")?;
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let output = if text.starts_with('{') {
+            serde_json::from_str(text).context("parsing command JSON output")?
+        } else {
+            CommandOutput {
+                path: PathBuf::from(text.lines().next().unwrap_or_default().trim()),
+                id: None,
+                orientation: None,
+                date_taken: None,
+                metadata: None,
+            }
+        };
+        let path = match &self.cfg.working_dir {
+            Some(dir) if output.path.is_relative() => Path::new(dir).join(&output.path),
+            _ => output.path,
+        };
+        let orientation = match output.orientation {
+            Some(o) => o,
+            None => {
+                let (w, h) = image::image_dimensions(&path)
+                    .with_context(|| format!("reading image {}", path.display()))?;
+                Orientation::from_dims(w, h)
+            }
+        };
+        Ok(Some(ImageMeta {
+            orientation,
+            date_taken: output
+                .date_taken
+                .or_else(|| crate::frame::read_file_date_taken(&path)),
+            exif_blob: None, // Will be extracted when loading the file
+            id: Some(
+                output
+                    .id
+                    .unwrap_or_else(|| path.to_string_lossy().to_string()),
+            ),
+            source_id: None, // Will be set when returning from next()
+            asset_metadata: output.metadata,
+            data: SourceData::Path(path),
+        }))
+    }
+
+    fn remember(&self, meta: &ImageMeta) {
+        let mut recent = self.recent.lock();
+        recent.retain(|m| m.id != meta.id);
+        if recent.len() == RECENT_OUTPUTS {
+            recent.pop_front();
+        }
+        recent.push_back(meta.clone());
+    }
+}
+
+#[async_trait]
+impl ImageSource for CommandImageSource {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        let Some(program) = &self.cfg.program else {
+            return Ok(None);
+        };
+        let result = self.run(program, ctx).await;
+        *self.last_error.lock() = result.as_ref().err().map(|e| format!("{e:#}"));
+        match result {
+            Ok(Some(meta)) => {
+                self.remember(&meta);
+                let id = meta.id.as_deref().unwrap_or_default();
+                if ctx.accepts(id, meta.orientation) {
+                    Ok(Some(meta))
+                } else {
+                    tracing::debug!(source_id = %self.id, asset_id = %id, "command output not accepted for this frame");
+                    Ok(None)
+                }
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                tracing::warn!(source_id = %self.id, error = %format!("{e:#}"), "command source failed");
+                Ok(None)
+            }
+        }
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        let all = self
+            .recent
+            .lock()
+            .iter()
+            .rev()
+            .map(|m| AssetSummary {
+                checksum: None,
+                id: m.id.clone().unwrap_or_default(),
+                orientation: m.orientation,
+                date_taken: m.date_taken,
+            })
+            .collect();
+        Ok(AssetPage::from_summaries(all, query))
+    }
+
+    async fn get(&self, id: &str, _ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        Ok(self
+            .recent
+            .lock()
+            .iter()
+            .find(|m| m.id.as_deref() == Some(id))
+            .cloned())
+    }
+
+    async fn health(&self) -> SourceHealth {
+        let last_run = *self.last_run.lock();
+        if self.cfg.program.is_none() {
+            return SourceHealth::unhealthy("no program configured", None);
+        }
+        match self.last_error.lock().clone() {
+            Some(error) => SourceHealth::unhealthy(format!("last run failed: {error}"), last_run),
+            None => SourceHealth::healthy(last_run),
+        }
+    }
+
+    fn stats(&self) -> SourceStats {
+        let recent: Vec<ImageMeta> = self.recent.lock().iter().cloned().collect();
+        SourceStats::from_entries(&recent)
+    }
+
+    fn remove_asset_from_cache(&self, asset_id: &str) -> Result<bool> {
+        let mut recent = self.recent.lock();
+        let before = recent.len();
+        recent.retain(|m| m.id.as_deref() != Some(asset_id));
+        Ok(before != recent.len())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+
+    fn script(body: &str) -> CommandSource {
+        CommandSource {
+            program: Some("sh".into()),
+            args: vec![
+                "-c".into(),
+                body.into(),
+                "sh".into(),
+                "{orientation}".into(),
+            ],
+            timeout_secs: Some(2),
+            ..Default::default()
+        }
+    }
+
+    fn ctx() -> SelectionContext {
+        SelectionContext {
+            frame_id: "kitchen".into(),
+            orientation: Orientation::Portrait,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn interprets_paths_json_bytes_and_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("tall.png");
+        std::fs::write(&png, test_support::png(10, 20)).unwrap();
+        let png = png.to_string_lossy().to_string();
+
+        // A plain path, with the orientation passed as an argument.
+        let cfg = script(&format!("[ \"$1\" = portrait ] && echo {png}"));
+        let source = CommandImageSource::new("test_cmd", &cfg).unwrap();
+        let meta = source.next(&ctx()).await.unwrap().unwrap();
+        assert_eq!(meta.id.as_deref(), Some(png.as_str()));
+        assert_eq!(meta.orientation, Orientation::Portrait);
+
+        // JSON, with the frame id taken from the environment.
+        let cfg = script(&format!(
+            r#"echo '{{"path": "{png}", "id": "'$PHOTOFRAME_FRAME_ID'-1", "metadata": {{"by": "sam"}}}}'"#
+        ));
+        let source = CommandImageSource::new("test_cmd", &cfg).unwrap();
+        let meta = source.next(&ctx()).await.unwrap().unwrap();
+        assert_eq!(meta.id.as_deref(), Some("kitchen-1"));
+        assert_eq!(meta.asset_metadata.unwrap()["by"], "sam");
+        assert!(source.get("kitchen-1", &ctx()).await.unwrap().is_some());
+
+        // Raw bytes.
+        let cfg = script(&format!("cat {png}"));
+        let source = CommandImageSource::new("test_cmd", &cfg).unwrap();
+        let meta = source.next(&ctx()).await.unwrap().unwrap();
+        assert!(matches!(meta.data, SourceData::Bytes(_)));
+        assert_eq!(meta.orientation, Orientation::Portrait);
+
+        // Nothing to show is not an error.
+        let source = CommandImageSource::new("test_cmd", &script("true")).unwrap();
+        assert!(source.next(&ctx()).await.unwrap().is_none());
+        assert!(source.health().await.healthy);
+
+        let source =
+            CommandImageSource::new("test_cmd", &script("echo 'no album' >&2; exit 3")).unwrap();
+        assert!(source.next(&ctx()).await.unwrap().is_none());
+        let health = source.health().await;
+        assert!(!health.healthy);
+        let detail = health.detail.unwrap();
+        assert!(detail.contains("code 3: no album"), "{detail}");
+
+        let mut cfg = script("sleep 5");
+        cfg.timeout_secs = Some(1);
+        let source = CommandImageSource::new("test_cmd", &cfg).unwrap();
+        assert!(source.next(&ctx()).await.unwrap().is_none());
+        let detail = source.health().await.detail.unwrap();
+        assert!(detail.contains("timed out"), "{detail}");
+    }
+}
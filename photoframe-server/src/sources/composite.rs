@@ -0,0 +1,484 @@
+//! Composite sources combine the listings of other configured sources (union,
+//! intersection or difference) and pick from the result in their own order. Images are
+//! fetched from the member that listed them, so the members' own caching, renditions
+//! and blacklists still apply.
+
+use super::{
+    AssetAction, AssetPage, AssetSummary, ImageMeta, ImageSource, ListQuery, SelectionContext,
+    SharedImageSource, ShuffleBagStore, SourceHealth, SourceStats,
+};
+use crate::config::{CompositeOperation, CompositeSource, OrderKind, Orientation};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::prelude::*;
+use rand::rng;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 600;
+/// Candidates tried per `next` call when members fail to produce a listed asset.
+const MAX_ATTEMPTS: usize = 8;
+
+/// One asset of the combined listing and the member it is fetched from.
+#[derive(Debug, Clone)]
+pub struct CompositeEntry {
+    pub member: String,
+    pub asset: AssetSummary,
+}
+
+/// Ids and checksums of one member's listing, for matching assets across members.
+#[derive(Default)]
+struct Keys<'a> {
+    ids: HashSet<&'a str>,
+    checksums: HashSet<&'a str>,
+}
+
+impl<'a> Keys<'a> {
+    fn of(assets: &'a [AssetSummary]) -> Self {
+        let mut keys = Self::default();
+        for a in assets {
+            keys.insert(a);
+        }
+        keys
+    }
+
+    fn insert(&mut self, a: &'a AssetSummary) {
+        self.ids.insert(&a.id);
+        if let Some(c) = &a.checksum {
+            self.checksums.insert(c);
+        }
+    }
+
+    fn contains(&self, a: &AssetSummary) -> bool {
+        self.ids.contains(a.id.as_str())
+            || a.checksum
+                .as_deref()
+                .is_some_and(|c| self.checksums.contains(c))
+    }
+}
+
+/// Combine member listings, given in member order. An asset matches another when their
+/// ids or checksums are equal; the union keeps the first member's copy of each photo.
+pub fn combine(
+    operation: CompositeOperation,
+    listings: &[(String, Vec<AssetSummary>)],
+) -> Vec<CompositeEntry> {
+    let entry = |member: &String, asset: &AssetSummary| CompositeEntry {
+        member: member.clone(),
+        asset: asset.clone(),
+    };
+    let Some(((base_id, base), rest)) = listings.split_first() else {
+        return Vec::new();
+    };
+    match operation {
+        CompositeOperation::Union => {
+            let mut seen = Keys::default();
+            let mut out = Vec::new();
+            for (member, assets) in listings {
+                for a in assets {
+                    if !seen.contains(a) {
+                        seen.insert(a);
+                        out.push(entry(member, a));
+                    }
+                }
+            }
+            out
+        }
+        CompositeOperation::Intersection | CompositeOperation::Difference => {
+            let others: Vec<Keys> = rest.iter().map(|(_, assets)| Keys::of(assets)).collect();
+            let keep_shared = operation == CompositeOperation::Intersection;
+            base.iter()
+                .filter(|a| {
+                    if keep_shared {
+                        others.iter().all(|k| k.contains(a))
+                    } else {
+                        !others.iter().any(|k| k.contains(a))
+                    }
+                })
+                .map(|a| entry(base_id, a))
+                .collect()
+        }
+    }
+}
+
+pub struct CompositeImageSource {
+    id: String,
+    operation: CompositeOperation,
+    order: OrderKind,
+    refresh_interval_secs: u64,
+    members: Vec<(String, SharedImageSource)>,
+    entries: parking_lot::RwLock<Vec<CompositeEntry>>,
+    last_list: AtomicU64, // unix seconds of last combination, 0 = never
+    last_error: parking_lot::Mutex<Option<String>>,
+    sync_lock: tokio::sync::Mutex<()>,
+    cursor: AtomicUsize,   // for sequential order
+    bags: ShuffleBagStore, // for shuffle order
+}
+
+impl CompositeImageSource {
+    /// `members` are the built sources named in `cfg.sources`, in that order.
+    pub fn new(
+        id: &str,
+        cfg: &CompositeSource,
+        members: Vec<(String, SharedImageSource)>,
+    ) -> Result<Self> {
+        if members.is_empty() {
+            bail!("composite source needs at least one member in `sources`");
+        }
+        Ok(Self {
+            id: id.to_string(),
+            operation: cfg.operation.unwrap_or_default(),
+            order: cfg.order.unwrap_or_default(),
+            refresh_interval_secs: cfg
+                .refresh_interval_secs
+                .unwrap_or(DEFAULT_REFRESH_INTERVAL_SECS),
+            members,
+            entries: parking_lot::RwLock::new(Vec::new()),
+            last_list: AtomicU64::new(0),
+            last_error: parking_lot::Mutex::new(None),
+            sync_lock: tokio::sync::Mutex::new(()),
+            cursor: AtomicUsize::new(0),
+            bags: ShuffleBagStore::new(id),
+        })
+    }
+
+    async fn list_if_needed(&self) -> Result<()> {
+        let result = self.combine_if_needed().await;
+        *self.last_error.lock() = result.as_ref().err().map(|e| format!("{e:#}"));
+        result
+    }
+
+    /// Combine if needed, falling back to the previous combination if that fails. The
+    /// error is logged and reported through `health`.
+    async fn list_or_keep_cached(&self) {
+        if let Err(e) = self.list_if_needed().await {
+            tracing::warn!(source = %self.id, error = %format!("{e:#}"), "composite listing failed; using previous combination");
+        }
+    }
+
+    async fn combine_if_needed(&self) -> Result<()> {
+        let _guard = self.sync_lock.lock().await;
+        let now = Utc::now().timestamp() as u64;
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        if last != 0 && now.saturating_sub(last) < self.refresh_interval_secs {
+            return Ok(());
+        }
+        let everything = ListQuery {
+            limit: usize::MAX,
+            ..Default::default()
+        };
+        let mut listings = Vec::with_capacity(self.members.len());
+        for (member, src) in &self.members {
+            // A missing member listing would widen intersections and differences, so keep
+            // the previous combination instead.
+            let page = src
+                .list(&everything)
+                .await
+                .map_err(|e| e.context(format!("listing member source '{member}'")))?;
+            listings.push((member.clone(), page.items));
+        }
+        let entries = combine(self.operation, &listings);
+        tracing::info!(source = %self.id, operation = ?self.operation, total = entries.len(), "Combined composite source");
+        *self.entries.write() = entries;
+        self.last_list.store(now, AtomicOrdering::Relaxed);
+        Ok(())
+    }
+
+    fn member(&self, member_id: &str) -> Option<&SharedImageSource> {
+        self.members
+            .iter()
+            .find(|(id, _)| id == member_id)
+            .map(|(_, src)| src)
+    }
+
+    fn entry(&self, asset_id: &str) -> Option<CompositeEntry> {
+        self.entries
+            .read()
+            .iter()
+            .find(|e| e.asset.id == asset_id)
+            .cloned()
+    }
+
+    /// Pick a listed candidate for `ctx` according to the source's order.
+    async fn pick(
+        &self,
+        snapshot: &[CompositeEntry],
+        ctx: &SelectionContext,
+    ) -> Option<CompositeEntry> {
+        let accepts = |e: &CompositeEntry| ctx.accepts(&e.asset.id, e.asset.orientation);
+        match self.order {
+            OrderKind::Sequential => {
+                let total = snapshot.len();
+                let start = self.cursor.fetch_add(1, AtomicOrdering::Relaxed);
+                (0..total).find_map(|offset| {
+                    let e = &snapshot[(start + offset) % total];
+                    accepts(e).then(|| {
+                        if offset > 0 {
+                            self.cursor.fetch_add(offset, AtomicOrdering::Relaxed);
+                        }
+                        e.clone()
+                    })
+                })
+            }
+            OrderKind::Random => {
+                let candidates: Vec<&CompositeEntry> =
+                    snapshot.iter().filter(|e| accepts(e)).collect();
+                candidates.choose(&mut rng()).map(|e| (*e).clone())
+            }
+            OrderKind::Shuffle => {
+                let index: HashMap<&str, &CompositeEntry> =
+                    snapshot.iter().map(|e| (e.asset.id.as_str(), e)).collect();
+                let ids: Vec<&str> = snapshot.iter().map(|e| e.asset.id.as_str()).collect();
+                let drawn = self
+                    .bags
+                    .draw(&ctx.frame_id, &ids, |id| {
+                        index.get(id).is_some_and(|e| accepts(e))
+                    })
+                    .await?;
+                index.get(drawn.as_str()).map(|e| (*e).clone())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ImageSource for CompositeImageSource {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        self.list_or_keep_cached().await;
+        let mut snapshot = self.entries.read().clone();
+        for _ in 0..MAX_ATTEMPTS {
+            if snapshot.is_empty() {
+                break;
+            }
+            let Some(entry) = self.pick(&snapshot, ctx).await else {
+                break;
+            };
+            let fetched = match self.member(&entry.member) {
+                Some(src) => src.get(&entry.asset.id, ctx).await,
+                None => Ok(None),
+            };
+            match fetched {
+                Ok(Some(meta)) => return Ok(Some(meta)),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::debug!(source = %self.id, member = %entry.member, asset_id = %entry.asset.id, error = %e, "member failed to fetch asset")
+                }
+            }
+            snapshot.retain(|e| e.asset.id != entry.asset.id);
+        }
+        Ok(None)
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        for (member, src) in &self.members {
+            if let Err(e) = src.refresh().await {
+                tracing::warn!(source = %self.id, member = %member, error = %e, "member refresh failed");
+            }
+        }
+        self.last_list.store(0, AtomicOrdering::Relaxed);
+        self.list_if_needed().await
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        self.list_or_keep_cached().await;
+        let all = self
+            .entries
+            .read()
+            .iter()
+            .map(|e| e.asset.clone())
+            .collect();
+        Ok(AssetPage::from_summaries(all, query))
+    }
+
+    async fn get(&self, id: &str, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        self.list_or_keep_cached().await;
+        let Some(entry) = self.entry(id) else {
+            return Ok(None);
+        };
+        match self.member(&entry.member) {
+            Some(src) => src.get(id, ctx).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn health(&self) -> SourceHealth {
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let last_refresh = (last > 0)
+            .then(|| DateTime::from_timestamp(last as i64, 0))
+            .flatten();
+        if let Some(error) = self.last_error.lock().clone() {
+            return SourceHealth::unhealthy(format!("combining failed: {error}"), last_refresh);
+        }
+        let mut problems = Vec::new();
+        for (member, src) in &self.members {
+            let health = src.health().await;
+            if !health.healthy {
+                let detail = health.detail.unwrap_or_else(|| "unhealthy".to_string());
+                problems.push(format!("{member}: {detail}"));
+            }
+        }
+        if problems.is_empty() {
+            SourceHealth::healthy(last_refresh)
+        } else {
+            SourceHealth::unhealthy(problems.join("; "), last_refresh)
+        }
+    }
+
+    async fn apply_action(&self, id: &str, action: &AssetAction) -> Result<bool> {
+        let Some(entry) = self.entry(id) else {
+            return Ok(false);
+        };
+        match self.member(&entry.member) {
+            Some(src) => src.apply_action(id, action).await,
+            None => Ok(false),
+        }
+    }
+
+    fn stats(&self) -> SourceStats {
+        let entries = self.entries.read();
+        let portrait = entries
+            .iter()
+            .filter(|e| e.asset.orientation == Orientation::Portrait)
+            .count();
+        SourceStats {
+            total: entries.len(),
+            landscape: entries.len() - portrait,
+            portrait,
+        }
+    }
+
+    fn remove_asset_from_cache(&self, asset_id: &str) -> Result<bool> {
+        let mut removed = {
+            let mut entries = self.entries.write();
+            let before = entries.len();
+            entries.retain(|e| e.asset.id != asset_id);
+            before != entries.len()
+        };
+        // Members may list the asset too, e.g. when it is blacklisted through the composite.
+        for (_, src) in &self.members {
+            removed |= src.remove_asset_from_cache(asset_id)?;
+        }
+        Ok(removed)
+    }
+
+    fn asset_owner(&self, asset_id: &str) -> Option<String> {
+        self.entry(asset_id).map(|e| e.member)
+    }
+
+    fn member_ids(&self) -> Vec<String> {
+        self.members.iter().map(|(id, _)| id.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(id: &str, checksum: Option<&str>) -> AssetSummary {
+        AssetSummary {
+            id: id.to_string(),
+            orientation: Orientation::Landscape,
+            date_taken: None,
+            checksum: checksum.map(str::to_string),
+        }
+    }
+
+    fn ids(entries: &[CompositeEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| format!("{}/{}", e.member, e.asset.id))
+            .collect()
+    }
+
+    #[test]
+    fn combines_by_id_or_checksum() {
+        let listings = vec![
+            (
+                "family".to_string(),
+                vec![
+                    asset("a", Some("c1")),
+                    asset("b", Some("c2")),
+                    asset("x", None),
+                ],
+            ),
+            (
+                "album".to_string(),
+                // `b2` is `b` in another account (same checksum); `x` matches by id.
+                vec![asset("b2", Some("c2")), asset("x", None), asset("y", None)],
+            ),
+        ];
+        assert_eq!(
+            ids(&combine(CompositeOperation::Union, &listings)),
+            ["family/a", "family/b", "family/x", "album/y"]
+        );
+        assert_eq!(
+            ids(&combine(CompositeOperation::Intersection, &listings)),
+            ["family/b", "family/x"]
+        );
+        assert_eq!(
+            ids(&combine(CompositeOperation::Difference, &listings)),
+            ["family/a"]
+        );
+        assert!(combine(CompositeOperation::Union, &[]).is_empty());
+    }
+
+    /// Member listing two assets whose listing can be made to fail.
+    struct Flaky {
+        failing: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    #[async_trait]
+    impl ImageSource for Flaky {
+        async fn next(&self, _ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+            Ok(None)
+        }
+
+        async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+            if self.failing.load(AtomicOrdering::Relaxed) {
+                bail!("member unavailable");
+            }
+            let all = vec![asset("a", None), asset("b", None)];
+            Ok(AssetPage::from_summaries(all, query))
+        }
+
+        async fn get(&self, id: &str, _ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+            Ok(Some(ImageMeta {
+                data: crate::sources::SourceData::Bytes(Vec::new()),
+                orientation: Orientation::Landscape,
+                date_taken: None,
+                exif_blob: None,
+                id: Some(id.to_string()),
+                source_id: None,
+                asset_metadata: None,
+            }))
+        }
+
+        async fn health(&self) -> SourceHealth {
+            SourceHealth::healthy(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_member_listing_keeps_previous_combination() {
+        let failing = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let member: SharedImageSource = std::sync::Arc::new(Box::new(Flaky {
+            failing: failing.clone(),
+        }));
+        let source = CompositeImageSource::new(
+            "test_composite_flaky",
+            &CompositeSource::default(),
+            vec![("flaky".to_string(), member)],
+        )
+        .unwrap();
+        assert_eq!(source.list(&ListQuery::default()).await.unwrap().total, 2);
+
+        failing.store(true, AtomicOrdering::Relaxed);
+        source.last_list.store(0, AtomicOrdering::Relaxed);
+        assert_eq!(source.list(&ListQuery::default()).await.unwrap().total, 2);
+        let ctx = SelectionContext::default();
+        let meta = source.get("b", &ctx).await.unwrap().unwrap();
+        assert_eq!(meta.id.as_deref(), Some("b"));
+        assert!(!source.health().await.healthy);
+    }
+}
@@ -0,0 +1,991 @@
+//! Google Photos source. Access is granted once through the OAuth2 device flow: the
+//! server shows a code, the user enters it at google.com/device, and the resulting
+//! refresh token is stored in the source's config. The user then picks photos on their
+//! phone in a Photos Picker session.
+//!
+//! Picker sessions expire and their media URLs only work for an hour, so once the user
+//! is done picking, the photos are downloaded into `<source_id>_google_photos/` and the
+//! session is deleted, as Google asks. The copies are shown from then on, also after
+//! restarts, until photos from a new session replace them.
+//!
+//! Only the Picker API is used: Google retired the Library API's read-all scope in
+//! March 2025, and the device flow doesn't grant Library scopes at all.
+
+use super::remote;
+use super::{
+    AssetPage, AssetSummary, ImageMeta, ImageSource, ListQuery, SelectionContext, ShuffleBagStore,
+    SourceData, SourceHealth, SourceStats,
+};
+use crate::config::{GooglePhotosSource, Orientation};
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use oauth2::basic::BasicClient;
+use oauth2::{
+    AuthType, ClientId, ClientSecret, DeviceAuthorizationUrl, EndpointNotSet, EndpointSet,
+    RefreshToken, Scope, StandardDeviceAuthorizationResponse, TokenResponse, TokenUrl,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
+
+/// Read access to items picked in Photos Picker sessions.
+const PICKER_SCOPE: &str = "https://www.googleapis.com/auth/photospicker.mediaitems.readonly";
+/// Media items requested per page (the maximum).
+const PAGE_SIZE: u32 = 100;
+/// Longest edge of the copies kept of picked photos; frames scale them down further.
+const COPY_EDGE: u32 = 4096;
+/// Seconds between checks whether the user has finished picking.
+const SESSION_CHECK_SECS: u64 = 10;
+/// Index of the copied photos within the copy directory.
+const INDEX_FILE: &str = "index.json";
+
+/// Google's endpoints; replaced in tests by a local mock.
+#[derive(Debug, Clone)]
+pub struct GoogleEndpoints {
+    pub device_auth: String,
+    pub token: String,
+    pub picker: String,
+}
+
+impl Default for GoogleEndpoints {
+    fn default() -> Self {
+        Self {
+            device_auth: "https://oauth2.googleapis.com/device/code".into(),
+            token: "https://oauth2.googleapis.com/token".into(),
+            picker: "https://photospicker.googleapis.com".into(),
+        }
+    }
+}
+
+type OAuthClient =
+    BasicClient<EndpointNotSet, EndpointSet, EndpointNotSet, EndpointNotSet, EndpointSet>;
+
+fn oauth_client(cfg: &GooglePhotosSource, endpoints: &GoogleEndpoints) -> Result<OAuthClient> {
+    let client_id = cfg
+        .client_id
+        .clone()
+        .ok_or_else(|| anyhow!("google photos source missing client_id"))?;
+    let mut client =
+        BasicClient::new(ClientId::new(client_id)).set_auth_type(AuthType::RequestBody);
+    if let Some(secret) = &cfg.client_secret {
+        client = client.set_client_secret(ClientSecret::new(secret.clone()));
+    }
+    let client = client
+        .set_device_authorization_url(DeviceAuthorizationUrl::new(endpoints.device_auth.clone())?)
+        .set_token_uri(TokenUrl::new(endpoints.token.clone())?);
+    Ok(client)
+}
+
+/// HTTP client for the OAuth exchanges; redirects are refused as the oauth2 crate advises.
+fn oauth_http() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("valid reqwest client")
+}
+
+/// Progress of a device flow, as reported by `GET /api/sources/<id>/google/device`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum DeviceFlowStatus {
+    /// Waiting for the user to enter `user_code` at `verification_url`.
+    Pending {
+        user_code: String,
+        verification_url: String,
+        expires_at: DateTime<Utc>,
+    },
+    Authorized,
+    Failed {
+        error: String,
+    },
+}
+
+static DEVICE_FLOWS: OnceLock<parking_lot::Mutex<HashMap<String, DeviceFlowStatus>>> =
+    OnceLock::new();
+
+fn device_flows() -> &'static parking_lot::Mutex<HashMap<String, DeviceFlowStatus>> {
+    DEVICE_FLOWS.get_or_init(|| parking_lot::Mutex::new(HashMap::new()))
+}
+
+/// Latest device flow status of a source, if one was started since the server started.
+pub fn device_flow_status(source_id: &str) -> Option<DeviceFlowStatus> {
+    device_flows().lock().get(source_id).cloned()
+}
+
+pub fn set_device_flow_status(source_id: &str, status: DeviceFlowStatus) {
+    device_flows().lock().insert(source_id.to_string(), status);
+}
+
+/// Request a device and user code for the Picker scope.
+pub async fn start_device_flow(
+    cfg: &GooglePhotosSource,
+    endpoints: &GoogleEndpoints,
+) -> Result<StandardDeviceAuthorizationResponse> {
+    oauth_client(cfg, endpoints)?
+        .exchange_device_code()
+        .add_scope(Scope::new(PICKER_SCOPE.to_string()))
+        .request_async(&oauth_http())
+        .await
+        .context("requesting google device code")
+}
+
+/// Poll until the user approved (or denied) the device flow and return the refresh token.
+pub async fn finish_device_flow(
+    cfg: &GooglePhotosSource,
+    endpoints: &GoogleEndpoints,
+    details: &StandardDeviceAuthorizationResponse,
+) -> Result<String> {
+    let token = oauth_client(cfg, endpoints)?
+        .exchange_device_access_token(details)
+        .request_async(&oauth_http(), tokio::time::sleep, None)
+        .await
+        .context("waiting for google authorization")?;
+    token
+        .refresh_token()
+        .map(|t| t.secret().clone())
+        .ok_or_else(|| anyhow!("google returned no refresh token"))
+}
+
+/// A Photos Picker session for the user to pick photos in.
+#[derive(Debug, Clone, Serialize)]
+pub struct PickerSession {
+    pub id: String,
+    /// Link to open on a device signed in to Google Photos.
+    pub picker_uri: String,
+    /// When Google discards the session; its photos must be copied before then.
+    pub expire_time: Option<DateTime<Utc>>,
+    /// Whether the user finished picking, so the picked items can be listed.
+    #[serde(skip)]
+    pub media_items_set: bool,
+    /// How often Google asks to be polled for `media_items_set`.
+    #[serde(skip)]
+    pub poll_interval: Option<Duration>,
+}
+
+impl PickerSession {
+    fn from_json(body: &serde_json::Value) -> Result<Self> {
+        let field = |key: &str| {
+            body.get(key)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("picker session response missing {key}"))
+        };
+        Ok(Self {
+            id: field("id")?,
+            picker_uri: field("pickerUri")?,
+            expire_time: body
+                .get("expireTime")
+                .and_then(|v| v.as_str())
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc)),
+            media_items_set: body
+                .get("mediaItemsSet")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            // Durations are JSON-encoded as decimal seconds with an `s` suffix, e.g. "5s".
+            poll_interval: body
+                .pointer("/pollingConfig/pollInterval")
+                .and_then(|v| v.as_str())
+                .and_then(|s| s.strip_suffix('s')?.parse::<f64>().ok())
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok()),
+        })
+    }
+}
+
+/// Access tokens derived from the stored refresh token, reused until shortly before
+/// they expire.
+struct GoogleAuth {
+    client: OAuthClient,
+    refresh_token: RefreshToken,
+    cached: tokio::sync::Mutex<Option<(String, Instant)>>,
+}
+
+impl GoogleAuth {
+    fn new(cfg: &GooglePhotosSource, endpoints: &GoogleEndpoints) -> Result<Option<Self>> {
+        let Some(refresh_token) = cfg.refresh_token.clone() else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            client: oauth_client(cfg, endpoints)?,
+            refresh_token: RefreshToken::new(refresh_token),
+            cached: tokio::sync::Mutex::new(None),
+        }))
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        let mut cached = self.cached.lock().await;
+        if let Some((token, valid_until)) = cached.as_ref()
+            && *valid_until > Instant::now()
+        {
+            return Ok(token.clone());
+        }
+        let resp = self
+            .client
+            .exchange_refresh_token(&self.refresh_token)
+            .request_async(&oauth_http())
+            .await
+            .context("refreshing google access token")?;
+        let lifetime = resp.expires_in().unwrap_or(Duration::from_secs(3_600));
+        let token = resp.access_token().secret().clone();
+        // Renew a minute early so a token never expires mid-listing.
+        *cached = Some((
+            token.clone(),
+            Instant::now() + lifetime.saturating_sub(Duration::from_secs(60)),
+        ));
+        Ok(token)
+    }
+}
+
+/// Create a Photos Picker session with the source's stored authorization.
+pub async fn create_picker_session(
+    cfg: &GooglePhotosSource,
+    endpoints: &GoogleEndpoints,
+) -> Result<PickerSession> {
+    let auth = GoogleAuth::new(cfg, endpoints)?
+        .ok_or_else(|| anyhow!("google photos source not authorized yet"))?;
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/sessions", endpoints.picker))
+        .bearer_auth(auth.access_token().await?)
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .context("creating google photos picker session")?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        bail!("creating picker session failed: {status} {body}");
+    }
+    PickerSession::from_json(&resp.json().await?)
+}
+
+/// A picked media item as listed by the Picker API.
+#[derive(Debug, Clone)]
+struct PickedItem {
+    id: String,
+    /// Short-lived URL; sizes are requested by appending `=w<W>-h<H>`.
+    base_url: String,
+    orientation: Orientation,
+    date_taken: Option<DateTime<Utc>>,
+    metadata: serde_json::Value,
+}
+
+/// A picked photo copied into the source's directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CopiedPhoto {
+    id: String,
+    /// File name within the copy directory.
+    file: String,
+    orientation: Orientation,
+    date_taken: Option<DateTime<Utc>>,
+    metadata: serde_json::Value,
+}
+
+/// The photos copied from one picker session, persisted as `index.json` next to them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CopiedSession {
+    session_id: String,
+    copied_at: Option<DateTime<Utc>>,
+    photos: Vec<CopiedPhoto>,
+}
+
+pub struct GooglePhotosImageSource {
+    cfg: GooglePhotosSource,
+    endpoints: GoogleEndpoints,
+    auth: Option<GoogleAuth>,
+    /// `<source_id>_google_photos/`, holding the copies and their index.
+    dir: PathBuf,
+    copied: parking_lot::RwLock<CopiedSession>,
+    /// Whether `copied` was restored from the index yet.
+    loaded: AtomicBool,
+    /// Expiry of the configured session, once it has been checked.
+    expire_time: parking_lot::Mutex<Option<DateTime<Utc>>>,
+    last_check: AtomicU64, // unix seconds of the last session check, 0 = never
+    cursor: AtomicUsize,   // for sequential order
+    bags: ShuffleBagStore, // for shuffle order
+    client: reqwest::Client,
+    // Serializes session checks so concurrent callers don't copy twice.
+    sync_lock: tokio::sync::Mutex<()>,
+    /// Why the last session check or copy failed, reported through `health`.
+    last_error: parking_lot::Mutex<Option<String>>,
+}
+
+impl GooglePhotosImageSource {
+    pub fn new(id: &str, cfg: &GooglePhotosSource) -> Result<Self> {
+        Self::with_endpoints(id, cfg, GoogleEndpoints::default())
+    }
+
+    pub fn with_endpoints(
+        id: &str,
+        cfg: &GooglePhotosSource,
+        endpoints: GoogleEndpoints,
+    ) -> Result<Self> {
+        let auth = match &cfg.client_id {
+            Some(_) => GoogleAuth::new(cfg, &endpoints)?,
+            None => None,
+        };
+        Ok(Self {
+            cfg: cfg.clone(),
+            endpoints,
+            auth,
+            dir: PathBuf::from(format!("{id}_google_photos")),
+            copied: parking_lot::RwLock::new(CopiedSession::default()),
+            loaded: AtomicBool::new(false),
+            expire_time: parking_lot::Mutex::new(None),
+            last_check: AtomicU64::new(0),
+            cursor: AtomicUsize::new(0),
+            bags: ShuffleBagStore::new(id),
+            client: reqwest::Client::new(),
+            sync_lock: tokio::sync::Mutex::new(()),
+            last_error: parking_lot::Mutex::new(None),
+        })
+    }
+
+    /// Why the source can't show anything yet, if so.
+    fn unconfigured(&self) -> Option<&'static str> {
+        if self.cfg.client_id.is_none() {
+            Some("client_id not configured")
+        } else if self.auth.is_none() {
+            Some("not authorized; start the device flow")
+        } else if self.cfg.picker_session_id.is_none() {
+            Some("no picker session; pick photos first")
+        } else {
+            None
+        }
+    }
+
+    async fn get_session(&self, auth: &GoogleAuth, session_id: &str) -> Result<PickerSession> {
+        let resp = self
+            .client
+            .get(format!(
+                "{}/v1/sessions/{session_id}",
+                self.endpoints.picker
+            ))
+            .bearer_auth(auth.access_token().await?)
+            .send()
+            .await
+            .context("checking google photos picker session")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("checking picker session failed: {status} {body}");
+        }
+        PickerSession::from_json(&resp.json().await?)
+    }
+
+    async fn delete_session(&self, auth: &GoogleAuth, session_id: &str) -> Result<()> {
+        self.client
+            .delete(format!(
+                "{}/v1/sessions/{session_id}",
+                self.endpoints.picker
+            ))
+            .bearer_auth(auth.access_token().await?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn list_page(
+        &self,
+        auth: &GoogleAuth,
+        session_id: &str,
+        token: Option<&str>,
+    ) -> Result<(Vec<serde_json::Value>, Option<String>)> {
+        let mut query = vec![
+            ("sessionId", session_id.to_string()),
+            ("pageSize", PAGE_SIZE.to_string()),
+        ];
+        if let Some(token) = token {
+            query.push(("pageToken", token.to_string()));
+        }
+        let resp = self
+            .client
+            .get(format!("{}/v1/mediaItems", self.endpoints.picker))
+            .query(&query)
+            .bearer_auth(auth.access_token().await?)
+            .send()
+            .await
+            .context("listing google photos media items")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("google photos listing failed: {status} {body}");
+        }
+        let mut body: serde_json::Value = resp.json().await?;
+        let items = match body.get_mut("mediaItems").map(serde_json::Value::take) {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => Vec::new(), // empty sessions omit the field
+        };
+        let next = body
+            .get("nextPageToken")
+            .and_then(|v| v.as_str())
+            .filter(|t| !t.is_empty())
+            .map(str::to_string);
+        Ok((items, next))
+    }
+
+    async fn list_if_needed(&self) -> Result<()> {
+        let result = self.sync_if_needed().await;
+        *self.last_error.lock() = result.as_ref().err().map(|e| format!("{e:#}"));
+        result
+    }
+
+    /// Copy the configured session's photos once the user has picked them. Photos of a
+    /// previous session keep being shown until then.
+    async fn sync_if_needed(&self) -> Result<()> {
+        let (Some(auth), Some(session_id)) = (&self.auth, &self.cfg.picker_session_id) else {
+            return Ok(());
+        };
+        let _guard = self.sync_lock.lock().await;
+        if !self.loaded.swap(true, AtomicOrdering::Relaxed)
+            && let Some(copied) = load_index(&self.dir).await
+        {
+            *self.copied.write() = copied;
+        }
+        if self.copied.read().session_id == *session_id {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let expired = |expire_time: Option<DateTime<Utc>>| match expire_time {
+            Some(t) if t <= now => Err(anyhow!(
+                "picker session expired at {t} before photos were picked; create a new one"
+            )),
+            _ => Ok(()),
+        };
+        expired(*self.expire_time.lock())?;
+        let last = self.last_check.load(AtomicOrdering::Relaxed);
+        if last != 0 && (now.timestamp() as u64).saturating_sub(last) < SESSION_CHECK_SECS {
+            return Ok(());
+        }
+        self.last_check
+            .store(now.timestamp() as u64, AtomicOrdering::Relaxed);
+
+        let session = self.get_session(auth, session_id).await?;
+        *self.expire_time.lock() = session.expire_time;
+        if !session.media_items_set {
+            expired(session.expire_time)?;
+            tracing::debug!(session = %session_id, "waiting for photos to be picked");
+            return Ok(());
+        }
+        let mut items = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let (page, next) = self.list_page(auth, session_id, token.as_deref()).await?;
+            items.extend(page.into_iter().filter_map(entry_from_item));
+            match next {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        let copied = CopiedSession {
+            session_id: session_id.clone(),
+            copied_at: Some(now),
+            photos: self.copy_items(auth, &items).await?,
+        };
+        save_index(&self.dir, &copied).await?;
+        tracing::info!(
+            total = copied.photos.len(),
+            "Copied google photos picker session"
+        );
+        *self.copied.write() = copied;
+        // Google asks for sessions to be deleted once their items were retrieved.
+        if let Err(e) = self.delete_session(auth, session_id).await {
+            tracing::debug!(error = %format!("{e:#}"), "deleting picker session failed");
+        }
+        Ok(())
+    }
+
+    /// Download picked items into the copy directory and delete copies of earlier picks.
+    /// Items that can't be downloaded are skipped; request failures abort so the next
+    /// check, while the session is still valid, tries again.
+    async fn copy_items(
+        &self,
+        auth: &GoogleAuth,
+        items: &[PickedItem],
+    ) -> Result<Vec<CopiedPhoto>> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("creating {}", self.dir.display()))?;
+        let mut photos = Vec::new();
+        for item in items {
+            let resp = self
+                .client
+                .get(format!("{}=w{COPY_EDGE}-h{COPY_EDGE}", item.base_url))
+                .bearer_auth(auth.access_token().await?)
+                .send()
+                .await
+                .with_context(|| format!("downloading google photos item {}", item.id))?;
+            if !resp.status().is_success() {
+                tracing::warn!(asset_id = %item.id, status = %resp.status(), "skipping picked item that failed to download");
+                continue;
+            }
+            let bytes = resp.bytes().await?;
+            let file = copy_file_name(&item.id);
+            let path = self.dir.join(&file);
+            let tmp = path.with_extension("tmp");
+            tokio::fs::write(&tmp, &bytes)
+                .await
+                .with_context(|| format!("writing {}", tmp.display()))?;
+            tokio::fs::rename(&tmp, &path)
+                .await
+                .with_context(|| format!("renaming {}", path.display()))?;
+            tracing::debug!(asset_id = %item.id, bytes = bytes.len(), "Copied Google Photos item");
+            photos.push(CopiedPhoto {
+                id: item.id.clone(),
+                file,
+                orientation: item.orientation,
+                date_taken: item.date_taken,
+                metadata: item.metadata.clone(),
+            });
+        }
+        let keep: std::collections::HashSet<&str> =
+            photos.iter().map(|p| p.file.as_str()).collect();
+        let mut dir = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name != INDEX_FILE && !keep.contains(name.as_ref()) {
+                let _ = tokio::fs::remove_file(entry.path()).await;
+            }
+        }
+        Ok(photos)
+    }
+
+    async fn fetch(&self, photo: &CopiedPhoto) -> Result<Option<ImageMeta>> {
+        let path = self.dir.join(&photo.file);
+        if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            tracing::debug!(asset_id = %photo.id, "google photos copy missing; removing from cache");
+            self.remove_asset_from_cache(&photo.id)?;
+            return Ok(None);
+        }
+        Ok(Some(ImageMeta {
+            data: SourceData::Path(path),
+            orientation: photo.orientation,
+            date_taken: photo.date_taken,
+            exif_blob: None, // The date is added to the image's EXIF when loading
+            id: Some(photo.id.clone()),
+            source_id: None, // Will be set when returning from next()
+            asset_metadata: Some(photo.metadata.clone()),
+        }))
+    }
+}
+
+/// Name of a copied item's file, derived from its id since ids aren't safe file names.
+fn copy_file_name(id: &str) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    id.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+async fn load_index(dir: &Path) -> Option<CopiedSession> {
+    let path = dir.join(INDEX_FILE);
+    let bytes = tokio::fs::read(&path).await.ok()?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| tracing::warn!(path=%path.display(), error=%e, "ignoring unreadable google photos index"))
+        .ok()
+}
+
+async fn save_index(dir: &Path, copied: &CopiedSession) -> Result<()> {
+    let path = dir.join(INDEX_FILE);
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(copied)?)
+        .await
+        .with_context(|| format!("writing {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .with_context(|| format!("renaming {}", path.display()))?;
+    Ok(())
+}
+
+/// Listing entry for a picked media item; videos are skipped.
+fn entry_from_item(item: serde_json::Value) -> Option<PickedItem> {
+    if item.get("type").and_then(|v| v.as_str()) != Some("PHOTO") {
+        return None;
+    }
+    let file = item.get("mediaFile")?;
+    let base_url = file.get("baseUrl");
+    let meta = file.get("mediaFileMetadata");
+    let created = item.get("createTime");
+    let dim = |key| {
+        let v = meta?.get(key)?;
+        v.as_u64()
+            .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+            .map(|n| n as u32)
+    };
+    let orientation = Orientation::from_dims(dim("width").unwrap_or(0), dim("height").unwrap_or(0));
+    let date_taken = created
+        .and_then(|v| v.as_str())
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc));
+    Some(PickedItem {
+        id: item.get("id")?.as_str()?.to_string(),
+        base_url: base_url?.as_str()?.to_string(),
+        orientation,
+        date_taken,
+        metadata: item,
+    })
+}
+
+#[async_trait]
+impl ImageSource for GooglePhotosImageSource {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        if let Err(e) = self.list_if_needed().await {
+            tracing::warn!(error = %format!("{e:#}"), "google photos session check failed");
+        }
+        let snapshot: Vec<CopiedPhoto> = { self.copied.read().photos.clone() };
+        let items: Vec<(&str, Orientation)> = snapshot
+            .iter()
+            .map(|p| (p.id.as_str(), p.orientation))
+            .collect();
+        let order = self.cfg.order.unwrap_or_default();
+        remote::pick_by_order(order, &items, ctx, &self.cursor, &self.bags, |i| {
+            self.fetch(&snapshot[i])
+        })
+        .await
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        // force the next call to check the session now
+        self.last_check.store(0, AtomicOrdering::Relaxed);
+        self.list_if_needed().await
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        if let Err(e) = self.list_if_needed().await {
+            tracing::warn!(error = %format!("{e:#}"), "google photos session check failed");
+        }
+        let all: Vec<AssetSummary> = self
+            .copied
+            .read()
+            .photos
+            .iter()
+            .map(|p| AssetSummary {
+                checksum: None,
+                id: p.id.clone(),
+                orientation: p.orientation,
+                date_taken: p.date_taken,
+            })
+            .collect();
+        Ok(AssetPage::from_summaries(all, query))
+    }
+
+    async fn get(&self, id: &str, _ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        if let Err(e) = self.list_if_needed().await {
+            tracing::warn!(error = %format!("{e:#}"), "google photos session check failed");
+        }
+        let photo = self
+            .copied
+            .read()
+            .photos
+            .iter()
+            .find(|p| p.id == id)
+            .cloned();
+        match photo {
+            Some(photo) => self.fetch(&photo).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Healthy once the configured session's photos are copied; until then the detail
+    /// says whether the user still has time to pick them.
+    async fn health(&self) -> SourceHealth {
+        // Errors are recorded in `last_error`.
+        let _ = self.list_if_needed().await;
+        let copied = self.copied.read().clone();
+        let last_refresh = copied.copied_at;
+        if let Some(reason) = self.unconfigured() {
+            return SourceHealth::unhealthy(reason, last_refresh);
+        }
+        if let Some(error) = self.last_error.lock().clone() {
+            return SourceHealth::unhealthy(error, last_refresh);
+        }
+        if self.cfg.picker_session_id.as_deref() == Some(copied.session_id.as_str()) {
+            return SourceHealth {
+                detail: Some(format!(
+                    "{} photos copied from the picker session",
+                    copied.photos.len()
+                )),
+                ..SourceHealth::healthy(last_refresh)
+            };
+        }
+        let waiting = match *self.expire_time.lock() {
+            Some(t) => format!("waiting for photos to be picked; the session expires at {t}"),
+            None => "waiting for photos to be picked".to_string(),
+        };
+        SourceHealth::unhealthy(waiting, last_refresh)
+    }
+
+    fn stats(&self) -> SourceStats {
+        SourceStats::from_orientations(self.copied.read().photos.iter().map(|p| p.orientation))
+    }
+
+    // Photos are served from the local copy, which has the same size for every frame.
+    fn serves_originals(&self) -> bool {
+        true
+    }
+
+    fn remove_asset_from_cache(&self, asset_id: &str) -> Result<bool> {
+        let mut copied = self.copied.write();
+        let before = copied.photos.len();
+        copied.photos.retain(|p| p.id != asset_id);
+        Ok(before != copied.photos.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+    use axum::extract::{Form, Path, Query, State};
+    use axum::http::{HeaderMap, StatusCode, header};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct MockGoogle {
+        /// Token polls answered with `authorization_pending` before approving.
+        pending_polls: usize,
+        refreshes: usize,
+        /// Whether the user finished picking in `sess1`.
+        picked: bool,
+        /// Picker listing queries, media paths and deleted sessions requested.
+        listings: Vec<HashMap<String, String>>,
+        downloads: Vec<String>,
+        deleted: Vec<String>,
+    }
+
+    type Shared = Arc<parking_lot::Mutex<MockGoogle>>;
+
+    fn authorized(headers: &HeaderMap) -> bool {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            == Some("Bearer at2")
+    }
+
+    async fn device_code(Form(form): Form<HashMap<String, String>>) -> Response {
+        assert_eq!(form["client_id"], "cid");
+        assert_eq!(form["scope"], PICKER_SCOPE);
+        Json(json!({
+            "device_code": "dev1", "user_code": "ABCD-EFGH",
+            "verification_url": "https://www.google.com/device",
+            "expires_in": 60, "interval": 0,
+        }))
+        .into_response()
+    }
+
+    async fn token(
+        State(state): State<Shared>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Response {
+        assert_eq!(form["client_secret"], "csecret");
+        let mut state = state.lock();
+        match form["grant_type"].as_str() {
+            "urn:ietf:params:oauth:grant-type:device_code" if state.pending_polls > 0 => {
+                state.pending_polls -= 1;
+                (
+                    StatusCode::PRECONDITION_REQUIRED,
+                    Json(json!({"error": "authorization_pending"})),
+                )
+                    .into_response()
+            }
+            "urn:ietf:params:oauth:grant-type:device_code" => Json(json!({
+                "access_token": "at1", "refresh_token": "rt1",
+                "expires_in": 3599, "token_type": "Bearer",
+            }))
+            .into_response(),
+            "refresh_token" if form["refresh_token"] == "rt1" => {
+                state.refreshes += 1;
+                Json(json!({"access_token": "at2", "expires_in": 3599, "token_type": "Bearer"}))
+                    .into_response()
+            }
+            _ => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_grant"})),
+            )
+                .into_response(),
+        }
+    }
+
+    fn picked_item(base: &str, id: &str, kind: &str) -> Value {
+        json!({
+            "id": id, "createTime": "2023-04-05T06:07:08Z", "type": kind,
+            "mediaFile": {
+                "baseUrl": format!("{base}/media/{id}"), "mimeType": "image/jpeg",
+                "mediaFileMetadata": {"width": 3000, "height": 4000},
+            },
+        })
+    }
+
+    async fn session(
+        State(state): State<Shared>,
+        headers: HeaderMap,
+        Path(id): Path<String>,
+    ) -> Response {
+        if !authorized(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        // `sess2` was created a day ago and never picked in.
+        let (expire, picked) = match id.as_str() {
+            "sess1" => ("2099-01-01T00:00:00Z", state.lock().picked),
+            _ => ("2000-01-01T00:00:00Z", false),
+        };
+        Json(json!({
+            "id": id, "pickerUri": format!("https://photos.google.com/picker/{id}"),
+            "pollingConfig": {"pollInterval": "5s", "timeoutIn": "1800s"},
+            "expireTime": expire, "mediaItemsSet": picked,
+        }))
+        .into_response()
+    }
+
+    async fn delete_session(State(state): State<Shared>, Path(id): Path<String>) -> StatusCode {
+        state.lock().deleted.push(id);
+        StatusCode::OK
+    }
+
+    async fn picked(
+        State(state): State<Shared>,
+        headers: HeaderMap,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Response {
+        if !authorized(&headers) || query["sessionId"] != "sess1" {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        let host = headers[header::HOST].to_str().unwrap();
+        let base = format!("http://{host}");
+        state.lock().listings.push(query.clone());
+        match query.get("pageToken") {
+            None => Json(json!({
+                "mediaItems": [picked_item(&base, "a1", "PHOTO"), picked_item(&base, "v1", "VIDEO")],
+                "nextPageToken": "page2",
+            })),
+            Some(_) => Json(json!({"mediaItems": [picked_item(&base, "a2", "PHOTO")]})),
+        }
+        .into_response()
+    }
+
+    async fn media(
+        State(state): State<Shared>,
+        headers: HeaderMap,
+        Path(file): Path<String>,
+    ) -> Response {
+        if !authorized(&headers) {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        state.lock().downloads.push(file.clone());
+        ([(header::CONTENT_TYPE, "image/jpeg")], file).into_response()
+    }
+
+    #[tokio::test]
+    async fn device_flow_then_lists_picker_session() {
+        let state: Shared = Arc::new(parking_lot::Mutex::new(MockGoogle {
+            pending_polls: 1,
+            ..Default::default()
+        }));
+        let app = Router::new()
+            .route("/device/code", post(device_code))
+            .route("/token", post(token))
+            .route("/v1/sessions/{id}", get(session).delete(delete_session))
+            .route("/v1/mediaItems", get(picked))
+            .route("/media/{file}", get(media))
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+        let base = format!("http://{addr}");
+        let endpoints = GoogleEndpoints {
+            device_auth: format!("{base}/device/code"),
+            token: format!("{base}/token"),
+            picker: base.clone(),
+        };
+
+        let mut cfg = GooglePhotosSource {
+            client_id: Some("cid".into()),
+            client_secret: Some("csecret".into()),
+            ..Default::default()
+        };
+        let details = start_device_flow(&cfg, &endpoints).await.unwrap();
+        assert_eq!(details.user_code().secret(), "ABCD-EFGH");
+        let refresh_token = finish_device_flow(&cfg, &endpoints, &details)
+            .await
+            .unwrap();
+        assert_eq!(refresh_token, "rt1");
+        assert_eq!(state.lock().pending_polls, 0);
+
+        cfg.refresh_token = Some(refresh_token);
+        let source =
+            GooglePhotosImageSource::with_endpoints("test_google", &cfg, endpoints.clone())
+                .unwrap();
+        assert!(!source.health().await.healthy);
+
+        cfg.picker_session_id = Some("sess1".into());
+        let id = format!("test_google_{}", std::process::id());
+        let source = GooglePhotosImageSource::with_endpoints(&id, &cfg, endpoints.clone()).unwrap();
+        source.refresh().await.unwrap();
+        let health = source.health().await;
+        assert!(!health.healthy);
+        assert!(health.detail.unwrap().contains("expires at 2099-01-01"));
+        assert!(state.lock().listings.is_empty());
+
+        state.lock().picked = true;
+        source.refresh().await.unwrap();
+        let page = source
+            .list(&ListQuery {
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let ids: Vec<_> = page.items.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["a1", "a2"]);
+        assert_eq!(page.items[0].orientation, Orientation::Portrait);
+        let listings = std::mem::take(&mut state.lock().listings);
+        assert_eq!(listings[1]["pageToken"], "page2");
+        assert_eq!(state.lock().downloads, ["a1=w4096-h4096", "a2=w4096-h4096"]);
+        assert_eq!(state.lock().deleted, ["sess1"]);
+        assert!(source.health().await.healthy);
+        // The access token is reused after the first refresh.
+        assert_eq!(state.lock().refreshes, 1);
+
+        // Copies are served from disk, also by a fresh instance after a restart.
+        let source = GooglePhotosImageSource::with_endpoints(&id, &cfg, endpoints.clone()).unwrap();
+        let meta = source
+            .get("a1", &SelectionContext::default())
+            .await
+            .unwrap()
+            .unwrap();
+        match &meta.data {
+            SourceData::Path(p) => assert_eq!(std::fs::read(p).unwrap(), b"a1=w4096-h4096"),
+            _ => panic!("expected a path"),
+        }
+        assert_eq!(
+            meta.date_taken,
+            Some(
+                DateTime::parse_from_rfc3339("2023-04-05T06:07:08Z")
+                    .unwrap()
+                    .with_timezone(&Utc)
+            )
+        );
+        assert_eq!(meta.asset_metadata.unwrap()["id"], "a1");
+        assert!(source.health().await.healthy);
+        assert!(state.lock().listings.is_empty());
+        assert_eq!(state.lock().downloads.len(), 2);
+
+        // A new session that expires unpicked is reported, while the old copies stay.
+        cfg.picker_session_id = Some("sess2".into());
+        let source = GooglePhotosImageSource::with_endpoints(&id, &cfg, endpoints).unwrap();
+        assert!(source.refresh().await.is_err());
+        let health = source.health().await;
+        assert!(!health.healthy);
+        assert!(health.detail.unwrap().contains("expired"));
+        assert_eq!(source.stats().total, 2);
+
+        let _ = std::fs::remove_dir_all(format!("{id}_google_photos"));
+    }
+}
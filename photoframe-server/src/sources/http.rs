@@ -0,0 +1,545 @@
+//! Generic HTTP source for services exposing images over HTTP. In list mode a JSON
+//! listing at `list_url` is mapped to images through JSON pointers; items without
+//! dimensions have their header probed like WebDAV files. In single-URL mode
+//! `image_url` (e.g. a webcam snapshot) is downloaded again on every selection.
+//!
+//! The configured `headers` usually carry credentials, so they are only sent to URLs on
+//! the same origin (scheme, host and port) as `list_url` or `image_url`.
+
+use super::remote::{self, RemoteFile};
+use super::{
+    AssetPage, AssetSummary, ImageMeta, ImageSource, ListQuery, SelectionContext, ShuffleBagStore,
+    SourceData, SourceHealth, SourceStats,
+};
+use crate::config::{HttpFieldMap, HttpSource, Orientation};
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+
+/// Default seconds between re-listings.
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 3_600;
+
+pub struct HttpImageSource {
+    cfg: HttpSource,
+    headers: reqwest::header::HeaderMap,
+    /// Where `headers` may be sent: the configured URL.
+    trusted: Option<reqwest::Url>,
+    entries: parking_lot::RwLock<Vec<RemoteFile>>,
+    last_list: AtomicU64,  // unix seconds of last listing, 0 = never
+    cursor: AtomicUsize,   // for sequential order
+    bags: ShuffleBagStore, // for shuffle order
+    client: reqwest::Client,
+    // Serializes listings so concurrent callers don't list twice.
+    sync_lock: tokio::sync::Mutex<()>,
+    /// Why the last listing or snapshot failed, reported through `health`.
+    last_error: parking_lot::Mutex<Option<String>>,
+}
+
+impl HttpImageSource {
+    pub fn new(id: &str, cfg: &HttpSource) -> Result<Self> {
+        if cfg.list_url.is_some() && cfg.image_url.is_some() {
+            bail!("http source takes either list_url or image_url, not both");
+        }
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &cfg.headers {
+            headers.insert(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("invalid header name {name}"))?,
+                reqwest::header::HeaderValue::from_str(value)
+                    .with_context(|| format!("invalid value for header {name}"))?,
+            );
+        }
+        let trusted = cfg
+            .list_url
+            .as_deref()
+            .or(cfg.image_url.as_deref())
+            .map(|url| {
+                reqwest::Url::parse(url).with_context(|| format!("invalid http source url {url}"))
+            })
+            .transpose()?;
+        Ok(Self {
+            cfg: cfg.clone(),
+            headers,
+            trusted,
+            entries: parking_lot::RwLock::new(Vec::new()),
+            last_list: AtomicU64::new(0),
+            cursor: AtomicUsize::new(0),
+            bags: ShuffleBagStore::new(id),
+            client: reqwest::Client::new(),
+            sync_lock: tokio::sync::Mutex::new(()),
+            last_error: parking_lot::Mutex::new(None),
+        })
+    }
+
+    /// A GET for `url`, with the configured headers if it is on the configured origin.
+    fn get_req(&self, url: &str) -> reqwest::RequestBuilder {
+        let same_origin = match (&self.trusted, reqwest::Url::parse(url)) {
+            (Some(trusted), Ok(url)) => trusted.origin() == url.origin(),
+            _ => false,
+        };
+        let req = self.client.get(url);
+        if same_origin {
+            req.headers(self.headers.clone())
+        } else {
+            req
+        }
+    }
+
+    async fn list_if_needed(&self) -> Result<()> {
+        let result = self.sync_if_needed().await;
+        *self.last_error.lock() = result.as_ref().err().map(|e| format!("{e:#}"));
+        result
+    }
+
+    async fn sync_if_needed(&self) -> Result<()> {
+        let Some(list_url) = &self.cfg.list_url else {
+            return Ok(());
+        };
+        let _guard = self.sync_lock.lock().await;
+        let now = Utc::now().timestamp() as u64;
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let interval = self
+            .cfg
+            .refresh_interval_secs
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL_SECS);
+        if last != 0 && now.saturating_sub(last) < interval {
+            return Ok(());
+        }
+
+        let base = reqwest::Url::parse(list_url)
+            .with_context(|| format!("invalid http list_url {list_url}"))?;
+        let resp = self
+            .get_req(list_url)
+            .send()
+            .await
+            .with_context(|| format!("http list {list_url}"))?;
+        if !resp.status().is_success() {
+            bail!("http list {list_url} failed: {}", resp.status());
+        }
+        let body: serde_json::Value = resp
+            .json()
+            .await
+            .with_context(|| format!("decoding http listing {list_url}"))?;
+        let (mut files, unprobed) = map_listing(&body, &self.cfg.fields, &base)?;
+        // Listings carry no ETags; an item still at the same URL keeps its probed metadata.
+        let (reused, unprobed) =
+            remote::reuse_matching(&self.entries.read(), unprobed, |old, new| {
+                old.url == new.url
+            });
+        files.extend(reused);
+        let probed = unprobed.len();
+        files.extend(remote::probe_all(unprobed, |f| Some(self.get_req(&f.url))).await);
+        tracing::info!(url = %list_url, total = files.len(), probed, "Listed http source");
+        *self.entries.write() = files;
+        self.last_list.store(now, AtomicOrdering::Relaxed);
+        Ok(())
+    }
+
+    async fn fetch(&self, file: &RemoteFile) -> Result<Option<ImageMeta>> {
+        let resp = self
+            .get_req(&file.url)
+            .send()
+            .await
+            .with_context(|| format!("downloading {}", file.url))?;
+        match resp.status() {
+            reqwest::StatusCode::NOT_FOUND => {
+                tracing::debug!(asset_id = %file.id, "http image gone; removing from cache");
+                self.remove_asset_from_cache(&file.id)?;
+                Ok(None)
+            }
+            status if !status.is_success() => {
+                tracing::warn!(asset_id = %file.id, %status, "http download failed");
+                Ok(None)
+            }
+            _ => {
+                let bytes = resp.bytes().await?.to_vec();
+                tracing::info!(asset_id = %file.id, bytes = bytes.len(), "Downloaded http image");
+                Ok(Some(file.image_meta(bytes)))
+            }
+        }
+    }
+
+    /// Download the single-URL image. Each download is a new asset, identified by when
+    /// it was taken; orientation and date come from the image itself.
+    async fn snapshot(&self, image_url: &str) -> Result<ImageMeta> {
+        let resp = self
+            .get_req(image_url)
+            .send()
+            .await
+            .with_context(|| format!("downloading {image_url}"))?;
+        if !resp.status().is_success() {
+            bail!("http snapshot {image_url} failed: {}", resp.status());
+        }
+        let bytes = resp.bytes().await?.to_vec();
+        let (orientation, date_taken) = remote::header_metadata(&bytes)
+            .ok_or_else(|| anyhow!("http snapshot {image_url} is not a decodable image"))?;
+        let fetched = Utc::now();
+        tracing::info!(url = %image_url, bytes = bytes.len(), "Downloaded http snapshot");
+        self.last_list
+            .store(fetched.timestamp() as u64, AtomicOrdering::Relaxed);
+        Ok(ImageMeta {
+            data: SourceData::Bytes(bytes),
+            orientation,
+            date_taken,
+            exif_blob: None, // Extracted from the bytes when loading
+            id: Some(format!("snapshot-{}", fetched.format("%Y%m%dT%H%M%S%.6fZ"))),
+            source_id: None, // Will be set when returning from next()
+            asset_metadata: None,
+        })
+    }
+
+    async fn next_snapshot(
+        &self,
+        image_url: &str,
+        ctx: &SelectionContext,
+    ) -> Result<Option<ImageMeta>> {
+        let result = self.snapshot(image_url).await;
+        *self.last_error.lock() = result.as_ref().err().map(|e| format!("{e:#}"));
+        match result {
+            Ok(meta) if ctx.accepts(meta.id.as_deref().unwrap_or_default(), meta.orientation) => {
+                Ok(Some(meta))
+            }
+            Ok(_) => Ok(None),
+            Err(e) => {
+                tracing::warn!(error = %format!("{e:#}"), "http snapshot failed");
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Map a JSON listing to files. Files whose listing gives both dimensions are returned
+/// first; the rest still need their header probed.
+fn map_listing(
+    body: &serde_json::Value,
+    fields: &HttpFieldMap,
+    base: &reqwest::Url,
+) -> Result<(Vec<RemoteFile>, Vec<RemoteFile>)> {
+    let items = body
+        .pointer(&fields.items)
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow!("no array at {:?} in http listing", fields.items))?;
+    let mut known = Vec::new();
+    let mut unprobed = Vec::new();
+    for item in items {
+        let field = |pointer: Option<&String>| pointer.and_then(|p| item.pointer(p));
+        let id = match field(Some(&fields.id)) {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Number(n)) => n.to_string(),
+            _ => {
+                tracing::debug!(item = %item, "http item without id; skipping");
+                continue;
+            }
+        };
+        let Some(url) = field(Some(&fields.url))
+            .and_then(|v| v.as_str())
+            .and_then(|u| base.join(u).ok())
+        else {
+            tracing::debug!(asset_id = %id, "http item without image url; skipping");
+            continue;
+        };
+        let dim = |pointer| {
+            let v = field(pointer)?;
+            v.as_u64()
+                .or_else(|| v.as_str().and_then(|s| s.trim().parse().ok()))
+                .map(|n| n as u32)
+        };
+        let mut file = RemoteFile::listed(id, url.to_string(), None);
+        file.date_taken = field(fields.date.as_ref()).and_then(parse_date);
+        match (dim(fields.width.as_ref()), dim(fields.height.as_ref())) {
+            (Some(w), Some(h)) => {
+                file.orientation = Some(Orientation::from_dims(w, h));
+                known.push(file);
+            }
+            _ => unprobed.push(file),
+        }
+    }
+    Ok((known, unprobed))
+}
+
+/// An RFC 3339 string or Unix seconds.
+fn parse_date(v: &serde_json::Value) -> Option<DateTime<Utc>> {
+    match v {
+        serde_json::Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|d| d.with_timezone(&Utc)),
+        serde_json::Value::Number(n) => DateTime::from_timestamp(n.as_i64()?, 0),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl ImageSource for HttpImageSource {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        if let Some(image_url) = &self.cfg.image_url {
+            return self.next_snapshot(image_url, ctx).await;
+        }
+        if let Err(e) = self.list_if_needed().await {
+            tracing::warn!(error = %format!("{e:#}"), "http listing failed");
+        }
+        let snapshot: Vec<RemoteFile> = { self.entries.read().clone() };
+        let items: Vec<(&str, Orientation)> = snapshot
+            .iter()
+            .map(|f| (f.id.as_str(), f.orientation()))
+            .collect();
+        let order = self.cfg.order.unwrap_or_default();
+        remote::pick_by_order(order, &items, ctx, &self.cursor, &self.bags, |i| {
+            self.fetch(&snapshot[i])
+        })
+        .await
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        // force next call to list to actually list now
+        self.last_list.store(0, AtomicOrdering::Relaxed);
+        self.list_if_needed().await
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        self.list_if_needed().await?;
+        let all: Vec<AssetSummary> = self
+            .entries
+            .read()
+            .iter()
+            .map(|f| AssetSummary {
+                checksum: None,
+                id: f.id.clone(),
+                orientation: f.orientation(),
+                date_taken: f.date_taken,
+            })
+            .collect();
+        Ok(AssetPage::from_summaries(all, query))
+    }
+
+    async fn get(&self, id: &str, _ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        if let Some(image_url) = &self.cfg.image_url {
+            // Earlier snapshots aren't kept; showing one shows the current image.
+            return self.snapshot(image_url).await.map(Some);
+        }
+        let file = self.entries.read().iter().find(|f| f.id == id).cloned();
+        match file {
+            Some(file) => self.fetch(&file).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn health(&self) -> SourceHealth {
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let last_refresh = (last > 0)
+            .then(|| DateTime::from_timestamp(last as i64, 0))
+            .flatten();
+        let Some(url) = self.cfg.list_url.as_ref().or(self.cfg.image_url.as_ref()) else {
+            return SourceHealth::unhealthy("neither list_url nor image_url configured", None);
+        };
+        if let Some(error) = self.last_error.lock().clone() {
+            return SourceHealth::unhealthy(format!("last request failed: {error}"), last_refresh);
+        }
+        match self.get_req(url).send().await {
+            Ok(resp) if resp.status().is_success() => SourceHealth::healthy(last_refresh),
+            Ok(resp) => SourceHealth::unhealthy(
+                format!("{url} responded with status {}", resp.status()),
+                last_refresh,
+            ),
+            Err(e) => SourceHealth::unhealthy(format!("{url} unreachable: {e}"), last_refresh),
+        }
+    }
+
+    fn stats(&self) -> SourceStats {
+        SourceStats::from_orientations(self.entries.read().iter().map(|f| f.orientation()))
+    }
+
+    fn serves_originals(&self) -> bool {
+        true
+    }
+
+    fn remove_asset_from_cache(&self, asset_id: &str) -> Result<bool> {
+        let mut entries = self.entries.write();
+        let before = entries.len();
+        entries.retain(|f| f.id != asset_id);
+        Ok(before != entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode, header};
+    use axum::response::{IntoResponse, Response};
+    use axum::{Json, Router, routing::get};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn authorized(headers: &HeaderMap) -> bool {
+        headers.get("x-api-key").and_then(|v| v.to_str().ok()) == Some("k1")
+    }
+
+    /// Image requests served: (host, path, whether the API key came along).
+    type Requests = Arc<parking_lot::Mutex<Vec<(String, String, bool)>>>;
+
+    async fn gallery(headers: HeaderMap) -> Response {
+        if !authorized(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        // Same server under another name, so another origin.
+        let port = headers[header::HOST]
+            .to_str()
+            .unwrap()
+            .rsplit(':')
+            .next()
+            .unwrap();
+        Json(json!({"data": {"drawings": [
+            {"uid": 7, "image": {"src": "/img/7.png", "w": "30", "h": "20"}, "created": 1700000000},
+            {"uid": "x", "image": {"src": "img/x.png"}, "created": "2024-02-03T04:05:06+01:00"},
+            {"uid": "ext", "image": {"src": format!("http://localhost:{port}/ext.png")}},
+            {"uid": "no-url", "image": {}},
+        ]}}))
+        .into_response()
+    }
+
+    async fn image(
+        State(requests): State<Requests>,
+        headers: HeaderMap,
+        uri: axum::http::Uri,
+    ) -> Response {
+        let host = headers[header::HOST].to_str().unwrap().to_string();
+        requests
+            .lock()
+            .push((host, uri.path().to_string(), authorized(&headers)));
+        let bytes = match uri.path() {
+            "/ext.png" => test_support::png(20, 30),
+            _ if !authorized(&headers) => return StatusCode::UNAUTHORIZED.into_response(),
+            "/img/7.png" => test_support::png(30, 20),
+            "/api/img/x.png" => test_support::png(20, 30),
+            "/cam.png" => test_support::png(40, 30),
+            _ => return StatusCode::NOT_FOUND.into_response(),
+        };
+        ([(header::CONTENT_TYPE, "image/png")], bytes).into_response()
+    }
+
+    async fn serve_gallery() -> (std::net::SocketAddr, Requests) {
+        let requests = Requests::default();
+        let app = Router::new()
+            .route("/api/gallery", get(gallery))
+            .fallback(image)
+            .with_state(requests.clone());
+        (test_support::serve(app).await, requests)
+    }
+
+    fn headers() -> HashMap<String, String> {
+        HashMap::from([("X-Api-Key".to_string(), "k1".to_string())])
+    }
+
+    fn gallery_source(addr: std::net::SocketAddr) -> HttpImageSource {
+        let cfg = HttpSource {
+            list_url: Some(format!("http://{addr}/api/gallery")),
+            headers: headers(),
+            fields: HttpFieldMap {
+                items: "/data/drawings".into(),
+                id: "/uid".into(),
+                url: "/image/src".into(),
+                width: Some("/image/w".into()),
+                height: Some("/image/h".into()),
+                date: Some("/created".into()),
+            },
+            ..Default::default()
+        };
+        HttpImageSource::new("test_http", &cfg).unwrap()
+    }
+
+    #[tokio::test]
+    async fn maps_json_listing_fields() {
+        let (addr, _) = serve_gallery().await;
+        let source = gallery_source(addr);
+        let page = source
+            .list(&ListQuery {
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let listed: Vec<_> = page
+            .items
+            .iter()
+            .map(|a| {
+                (
+                    a.id.as_str(),
+                    a.orientation,
+                    a.date_taken.map(|d| d.timestamp()),
+                )
+            })
+            .collect();
+        // `x` has no dimensions in the listing, so they were probed from the image.
+        assert_eq!(
+            listed,
+            [
+                ("7", Orientation::Landscape, Some(1_700_000_000)),
+                ("x", Orientation::Portrait, Some(1_706_929_506)),
+                ("ext", Orientation::Portrait, None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_headers_only_to_the_listing_origin() {
+        let (addr, requests) = serve_gallery().await;
+        gallery_source(addr).refresh().await.unwrap();
+        let mut probes = requests.lock().clone();
+        probes.sort();
+        assert_eq!(
+            probes,
+            [
+                (addr.to_string(), "/api/img/x.png".to_string(), true),
+                (
+                    format!("localhost:{}", addr.port()),
+                    "/ext.png".to_string(),
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reuses_probed_items_on_relisting() {
+        let (addr, requests) = serve_gallery().await;
+        let source = gallery_source(addr);
+        source.refresh().await.unwrap();
+        assert_eq!(requests.lock().drain(..).count(), 2);
+        source.refresh().await.unwrap();
+        assert!(requests.lock().is_empty());
+        assert_eq!(source.stats().portrait, 2);
+    }
+
+    #[tokio::test]
+    async fn fetches_listed_items() {
+        let (addr, _) = serve_gallery().await;
+        let source = gallery_source(addr);
+        source.refresh().await.unwrap();
+        let ctx = SelectionContext::default();
+        let meta = source.get("x", &ctx).await.unwrap().unwrap();
+        match meta.data {
+            SourceData::Bytes(bytes) => assert_eq!(bytes, test_support::png(20, 30)),
+            _ => panic!("expected bytes"),
+        }
+    }
+
+    #[tokio::test]
+    async fn snapshot_url_yields_a_new_image_each_time() {
+        let (addr, _) = serve_gallery().await;
+        let cfg = HttpSource {
+            image_url: Some(format!("http://{addr}/cam.png")),
+            headers: headers(),
+            ..Default::default()
+        };
+        let source = HttpImageSource::new("test_http_cam", &cfg).unwrap();
+        let ctx = SelectionContext::default();
+        let first = source.next(&ctx).await.unwrap().unwrap();
+        let second = source.next(&ctx).await.unwrap().unwrap();
+        assert_eq!(first.orientation, Orientation::Landscape);
+        assert_ne!(first.id, second.id);
+        assert!(source.health().await.healthy);
+    }
+}
@@ -0,0 +1,2250 @@
+//! Immich source. Assets are listed through `/api/search/metadata` and then kept up to
+//! date incrementally: each sync only asks for assets updated after the newest
+//! `updatedAt` seen so far (including trashed ones, which are dropped). A periodic full
+//! listing catches permanently deleted assets and assets that no longer match the
+//! filters. The listing and cursor are persisted as `<source_id>_immich_sync.json`.
+//!
+//! With `smart_query` set, assets come from `/api/search/smart` instead. Those results
+//! are ranked by relevance rather than filtered, so they are always re-listed in full
+//! and only the first `max_pages` pages (default 1) are used.
+//!
+//! With `memories` set, the listing is today's Immich memories ("on this day"), falling
+//! back to the filtered search when there are none. It is re-listed when the local date
+//! (in the scheduler timezone) changes instead of on `refresh_interval_secs`.
+//!
+//! A source can merge several accounts: API keys and shared links (`key=`), on one or
+//! more servers. Each keeps its own listing and cursor; shared links have no search API,
+//! so they are re-listed in full on every sync. Assets present in several accounts are
+//! shown once, from the first account listing them, matched by id or checksum.
+
+use super::{
+    AssetAction, AssetPage, AssetSummary, ImageMeta, ImageSource, ListQuery, SelectionContext,
+    ShuffleBagStore, SourceData, SourceHealth, SourceStats, Thumbnail,
+};
+use crate::config::{ImmichSource, NameMatch, OrderKind, Orientation};
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone, Utc};
+use rand::{Rng, rng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default seconds between incremental syncs.
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 3_600;
+/// Default seconds between full re-listings.
+const DEFAULT_FULL_SYNC_INTERVAL_SECS: u64 = 7 * 86_400;
+/// Album used by `add_to_album` actions that don't name one.
+const DEFAULT_ACTION_ALBUM: &str = "Frame favorites";
+/// Successive header sizes fetched from an original to find its EXIF. JPEG APP1
+/// segments fit in 64 KiB; HEIC and some RAW layouts need more.
+const EXIF_PROBE_BYTES: [u64; 2] = [64 * 1024, 1024 * 1024];
+
+/// Listing state persisted between runs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncState {
+    /// Accounts and filters the assets were listed with; a mismatch forces a full sync.
+    fingerprint: String,
+    /// Unix seconds of the last successful sync (full or incremental).
+    last_sync: u64,
+    /// Local date the memories listing was made for, in `memories` mode.
+    #[serde(default)]
+    memories_day: Option<NaiveDate>,
+    /// One per configured account, in order.
+    #[serde(default)]
+    accounts: Vec<AccountState>,
+}
+
+/// Listing of a single account.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct AccountState {
+    /// Newest `updatedAt` seen; the next incremental sync starts from here.
+    cursor: Option<DateTime<Utc>>,
+    /// Unix seconds of the last successful full listing.
+    last_full_sync: u64,
+    assets: Vec<serde_json::Value>,
+}
+
+/// How an account authenticates.
+#[derive(Debug, Clone)]
+enum Credential {
+    /// `x-api-key` header; full API access.
+    ApiKey(String),
+    /// `key` query parameter of a shared link; read-only access to the link's assets.
+    SharedKey(String),
+}
+
+/// One Immich login a source lists assets from.
+#[derive(Debug, Clone)]
+struct Account {
+    base_url: String,
+    credential: Credential,
+}
+
+impl Account {
+    fn new(base_url: &str, api_key: Option<&str>, shared_key: Option<&str>) -> Self {
+        let credential = match shared_key {
+            Some(key) => Credential::SharedKey(key.to_string()),
+            None => Credential::ApiKey(api_key.unwrap_or_default().to_string()),
+        };
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            credential,
+        }
+    }
+
+    /// The source's own `base_url` credentials first, then `accounts` in order.
+    fn from_config(cfg: &ImmichSource) -> Vec<Self> {
+        let mut accounts = Vec::new();
+        if let Some(base) = &cfg.base_url {
+            accounts.push(Self::new(
+                base,
+                cfg.api_key.as_deref(),
+                cfg.shared_key.as_deref(),
+            ));
+        }
+        for extra in &cfg.accounts {
+            let Some(base) = extra.base_url.as_ref().or(cfg.base_url.as_ref()) else {
+                tracing::warn!("ignoring Immich account without base_url");
+                continue;
+            };
+            accounts.push(Self::new(
+                base,
+                extra.api_key.as_deref(),
+                extra.shared_key.as_deref(),
+            ));
+        }
+        accounts
+    }
+
+    /// Short digest of the credential, so the sync state is discarded when it changes
+    /// without storing the key itself.
+    fn credential_digest(&self) -> String {
+        use sha2::{Digest, Sha256};
+        let key = match &self.credential {
+            Credential::ApiKey(key) | Credential::SharedKey(key) => key,
+        };
+        Sha256::digest(key.as_bytes())[..8]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    fn is_shared_link(&self) -> bool {
+        matches!(self.credential, Credential::SharedKey(_))
+    }
+
+    /// Request to `path` (starting with `/api/`) carrying this account's credential.
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        method: reqwest::Method,
+        path: &str,
+    ) -> reqwest::RequestBuilder {
+        let req = client.request(method, format!("{}{path}", self.base_url));
+        match &self.credential {
+            Credential::ApiKey(key) => req.header("x-api-key", key),
+            Credential::SharedKey(key) => req.query(&[("key", key)]),
+        }
+    }
+
+    fn get(&self, client: &reqwest::Client, path: &str) -> reqwest::RequestBuilder {
+        self.request(client, reqwest::Method::GET, path)
+    }
+}
+
+/// Identifies the account in logs and fingerprints without revealing its credential.
+impl std::fmt::Display for Account {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.credential {
+            Credential::ApiKey(_) => write!(f, "{}", self.base_url),
+            Credential::SharedKey(_) => write!(f, "{} (shared link)", self.base_url),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn asset_updated_at(item: &serde_json::Value) -> Option<DateTime<Utc>> {
+    item.get("updatedAt")
+        .and_then(|v| v.as_str())
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+fn asset_id(item: &serde_json::Value) -> Option<&str> {
+    item.get("id").and_then(|v| v.as_str())
+}
+
+/// A listed asset and the index of the account it is fetched through.
+#[derive(Debug, Clone)]
+pub struct ImmichEntry {
+    pub id: String,
+    pub orientation: Orientation,
+    /// Full listing metadata, kept so it doesn't have to be fetched again.
+    pub metadata: serde_json::Value,
+    pub account: usize,
+}
+
+pub struct ImmichImageSource {
+    pub cfg: ImmichSource,
+    /// Deduplicated view over all accounts' listings.
+    pub entries: parking_lot::RwLock<Vec<ImmichEntry>>,
+    pub last_list: AtomicU64, // unix seconds of last sync, 0 = never
+    pub cursor: AtomicUsize,  // for sequential order
+    bags: ShuffleBagStore,    // for shuffle order
+    state_path: PathBuf,
+    accounts: Vec<Account>,
+    account_states: parking_lot::Mutex<Vec<AccountState>>,
+    memories_day: parking_lot::Mutex<Option<NaiveDate>>,
+    // Serializes syncs so concurrent callers don't list twice.
+    sync_lock: tokio::sync::Mutex<()>,
+    /// Why the last sync attempt failed, reported through `health`.
+    last_error: parking_lot::Mutex<Option<String>>,
+    /// Digest of the listing last written to (or read from) `state_path`.
+    saved_digest: parking_lot::Mutex<Option<Vec<u8>>>,
+}
+
+impl ImmichImageSource {
+    pub fn new(id: &str, cfg: &ImmichSource) -> Result<Self> {
+        let accounts = Account::from_config(cfg);
+        let source = Self {
+            cfg: cfg.clone(),
+            entries: parking_lot::RwLock::new(Vec::new()),
+            last_list: AtomicU64::new(0),
+            cursor: AtomicUsize::new(0),
+            bags: ShuffleBagStore::new(id),
+            state_path: PathBuf::from(format!("{id}_immich_sync.json")),
+            account_states: parking_lot::Mutex::new(vec![AccountState::default(); accounts.len()]),
+            accounts,
+            memories_day: parking_lot::Mutex::new(None),
+            sync_lock: tokio::sync::Mutex::new(()),
+            last_error: parking_lot::Mutex::new(None),
+            saved_digest: parking_lot::Mutex::new(None),
+        };
+        source.load_state();
+        Ok(source)
+    }
+
+    fn fingerprint(&self) -> String {
+        let accounts: Vec<String> = self
+            .accounts
+            .iter()
+            .map(|a| format!("{a} {}", a.credential_digest()))
+            .collect();
+        serde_json::json!([
+            accounts,
+            self.cfg.filters,
+            self.cfg.smart_query,
+            self.cfg.albums,
+            self.cfg.people,
+            self.cfg.name_match,
+            self.cfg.memories,
+        ])
+        .to_string()
+    }
+
+    /// Restore the persisted listing, unless it was made with different settings.
+    fn load_state(&self) {
+        let Ok(bytes) = std::fs::read(&self.state_path) else {
+            return;
+        };
+        let state: SyncState = match serde_json::from_slice(&bytes) {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::warn!(path=%self.state_path.display(), error=%e, "ignoring unreadable immich sync state");
+                return;
+            }
+        };
+        if state.fingerprint != self.fingerprint() || state.accounts.len() != self.accounts.len() {
+            tracing::info!(path=%self.state_path.display(), "immich settings changed; discarding sync state");
+            return;
+        }
+        *self.saved_digest.lock() = Self::state_digest(&state).ok();
+        *self.account_states.lock() = state.accounts;
+        *self.memories_day.lock() = state.memories_day;
+        self.last_list
+            .store(state.last_sync, AtomicOrdering::Relaxed);
+        self.rebuild_entries();
+        tracing::debug!(
+            assets = self.entries.read().len(),
+            "restored immich sync state"
+        );
+    }
+
+    /// Digest of everything persisted except the sync time, to tell whether the listing
+    /// changed since it was last written.
+    fn state_digest(state: &SyncState) -> Result<Vec<u8>> {
+        use sha2::{Digest, Sha256};
+        let bytes =
+            serde_json::to_vec(&(&state.fingerprint, &state.memories_day, &state.accounts))?;
+        Ok(Sha256::digest(bytes).to_vec())
+    }
+
+    /// Persist the listing, unless it is unchanged since the last write. Skipping only
+    /// leaves `last_sync` stale, which at worst makes a restarted source sync early.
+    async fn save_state(&self) -> Result<()> {
+        let state = SyncState {
+            fingerprint: self.fingerprint(),
+            last_sync: self.last_list.load(AtomicOrdering::Relaxed),
+            memories_day: *self.memories_day.lock(),
+            accounts: self.account_states.lock().clone(),
+        };
+        let digest = Self::state_digest(&state)?;
+        if self.saved_digest.lock().as_ref() == Some(&digest) {
+            tracing::debug!(path=%self.state_path.display(), "immich listing unchanged; not rewriting sync state");
+            return Ok(());
+        }
+        let tmp = self.state_path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(&state)?)
+            .await
+            .with_context(|| format!("writing {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &self.state_path)
+            .await
+            .with_context(|| format!("renaming {}", self.state_path.display()))?;
+        *self.saved_digest.lock() = Some(digest);
+        Ok(())
+    }
+
+    /// Recompute `entries` from the account listings: blacklisted assets are skipped, and
+    /// an asset whose id or checksum an earlier account already listed is shown only once.
+    fn rebuild_entries(&self) {
+        let states = self.account_states.lock();
+        let mut entries = Vec::new();
+        let mut seen_ids = HashSet::new();
+        let mut seen_checksums = HashSet::new();
+        for (account, state) in states.iter().enumerate() {
+            for item in &state.assets {
+                let Some(id) = asset_id(item) else {
+                    continue;
+                };
+                if self.cfg.blacklist.iter().any(|b| b == id) {
+                    tracing::trace!(asset_id = %id, "Skipping blacklisted asset");
+                    continue;
+                }
+                if !seen_ids.insert(id) {
+                    continue;
+                }
+                if let Some(checksum) = item.get("checksum").and_then(|v| v.as_str())
+                    && !seen_checksums.insert(checksum)
+                {
+                    tracing::trace!(asset_id = %id, account, "Skipping asset listed by another account");
+                    continue;
+                }
+                entries.push(ImmichEntry {
+                    id: id.to_string(),
+                    orientation: immich_asset_orientation(item),
+                    metadata: item.clone(),
+                    account,
+                });
+            }
+        }
+        *self.entries.write() = entries;
+    }
+
+    /// An asset of the source's listing. Assets the filters didn't list are never served,
+    /// even when asked for by id.
+    fn listed_asset(&self, id: &str) -> Option<ImmichEntry> {
+        if let Some(entry) = self.entries.read().iter().find(|e| e.id == id) {
+            return Some(entry.clone());
+        }
+        // Blacklisted assets are left out of `entries` but still belong to the listing.
+        let states = self.account_states.lock();
+        states.iter().enumerate().find_map(|(account, state)| {
+            let item = state.assets.iter().find(|a| asset_id(a) == Some(id))?;
+            Some(ImmichEntry {
+                id: id.to_string(),
+                orientation: immich_asset_orientation(item),
+                metadata: item.clone(),
+                account,
+            })
+        })
+    }
+
+    /// The account that listed an asset.
+    fn account_for(&self, id: &str) -> Option<&Account> {
+        self.accounts.get(self.listed_asset(id)?.account)
+    }
+
+    async fn list_if_needed(&self) -> Result<()> {
+        let result = self.sync_if_needed().await;
+        *self.last_error.lock() = result.as_ref().err().map(|e| format!("{e:#}"));
+        result
+    }
+
+    /// List if needed, falling back to the previous listing if that fails. The error is
+    /// logged and reported through `health`.
+    async fn list_or_keep_cached(&self) {
+        if let Err(e) = self.list_if_needed().await {
+            tracing::warn!(error = %format!("{e:#}"), "immich listing failed; using cached listing");
+        }
+    }
+
+    async fn sync_if_needed(&self) -> Result<()> {
+        if self.accounts.is_empty() {
+            return Ok(());
+        }
+        let _guard = self.sync_lock.lock().await;
+        let now = unix_now();
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let result = if self.cfg.memories {
+            let today = Utc::now()
+                .with_timezone(&crate::scheduler::scheduler_timezone())
+                .date_naive();
+            if last != 0 && *self.memories_day.lock() == Some(today) {
+                return Ok(());
+            }
+            let result = self.memories_sync(today, now).await;
+            if result.is_ok() {
+                *self.memories_day.lock() = Some(today);
+            }
+            result
+        } else {
+            let interval = self
+                .cfg
+                .refresh_interval_secs
+                .unwrap_or(DEFAULT_REFRESH_INTERVAL_SECS);
+            if last != 0 && now.saturating_sub(last) < interval {
+                return Ok(());
+            }
+            self.search_sync(now).await
+        };
+
+        // Accounts that synced are shown even if another one failed, but the
+        // timestamp is only updated (and persisted) once the whole sync succeeded.
+        self.rebuild_entries();
+        result?;
+        self.last_list.store(now, AtomicOrdering::Relaxed);
+        if let Err(e) = self.save_state().await {
+            tracing::warn!(error=%e, "failed to persist immich sync state");
+        }
+        Ok(())
+    }
+
+    /// Name the failing account in errors, when there is more than one.
+    fn in_account(&self, e: anyhow::Error, account: &Account) -> anyhow::Error {
+        if self.accounts.len() > 1 {
+            e.context(format!("account {account}"))
+        } else {
+            e
+        }
+    }
+
+    /// Sync every account, incrementally or in full depending on its cursor and the
+    /// full-sync interval. Returns the last failure after trying all of them.
+    async fn search_sync(&self, now: u64) -> Result<()> {
+        let full_interval = self
+            .cfg
+            .full_sync_interval_secs
+            .unwrap_or(DEFAULT_FULL_SYNC_INTERVAL_SECS);
+        let mut result = Ok(());
+        for (idx, account) in self.accounts.iter().enumerate() {
+            let (cursor, last_full) = {
+                let states = self.account_states.lock();
+                (states[idx].cursor, states[idx].last_full_sync)
+            };
+            let synced = match cursor {
+                Some(since)
+                    if self.cfg.smart_query.is_none()
+                        && now.saturating_sub(last_full) < full_interval =>
+                {
+                    self.incremental_sync(idx, account, since).await
+                }
+                _ => self.full_sync(idx, account, now).await,
+            };
+            if let Err(e) = synced {
+                tracing::warn!(%account, error = %format!("{e:#}"), "immich account sync failed");
+                result = Err(self.in_account(e, account));
+            }
+        }
+        result
+    }
+
+    /// List today's memories of every account (for shared links: assets taken on
+    /// today's date in earlier years), or fall back to full listings when there are none.
+    async fn memories_sync(&self, today: NaiveDate, now: u64) -> Result<()> {
+        let client = reqwest::Client::new();
+        let mut listings = Vec::with_capacity(self.accounts.len());
+        for account in &self.accounts {
+            let items = match account.credential {
+                Credential::ApiKey(_) => self.fetch_memories(&client, account, today).await,
+                Credential::SharedKey(_) => {
+                    self.list_shared_link(&client, account).await.map(|items| {
+                        items
+                            .into_iter()
+                            .filter(|item| is_on_this_day(item, today))
+                            .collect()
+                    })
+                }
+            }
+            .map_err(|e| self.in_account(e, account))?;
+            listings.push(items);
+        }
+        if listings.iter().all(Vec::is_empty) {
+            tracing::info!(%today, "no Immich memories today; falling back to filters");
+            let mut result = Ok(());
+            for (idx, account) in self.accounts.iter().enumerate() {
+                if let Err(e) = self.full_sync(idx, account, now).await {
+                    result = Err(self.in_account(e, account));
+                }
+            }
+            return result;
+        }
+        let mut states = self.account_states.lock();
+        for (state, items) in states.iter_mut().zip(listings) {
+            let (assets, _) = dedupe_listing(items);
+            tracing::info!(%today, assets = assets.len(), "Listed Immich memories");
+            state.assets = assets;
+            state.cursor = None;
+        }
+        Ok(())
+    }
+
+    /// Assets of `today`'s memories for an API-key account.
+    async fn fetch_memories(
+        &self,
+        client: &reqwest::Client,
+        account: &Account,
+        today: NaiveDate,
+    ) -> Result<Vec<serde_json::Value>> {
+        let resp = account
+            .get(client, "/api/memories")
+            .query(&[(
+                "for",
+                local_midnight(today, crate::scheduler::scheduler_timezone())
+                    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            )])
+            .send()
+            .await
+            .context("immich memories")?;
+        if !resp.status().is_success() {
+            bail!("immich memories failed: {}", resp.status());
+        }
+        let memories: serde_json::Value = resp.json().await?;
+        Ok(memories
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|m| {
+                m.get("assets")
+                    .and_then(|a| a.as_array())
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .filter(is_listable_image)
+            .collect())
+    }
+
+    /// Assets of a shared link, including the album's assets for album links.
+    async fn list_shared_link(
+        &self,
+        client: &reqwest::Client,
+        account: &Account,
+    ) -> Result<Vec<serde_json::Value>> {
+        let resp = account
+            .get(client, "/api/shared-links/me")
+            .send()
+            .await
+            .context("immich shared link")?;
+        if !resp.status().is_success() {
+            bail!("immich shared link failed: {}", resp.status());
+        }
+        let link: serde_json::Value = resp.json().await?;
+        let mut items = link
+            .get("assets")
+            .and_then(|a| a.as_array())
+            .cloned()
+            .unwrap_or_default();
+        if let Some(album_id) = link
+            .get("album")
+            .and_then(|a| a.get("id"))
+            .and_then(|v| v.as_str())
+        {
+            let resp = account
+                .get(client, &format!("/api/albums/{album_id}"))
+                .send()
+                .await
+                .context("immich shared album")?;
+            if !resp.status().is_success() {
+                bail!("immich shared album failed: {}", resp.status());
+            }
+            let album: serde_json::Value = resp.json().await?;
+            items.extend(
+                album
+                    .get("assets")
+                    .and_then(|a| a.as_array())
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+        }
+        Ok(items.into_iter().filter(is_listable_image).collect())
+    }
+
+    /// Replace an account's listing: a fresh search over all filters, or everything in
+    /// a shared link.
+    async fn full_sync(&self, idx: usize, account: &Account, now: u64) -> Result<()> {
+        let items = match account.credential {
+            Credential::ApiKey(_) => self.search(account, &serde_json::Map::new()).await?,
+            Credential::SharedKey(_) => {
+                self.list_shared_link(&reqwest::Client::new(), account)
+                    .await?
+            }
+        };
+        let (assets, cursor) = dedupe_listing(items);
+        tracing::info!(
+            %account,
+            total_unique_assets = assets.len(),
+            "Completed full Immich listing"
+        );
+        let mut states = self.account_states.lock();
+        states[idx] = AccountState {
+            // Shared links can't be searched incrementally, so they never get a cursor.
+            cursor: cursor.filter(|_| !account.is_shared_link()),
+            last_full_sync: now,
+            assets,
+        };
+        Ok(())
+    }
+
+    /// Apply assets updated after `since`: trashed ones are removed, others upserted.
+    async fn incremental_sync(
+        &self,
+        idx: usize,
+        account: &Account,
+        since: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut extra = serde_json::Map::new();
+        extra.insert(
+            "updatedAfter".to_string(),
+            serde_json::Value::String(since.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+        );
+        extra.insert("withDeleted".to_string(), serde_json::Value::Bool(true));
+        let items = self.search(account, &extra).await?;
+
+        let mut cursor = Some(since);
+        let (mut upserted, mut removed) = (0usize, 0usize);
+        {
+            let mut states = self.account_states.lock();
+            let assets = &mut states[idx].assets;
+            for item in items {
+                cursor = cursor.max(asset_updated_at(&item));
+                let Some(id) = asset_id(&item).map(str::to_string) else {
+                    continue;
+                };
+                let pos = assets.iter().position(|a| asset_id(a) == Some(id.as_str()));
+                let trashed = item
+                    .get("isTrashed")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                if trashed {
+                    if let Some(pos) = pos {
+                        assets.remove(pos);
+                        removed += 1;
+                    }
+                    continue;
+                }
+                match pos {
+                    Some(pos) => assets[pos] = item,
+                    None => assets.push(item),
+                }
+                upserted += 1;
+            }
+            states[idx].cursor = cursor;
+        }
+        tracing::info!(%account, %since, upserted, removed, "Completed incremental Immich sync");
+        Ok(())
+    }
+
+    /// Run every configured filter (merged with `extra` and type=IMAGE) through
+    /// `/api/search/metadata` (or `/api/search/smart` with the configured query),
+    /// following `nextPage` until exhausted or `max_pages`.
+    async fn search(
+        &self,
+        account: &Account,
+        extra: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Vec<serde_json::Value>> {
+        let client = reqwest::Client::new();
+        let endpoint = match self.cfg.smart_query {
+            Some(_) => "/api/search/smart",
+            None => "/api/search/metadata",
+        };
+
+        // Handle multiple filters by performing multiple searches; callers deduplicate
+        let empty_filters = vec![];
+        let filters_list = self.cfg.filters.as_ref().unwrap_or(&empty_filters);
+
+        // If no filters are configured, perform a single search with just type=IMAGE
+        let filters = if filters_list.is_empty() {
+            vec![serde_json::Value::Object(serde_json::Map::new())]
+        } else {
+            filters_list.clone()
+        };
+        // Named albums/people add their ids to every filter, one search per id for
+        // `any` so results are unioned.
+        let name_filters = self.resolve_name_filters(&client, account).await?;
+        let mut searches = Vec::new();
+        for filter in &filters {
+            if name_filters.is_empty() {
+                searches.push(filter.clone());
+                continue;
+            }
+            for names in &name_filters {
+                let mut merged = filter.as_object().cloned().unwrap_or_default();
+                for (k, ids) in names {
+                    let mut all = merged
+                        .get(k)
+                        .and_then(|v| v.as_array())
+                        .cloned()
+                        .unwrap_or_default();
+                    all.extend(ids.iter().cloned().map(serde_json::Value::String));
+                    merged.insert(k.clone(), serde_json::Value::Array(all));
+                }
+                searches.push(serde_json::Value::Object(merged));
+            }
+        }
+
+        let mut all_items = Vec::new();
+        for (filter_idx, filter) in searches.iter().enumerate() {
+            tracing::debug!(?filter, filter_idx, "Starting Immich search for filter");
+
+            // Build filters body: merge user-provided filters (object) + sync fields + enforced type=IMAGE.
+            // We'll loop over pages and update the page field each iteration.
+            let mut base = serde_json::Map::new();
+            if let Some(obj) = filter.as_object() {
+                for (k, v) in obj.iter() {
+                    base.insert(k.clone(), v.clone());
+                }
+            }
+            for (k, v) in extra {
+                base.insert(k.clone(), v.clone());
+            }
+            if let Some(query) = &self.cfg.smart_query {
+                base.insert(
+                    "query".to_string(),
+                    serde_json::Value::String(query.clone()),
+                );
+            }
+            base.insert(
+                "type".to_string(),
+                serde_json::Value::String("IMAGE".to_string()),
+            );
+
+            // Use a generic page token to support cursor-based pagination (nextPage: String|null).
+            // If the filter specified an explicit page, start from there; otherwise omit 'page' on first call.
+            let mut page_token: Option<serde_json::Value> = base.get("page").cloned();
+            let size: u32 = base.get("size").and_then(|v| v.as_u64()).unwrap_or(1000) as u32;
+            if !base.contains_key("withExif") {
+                base.insert("withExif".to_string(), serde_json::Value::Bool(true));
+            }
+
+            // Smart search ranks the whole library, so it always needs a page limit.
+            let max_pages = self
+                .cfg
+                .max_pages
+                .or(self.cfg.smart_query.as_ref().map(|_| 1))
+                .map(|m| m.max(1));
+            let mut fetched_pages: u32 = 0;
+
+            loop {
+                if max_pages.is_some_and(|max| fetched_pages >= max) {
+                    tracing::trace!(
+                        filter_idx,
+                        fetched_pages,
+                        ?max_pages,
+                        "Reached maximum pages limit for filter"
+                    );
+                    break;
+                }
+                let mut body_map = base.clone();
+                // Only include 'page' when we have a token; otherwise let API start from first page.
+                if let Some(tok) = &page_token {
+                    body_map.insert("page".to_string(), tok.clone());
+                } else {
+                    body_map.remove("page");
+                }
+                body_map.insert("size".to_string(), serde_json::Value::Number(size.into()));
+                let body = serde_json::Value::Object(body_map);
+
+                tracing::trace!(
+                    filter_idx,
+                    page_num = fetched_pages + 1,
+                    ?page_token,
+                    size,
+                    "Fetching Immich assets page"
+                );
+
+                let resp = account
+                    .request(&client, reqwest::Method::POST, endpoint)
+                    .json(&body)
+                    .send()
+                    .await
+                    .context("immich search assets")?;
+
+                if !resp.status().is_success() {
+                    // A partial listing would drop assets or advance the cursor past
+                    // unseen changes, so fail the whole sync instead.
+                    let status = resp.status();
+                    let text = resp.text().await.unwrap_or_default();
+                    bail!("immich search assets failed for filter {filter_idx}: {status} {text}");
+                }
+
+                let items = resp.json::<serde_json::Value>().await.unwrap_or_default();
+                let assets_obj = items.get("assets");
+                let arr = assets_obj
+                    .and_then(|v| v.get("items"))
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_else(|| items.as_array().cloned().unwrap_or_default());
+
+                tracing::trace!(
+                    filter_idx,
+                    page_num = fetched_pages + 1,
+                    assets_in_page = arr.len(),
+                    "Received Immich assets page"
+                );
+
+                if arr.is_empty() {
+                    break; // no more pages
+                }
+                all_items.extend(arr);
+
+                fetched_pages += 1;
+                // Advance using nextPage token when available; stop if null/missing.
+                let next_page_val = assets_obj
+                    .and_then(|v| v.get("nextPage"))
+                    .cloned()
+                    .or_else(|| items.get("nextPage").cloned());
+                match next_page_val {
+                    Some(v) if !v.is_null() => page_token = Some(v),
+                    _ => break,
+                }
+            }
+
+            tracing::trace!(
+                filter_idx,
+                total_pages_fetched = fetched_pages,
+                "Completed Immich search for filter"
+            );
+        }
+        Ok(all_items)
+    }
+
+    /// Resolve `albums` and `people` to search filter fragments: a single fragment with
+    /// all ids for `all`, one fragment per id for `any`. Empty when no names are configured.
+    async fn resolve_name_filters(
+        &self,
+        client: &reqwest::Client,
+        account: &Account,
+    ) -> Result<Vec<Vec<(String, Vec<String>)>>> {
+        if self.cfg.albums.is_empty() && self.cfg.people.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut ids = Vec::new();
+        if !self.cfg.albums.is_empty() {
+            let albums = self.fetch_named(client, account, "albums").await?;
+            for name in &self.cfg.albums {
+                ids.push(("albumIds", resolve_name("album", name, &albums)?));
+            }
+        }
+        if !self.cfg.people.is_empty() {
+            let people = self.fetch_named(client, account, "people").await?;
+            for name in &self.cfg.people {
+                ids.push(("personIds", resolve_name("person", name, &people)?));
+            }
+        }
+        tracing::debug!(?ids, "resolved Immich album and person names");
+        Ok(match self.cfg.name_match.unwrap_or_default() {
+            NameMatch::Any => ids
+                .into_iter()
+                .map(|(key, id)| vec![(key.to_string(), vec![id])])
+                .collect(),
+            NameMatch::All => {
+                let mut albums = Vec::new();
+                let mut people = Vec::new();
+                for (key, id) in ids {
+                    match key {
+                        "albumIds" => albums.push(id),
+                        _ => people.push(id),
+                    }
+                }
+                let mut fragment = Vec::new();
+                if !albums.is_empty() {
+                    fragment.push(("albumIds".to_string(), albums));
+                }
+                if !people.is_empty() {
+                    fragment.push(("personIds".to_string(), people));
+                }
+                vec![fragment]
+            }
+        })
+    }
+
+    /// Bulk-update endpoint (`PUT /api/assets`) applied to a single asset.
+    async fn update_asset(
+        &self,
+        client: &reqwest::Client,
+        account: &Account,
+        id: &str,
+        changes: serde_json::Value,
+    ) -> Result<()> {
+        let mut body = changes;
+        body["ids"] = serde_json::json!([id]);
+        let resp = account
+            .request(client, reqwest::Method::PUT, "/api/assets")
+            .json(&body)
+            .send()
+            .await
+            .context("immich update asset")?;
+        if !resp.status().is_success() {
+            bail!("immich update asset failed: {}", resp.status());
+        }
+        Ok(())
+    }
+
+    /// List `(id, name)` pairs of all albums (`/api/albums`) or people (`/api/people`,
+    /// which is paginated).
+    async fn fetch_named(
+        &self,
+        client: &reqwest::Client,
+        account: &Account,
+        kind: &str,
+    ) -> Result<Vec<(String, String)>> {
+        let mut named = Vec::new();
+        let mut page = 1;
+        loop {
+            let path = match kind {
+                "people" => format!("/api/people?withHidden=true&size=1000&page={page}"),
+                _ => format!("/api/{kind}"),
+            };
+            let resp = account
+                .get(client, &path)
+                .send()
+                .await
+                .with_context(|| format!("immich list {kind}"))?;
+            if !resp.status().is_success() {
+                bail!("immich list {kind} failed: {}", resp.status());
+            }
+            let body: serde_json::Value = resp.json().await?;
+            let (items, name_key) = match kind {
+                "people" => (body.get("people").cloned().unwrap_or_default(), "name"),
+                _ => (body.clone(), "albumName"),
+            };
+            for item in items.as_array().into_iter().flatten() {
+                if let (Some(id), Some(name)) = (
+                    item.get("id").and_then(|v| v.as_str()),
+                    item.get(name_key).and_then(|v| v.as_str()),
+                ) {
+                    named.push((id.to_string(), name.to_string()));
+                }
+            }
+            let more = body
+                .get("hasNextPage")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            if !more {
+                return Ok(named);
+            }
+            page += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl ImageSource for ImmichImageSource {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        self.list_or_keep_cached().await;
+        let snapshot: Vec<ImmichEntry> = { self.entries.read().clone() };
+        if snapshot.is_empty() {
+            return Ok(None);
+        }
+        let order = self.cfg.order.unwrap_or_default();
+        match order {
+            OrderKind::Random => {
+                for _ in 0..32 {
+                    let idx = {
+                        let mut rng = rng();
+                        rng.random_range(0..snapshot.len())
+                    };
+                    let entry = &snapshot[idx];
+                    if !ctx.accepts(&entry.id, entry.orientation) {
+                        continue;
+                    }
+                    if let Some(meta) = self.fetch_entry(entry, ctx.max_dimension).await? {
+                        return Ok(Some(meta));
+                    }
+                }
+                Ok(None)
+            }
+            OrderKind::Sequential => {
+                let total = snapshot.len();
+                let start = self.cursor.fetch_add(1, AtomicOrdering::Relaxed);
+                for offset in 0..total {
+                    let idx = (start + offset) % total;
+                    let entry = &snapshot[idx];
+                    if !ctx.accepts(&entry.id, entry.orientation) {
+                        continue;
+                    }
+                    if let Some(meta) = self.fetch_entry(entry, ctx.max_dimension).await? {
+                        if offset > 0 {
+                            self.cursor.fetch_add(offset, AtomicOrdering::Relaxed);
+                        }
+                        return Ok(Some(meta));
+                    }
+                }
+                Ok(None)
+            }
+            OrderKind::Shuffle => {
+                let index: std::collections::HashMap<&str, usize> = snapshot
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (e.id.as_str(), i))
+                    .collect();
+                let ids: Vec<&str> = snapshot.iter().map(|e| e.id.as_str()).collect();
+                // Assets that fail to download are counted as drawn; try a bounded number.
+                for _ in 0..std::cmp::min(32, snapshot.len()) {
+                    let Some(asset_id) = self
+                        .bags
+                        .draw(&ctx.frame_id, &ids, |id| {
+                            index
+                                .get(id)
+                                .is_some_and(|&i| ctx.accepts(id, snapshot[i].orientation))
+                        })
+                        .await
+                    else {
+                        return Ok(None);
+                    };
+                    let entry = &snapshot[index[asset_id.as_str()]];
+                    if let Some(meta) = self.fetch_entry(entry, ctx.max_dimension).await? {
+                        return Ok(Some(meta));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        // force next call to list to actually list now
+        self.last_list.store(0, AtomicOrdering::Relaxed);
+        self.list_if_needed().await
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        self.list_or_keep_cached().await;
+        let all: Vec<AssetSummary> = self
+            .entries
+            .read()
+            .iter()
+            .map(|e| AssetSummary {
+                checksum: e
+                    .metadata
+                    .get("checksum")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string),
+                id: e.id.clone(),
+                orientation: e.orientation,
+                date_taken: immich_asset_date(&e.metadata),
+            })
+            .collect();
+        Ok(AssetPage::from_summaries(all, query))
+    }
+
+    async fn get(&self, id: &str, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        self.list_or_keep_cached().await;
+        match self.listed_asset(id) {
+            Some(entry) => self.fetch_entry(&entry, ctx.max_dimension).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn thumbnail(&self, id: &str) -> Result<Option<Thumbnail>> {
+        self.list_or_keep_cached().await;
+        let Some(account) = self.account_for(id) else {
+            return Ok(None);
+        };
+        let resp = account
+            .get(
+                &reqwest::Client::new(),
+                &format!("/api/assets/{id}/thumbnail?size=thumbnail"),
+            )
+            .send()
+            .await
+            .context("immich thumbnail")?;
+        if !resp.status().is_success() {
+            return Ok(None);
+        }
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("image/jpeg")
+            .to_string();
+        let bytes = resp.bytes().await?.to_vec();
+        Ok(Some(Thumbnail {
+            bytes,
+            content_type,
+        }))
+    }
+
+    async fn health(&self) -> SourceHealth {
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let last_refresh = (last > 0)
+            .then(|| chrono::DateTime::from_timestamp(last as i64, 0))
+            .flatten();
+        if self.accounts.is_empty() {
+            return SourceHealth::unhealthy("base_url not configured", last_refresh);
+        }
+        if let Some(error) = self.last_error.lock().clone() {
+            return SourceHealth::unhealthy(format!("listing failed: {error}"), last_refresh);
+        }
+        let client = reqwest::Client::new();
+        for account in &self.accounts {
+            let path = match account.credential {
+                Credential::ApiKey(_) => "/api/users/me",
+                Credential::SharedKey(_) => "/api/shared-links/me",
+            };
+            match account.get(&client, path).send().await {
+                Ok(resp) if resp.status().is_success() => {}
+                Ok(resp) => {
+                    return SourceHealth::unhealthy(
+                        format!("immich {account} responded with status {}", resp.status()),
+                        last_refresh,
+                    );
+                }
+                Err(e) => {
+                    return SourceHealth::unhealthy(
+                        format!("immich {account} unreachable: {e}"),
+                        last_refresh,
+                    );
+                }
+            }
+        }
+        SourceHealth::healthy(last_refresh)
+    }
+
+    async fn apply_action(&self, id: &str, action: &AssetAction) -> Result<bool> {
+        let Some(account) = self.account_for(id) else {
+            return Ok(false);
+        };
+        if account.is_shared_link() {
+            // Shared links are read-only.
+            return Ok(false);
+        }
+        let client = reqwest::Client::new();
+        match action {
+            AssetAction::Favorite => {
+                self.update_asset(
+                    &client,
+                    account,
+                    id,
+                    serde_json::json!({ "isFavorite": true }),
+                )
+                .await?
+            }
+            AssetAction::Archive => {
+                // `isArchived` for older servers, `visibility` for newer ones; each ignores the other.
+                self.update_asset(
+                    &client,
+                    account,
+                    id,
+                    serde_json::json!({ "isArchived": true, "visibility": "archive" }),
+                )
+                .await?;
+                // Archived assets drop out of search results, so stop showing it now.
+                self.remove_asset_from_cache(id)?;
+            }
+            AssetAction::AddToAlbum { album } => {
+                let name = album.as_deref().unwrap_or(DEFAULT_ACTION_ALBUM);
+                let albums = self.fetch_named(&client, account, "albums").await?;
+                let exists = albums
+                    .iter()
+                    .any(|(_, n)| n.trim().eq_ignore_ascii_case(name.trim()));
+                let req = if exists {
+                    let album_id = resolve_name("album", name, &albums)?;
+                    account
+                        .request(
+                            &client,
+                            reqwest::Method::PUT,
+                            &format!("/api/albums/{album_id}/assets"),
+                        )
+                        .json(&serde_json::json!({ "ids": [id] }))
+                } else {
+                    tracing::info!(album = %name, "creating Immich album");
+                    account
+                        .request(&client, reqwest::Method::POST, "/api/albums")
+                        .json(&serde_json::json!({ "albumName": name, "assetIds": [id] }))
+                };
+                let resp = req.send().await.context("immich add to album")?;
+                if !resp.status().is_success() {
+                    bail!("immich add to album failed: {}", resp.status());
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn stats(&self) -> SourceStats {
+        SourceStats::from_orientations(self.entries.read().iter().map(|e| e.orientation))
+    }
+
+    fn remove_asset_from_cache(&self, asset_id_to_remove: &str) -> Result<bool> {
+        for state in self.account_states.lock().iter_mut() {
+            state
+                .assets
+                .retain(|a| asset_id(a) != Some(asset_id_to_remove));
+        }
+        let mut entries = self.entries.write();
+        let before = entries.len();
+        entries.retain(|e| e.id != asset_id_to_remove);
+        let after = entries.len();
+        Ok(before != after)
+    }
+}
+
+impl ImmichImageSource {
+    async fn fetch_entry(
+        &self,
+        entry: &ImmichEntry,
+        max_dimension: Option<u32>,
+    ) -> Result<Option<ImageMeta>> {
+        let account = self
+            .accounts
+            .get(entry.account)
+            .ok_or_else(|| anyhow!("immich account {} not configured", entry.account))?;
+        self.fetch_asset(
+            account,
+            &entry.id,
+            entry.orientation,
+            entry.metadata.clone(),
+            max_dimension,
+        )
+        .await
+    }
+
+    async fn fetch_asset(
+        &self,
+        account: &Account,
+        asset_id: &str,
+        orient: Orientation,
+        asset_metadata: serde_json::Value,
+        max_dimension: Option<u32>,
+    ) -> Result<Option<ImageMeta>> {
+        let client = reqwest::Client::new();
+
+        let original_decodable = asset_metadata
+            .get("originalMimeType")
+            .and_then(|v| v.as_str())
+            .is_some_and(is_decodable_mime);
+        let mut image = None;
+        for rendition in rendition_candidates(max_dimension, original_decodable) {
+            let path = match rendition {
+                Rendition::Original => format!("/api/assets/{asset_id}/original"),
+                _ => format!(
+                    "/api/assets/{asset_id}/thumbnail?size={}",
+                    rendition.as_str()
+                ),
+            };
+            let resp = account.get(&client, &path).send().await?;
+
+            if resp.status() == reqwest::StatusCode::NOT_FOUND && rendition != Rendition::Fullsize {
+                // Deleted since the last sync; drop it until the next listing says otherwise.
+                // (Older servers answer 404 for `fullsize`, so that one just falls through.)
+                tracing::debug!(asset_id = %asset_id, "immich asset gone; removing from cache");
+                self.remove_asset_from_cache(asset_id)?;
+                return Ok(None);
+            }
+            let content_type = resp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("image/jpeg")
+                .to_string();
+            if !resp.status().is_success() || !is_decodable_mime(&content_type) {
+                tracing::debug!(asset_id = %asset_id, rendition = rendition.as_str(), status = %resp.status(), %content_type, "immich rendition unusable; trying next");
+                continue;
+            }
+            tracing::debug!(asset_id = %asset_id, rendition = rendition.as_str(), ?max_dimension, "chose immich rendition");
+            image = Some(resp);
+            break;
+        }
+        let Some(thumb_resp) = image else {
+            return Ok(None);
+        };
+
+        let thumb_bytes = thumb_resp.bytes().await?;
+        let mut bytes_downloaded = thumb_bytes.len() as u64;
+
+        // Prefer the capture time Immich already extracted; only probe the original's
+        // header when the listing has none.
+        let (date_taken, exif_blob, exif_source) = match immich_exif_date_taken(&asset_metadata) {
+            // The date is added to the image's own EXIF when loading.
+            Some(date) => (Some(date), None, "listing"),
+            None => {
+                let (date, blob, fetched) = self
+                    .extract_exif_metadata(account, asset_id)
+                    .await
+                    .unwrap_or((None, None, 0));
+                bytes_downloaded += fetched;
+                (date, blob, "original")
+            }
+        };
+        tracing::info!(
+            asset_id = %asset_id,
+            bytes_downloaded,
+            exif_source,
+            "Fetched Immich asset"
+        );
+
+        Ok(Some(ImageMeta {
+            data: SourceData::Bytes(thumb_bytes.to_vec()),
+            orientation: orient,
+            date_taken,
+            exif_blob,
+            id: Some(asset_id.to_string()),
+            source_id: None, // Will be set when returning from next()
+            asset_metadata: Some(asset_metadata),
+        }))
+    }
+
+    /// Read EXIF from the start of the original asset using HTTP Range requests, widening
+    /// the window once if the header doesn't fit. Returns the date, the raw EXIF blob and
+    /// the number of bytes downloaded. Servers that ignore Range are cut off at the limit.
+    async fn extract_exif_metadata(
+        &self,
+        account: &Account,
+        asset_id: &str,
+    ) -> Result<(Option<chrono::DateTime<chrono::Utc>>, Option<Vec<u8>>, u64)> {
+        let client = reqwest::Client::new();
+        let original_path = format!("/api/assets/{asset_id}/original");
+
+        let mut downloaded = 0u64;
+        for limit in EXIF_PROBE_BYTES {
+            let mut resp = account
+                .get(&client, &original_path)
+                .header(reqwest::header::RANGE, format!("bytes=0-{}", limit - 1))
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                return Ok((None, None, downloaded));
+            }
+
+            let mut bytes = Vec::new();
+            while (bytes.len() as u64) < limit {
+                match resp.chunk().await? {
+                    Some(chunk) => bytes.extend_from_slice(&chunk),
+                    None => break,
+                }
+            }
+            bytes.truncate(limit as usize);
+            downloaded += bytes.len() as u64;
+
+            let mut cursor = std::io::Cursor::new(&bytes[..]);
+            if let Ok(exif) = exif::Reader::new().read_from_container(&mut cursor) {
+                let date_taken = crate::frame::exif_date_taken(&exif);
+                return Ok((date_taken, Some(exif.buf().to_vec()), downloaded));
+            }
+            if (bytes.len() as u64) < limit {
+                break; // whole file read; there is no EXIF to find
+            }
+        }
+        Ok((None, None, downloaded))
+    }
+}
+
+/// Skip listed items that aren't displayable images (videos, trashed assets).
+fn is_listable_image(item: &serde_json::Value) -> bool {
+    item.get("type")
+        .and_then(|t| t.as_str())
+        .is_none_or(|t| t == "IMAGE")
+        && item.get("isTrashed").and_then(|t| t.as_bool()) != Some(true)
+}
+
+/// The instant `day` starts in `tz` (or the first instant after midnight if a DST jump
+/// skips it).
+fn local_midnight(day: NaiveDate, tz: chrono_tz::Tz) -> DateTime<Utc> {
+    let midnight = day.and_time(chrono::NaiveTime::MIN);
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(midnight + chrono::Duration::hours(1)))
+                .earliest()
+        })
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+/// Whether an asset was taken on `today`'s month and day in an earlier year, the way
+/// Immich builds its "on this day" memories. Used for shared links, which have none.
+fn is_on_this_day(item: &serde_json::Value, today: NaiveDate) -> bool {
+    immich_asset_date(item)
+        .map(|d| {
+            d.with_timezone(&crate::scheduler::scheduler_timezone())
+                .date_naive()
+        })
+        .is_some_and(|d| d.month() == today.month() && d.day() == today.day() && d < today)
+}
+
+/// Drop items without an id or with an id already listed, and find the newest
+/// `updatedAt` among them.
+fn dedupe_listing(
+    items: Vec<serde_json::Value>,
+) -> (Vec<serde_json::Value>, Option<DateTime<Utc>>) {
+    let mut seen_ids = HashSet::new();
+    let mut cursor: Option<DateTime<Utc>> = None;
+    let mut assets = Vec::new();
+    for item in items {
+        cursor = cursor.max(asset_updated_at(&item));
+        let Some(id) = asset_id(&item) else {
+            continue;
+        };
+        if !seen_ids.insert(id.to_string()) {
+            continue; // Skip duplicates
+        }
+        assets.push(item);
+    }
+    (assets, cursor)
+}
+
+/// Find the single id whose name matches `name` case-insensitively.
+fn resolve_name(kind: &str, name: &str, named: &[(String, String)]) -> Result<String> {
+    let wanted = name.trim().to_lowercase();
+    let matches: Vec<&(String, String)> = named
+        .iter()
+        .filter(|(_, n)| n.trim().to_lowercase() == wanted)
+        .collect();
+    match matches.as_slice() {
+        [(id, _)] => Ok(id.clone()),
+        [] => bail!("unknown Immich {kind} \"{name}\""),
+        _ => bail!(
+            "ambiguous Immich {kind} \"{name}\": {} {kind}s share that name ({}); use ids in `filters` instead",
+            matches.len(),
+            matches
+                .iter()
+                .map(|(id, _)| id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Sizes Immich can serve an asset in. `thumbnail` and `preview` are bounded by the
+/// server's thumbnail settings (250 and 1440 px on the long edge by default).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rendition {
+    Thumbnail,
+    Preview,
+    Fullsize,
+    Original,
+}
+
+impl Rendition {
+    fn as_str(self) -> &'static str {
+        match self {
+            Rendition::Thumbnail => "thumbnail",
+            Rendition::Preview => "preview",
+            Rendition::Fullsize => "fullsize",
+            Rendition::Original => "original",
+        }
+    }
+}
+
+const THUMBNAIL_EDGE: u32 = 250;
+const PREVIEW_EDGE: u32 = 1440;
+
+/// Renditions to try, best fit first. Larger ones follow as fallbacks (e.g. when the
+/// thumbnail is WebP, which we can't decode); the original is only used when it is a
+/// format we can decode. Without a known size the preview is preferred, as before.
+fn rendition_candidates(max_dimension: Option<u32>, original_decodable: bool) -> Vec<Rendition> {
+    let mut candidates = match max_dimension {
+        Some(edge) if edge <= THUMBNAIL_EDGE => vec![
+            Rendition::Thumbnail,
+            Rendition::Preview,
+            Rendition::Fullsize,
+            Rendition::Original,
+        ],
+        Some(edge) if edge > PREVIEW_EDGE => {
+            vec![Rendition::Original, Rendition::Fullsize, Rendition::Preview]
+        }
+        _ => vec![Rendition::Preview, Rendition::Fullsize, Rendition::Original],
+    };
+    if !original_decodable {
+        candidates.retain(|r| *r != Rendition::Original);
+    }
+    candidates
+}
+
+/// Whether `load_and_store_base` can decode images of this MIME type.
+fn is_decodable_mime(mime: &str) -> bool {
+    let essence = mime.split(';').next().unwrap_or("").trim();
+    matches!(essence, "image/jpeg" | "image/jpg" | "image/png")
+}
+
+/// Capture time from the listing's `exifInfo`. Immich stores `dateTimeOriginal` as an
+/// instant; when `timeZone` is unknown the wall-clock `localDateTime` is interpreted in
+/// local time instead, matching how offset-less EXIF dates are read from files.
+pub fn immich_exif_date_taken(item: &serde_json::Value) -> Option<DateTime<Utc>> {
+    let exif = item.get("exifInfo")?;
+    let parse = |v: Option<&serde_json::Value>| {
+        v.and_then(|v| v.as_str())
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+    };
+    let original = parse(exif.get("dateTimeOriginal"))?;
+    let has_zone = exif
+        .get("timeZone")
+        .and_then(|v| v.as_str())
+        .is_some_and(|z| !z.is_empty());
+    if !has_zone
+        && let Some(local) = parse(item.get("localDateTime"))
+        && let Some(dt) = Local.from_local_datetime(&local.naive_utc()).earliest()
+    {
+        return Some(dt.with_timezone(&Utc));
+    }
+    Some(original.with_timezone(&Utc))
+}
+
+/// Derive display orientation from an Immich asset's EXIF dimensions, accounting for
+/// rotated EXIF orientations. Assets without dimensions are treated as landscape.
+pub fn immich_asset_orientation(item: &serde_json::Value) -> Orientation {
+    let exif = item.get("exifInfo");
+    let raw_w = exif
+        .and_then(|m| m.get("exifImageWidth"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as u32;
+    let raw_h = exif
+        .and_then(|m| m.get("exifImageHeight"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as u32;
+    let exif_orientation = exif
+        .and_then(|m| m.get("orientation"))
+        .and_then(|v| {
+            if let Some(n) = v.as_u64() {
+                Some(n)
+            } else if let Some(s) = v.as_str() {
+                s.trim().parse::<u64>().ok()
+            } else {
+                None
+            }
+        })
+        .unwrap_or(1);
+    let (w, h) = match exif_orientation {
+        6 | 8 => (raw_h, raw_w),
+        _ => (raw_w, raw_h),
+    };
+
+    if w > 0 && h > 0 {
+        Orientation::from_dims(w, h)
+    } else {
+        Orientation::Landscape
+    }
+}
+
+/// Best-effort capture date of an Immich asset from its listing metadata.
+pub fn immich_asset_date(item: &serde_json::Value) -> Option<chrono::DateTime<chrono::Utc>> {
+    item.get("exifInfo")
+        .and_then(|e| e.get("dateTimeOriginal"))
+        .or_else(|| item.get("localDateTime"))
+        .or_else(|| item.get("fileCreatedAt"))
+        .and_then(|v| v.as_str())
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+    use axum::http::{HeaderMap, StatusCode, header};
+    use axum::response::{IntoResponse, Response};
+    use axum::{
+        Json, Router,
+        extract::State,
+        routing::{get, post},
+    };
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct MockImmich {
+        assets: Vec<Value>,
+        requests: Vec<Value>,
+        /// Answer searches with 503, as during an Immich restart.
+        unavailable: bool,
+    }
+
+    type Shared = Arc<parking_lot::Mutex<MockImmich>>;
+
+    /// Minimal `/api/search/metadata`: honours `updatedAfter`, `withDeleted`, `size` and
+    /// numeric `page` tokens.
+    async fn search_metadata(State(state): State<Shared>, Json(body): Json<Value>) -> Response {
+        let mut state = state.lock();
+        if state.unavailable {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+        state.requests.push(body.clone());
+        let after = body
+            .get("updatedAfter")
+            .and_then(|v| v.as_str())
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok());
+        let with_deleted = body.get("withDeleted") == Some(&json!(true));
+        let matching: Vec<Value> = state
+            .assets
+            .iter()
+            .filter(|a| with_deleted || a["isTrashed"] != json!(true))
+            .filter(|a| {
+                after.is_none_or(|after| {
+                    DateTime::parse_from_rfc3339(a["updatedAt"].as_str().unwrap()).unwrap() > after
+                })
+            })
+            .cloned()
+            .collect();
+        let size = body["size"].as_u64().unwrap() as usize;
+        let page: usize = body
+            .get("page")
+            .and_then(|v| v.as_str())
+            .map_or(1, |p| p.parse().unwrap());
+        let items: Vec<Value> = matching
+            .iter()
+            .skip((page - 1) * size)
+            .take(size)
+            .cloned()
+            .collect();
+        let next = (page * size < matching.len()).then(|| (page + 1).to_string());
+        Json(json!({ "assets": { "items": items, "nextPage": next } })).into_response()
+    }
+
+    fn asset(id: &str, updated_at: &str) -> Value {
+        json!({
+            "id": id,
+            "updatedAt": updated_at,
+            "isTrashed": false,
+            "exifInfo": { "exifImageWidth": 400, "exifImageHeight": 300 },
+        })
+    }
+
+    fn ids(source: &ImmichImageSource) -> Vec<String> {
+        let mut ids: Vec<String> = source.entries.read().iter().map(|e| e.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn incremental_sync_against_mock_server() {
+        let state: Shared = Arc::default();
+        state.lock().assets = vec![
+            asset("a", "2024-01-01T00:00:00.000Z"),
+            asset("b", "2024-01-01T00:00:00.000Z"),
+            asset("c", "2024-01-02T00:00:00.000Z"),
+        ];
+        let app = Router::new()
+            .route("/api/search/metadata", post(search_metadata))
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+
+        let source_id = format!("test_immich_sync_{}", std::process::id());
+        let cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            filters: Some(vec![json!({ "size": 2 })]),
+            ..Default::default()
+        };
+
+        // Initial full listing follows pagination.
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        source.refresh().await.unwrap();
+        assert_eq!(ids(&source), ["a", "b", "c"]);
+        assert_eq!(state.lock().requests.len(), 2);
+        assert!(state.lock().requests[0].get("updatedAfter").is_none());
+
+        // Trash one asset, add another and refresh: only changes are fetched.
+        {
+            let mut s = state.lock();
+            s.assets[1]["isTrashed"] = json!(true);
+            s.assets[1]["updatedAt"] = json!("2024-02-01T00:00:00.000Z");
+            s.assets.push(asset("d", "2024-02-02T00:00:00.000Z"));
+        }
+        source.refresh().await.unwrap();
+        assert_eq!(ids(&source), ["a", "c", "d"]);
+        {
+            let s = state.lock();
+            let last = s.requests.last().unwrap();
+            assert_eq!(last["updatedAfter"], json!("2024-01-02T00:00:00.000Z"));
+            assert_eq!(last["withDeleted"], json!(true));
+        }
+
+        // A restarted source resumes from the persisted listing without hitting the server.
+        let requests_before = state.lock().requests.len();
+        let restarted = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        let page = restarted.list(&ListQuery::default()).await.unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(state.lock().requests.len(), requests_before);
+        assert_eq!(
+            restarted.account_states.lock()[0].cursor,
+            asset_updated_at(&json!({ "updatedAt": "2024-02-02T00:00:00.000Z" }))
+        );
+
+        std::fs::remove_file(&restarted.state_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sync_state_tracks_credential_and_skips_unchanged_writes() {
+        let state: Shared = Arc::default();
+        state.lock().assets = vec![asset("a", "2024-01-01T00:00:00.000Z")];
+        let app = Router::new()
+            .route("/api/search/metadata", post(search_metadata))
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+
+        let source_id = format!("test_immich_cred_{}", std::process::id());
+        let cfg = |key: &str| ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            api_key: Some(key.to_string()),
+            ..Default::default()
+        };
+        let source = ImmichImageSource::new(&source_id, &cfg("one")).unwrap();
+        source.refresh().await.unwrap();
+        assert!(source.state_path.exists());
+
+        // The same key restores the listing; another key starts over.
+        assert_eq!(
+            ids(&ImmichImageSource::new(&source_id, &cfg("one")).unwrap()),
+            ["a"]
+        );
+        assert!(ids(&ImmichImageSource::new(&source_id, &cfg("two")).unwrap()).is_empty());
+
+        // A sync that finds nothing new leaves the file alone.
+        std::fs::remove_file(&source.state_path).unwrap();
+        source.refresh().await.unwrap();
+        assert!(!source.state_path.exists());
+        state
+            .lock()
+            .assets
+            .push(asset("b", "2024-02-01T00:00:00.000Z"));
+        source.refresh().await.unwrap();
+        assert!(source.state_path.exists());
+
+        std::fs::remove_file(&source.state_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn serves_only_listed_assets_by_id() {
+        let state: Shared = Arc::default();
+        state.lock().assets = vec![asset("a", "2024-01-01T00:00:00.000Z")];
+        let app = Router::new()
+            .route("/api/search/metadata", post(search_metadata))
+            .route(
+                "/api/assets/{id}/thumbnail",
+                get(|| async { ([(header::CONTENT_TYPE, "image/jpeg")], vec![1u8, 2, 3]) }),
+            )
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+
+        let source_id = format!("test_immich_listed_{}", std::process::id());
+        let cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            ..Default::default()
+        };
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        let thumb = source.thumbnail("a").await.unwrap().unwrap();
+        assert_eq!(thumb.bytes, [1, 2, 3]);
+        // The server knows any id, but ids outside the filtered listing are refused.
+        assert!(source.thumbnail("other").await.unwrap().is_none());
+        let ctx = SelectionContext::default();
+        assert!(source.get("other", &ctx).await.unwrap().is_none());
+
+        let _ = std::fs::remove_file(&source.state_path);
+    }
+
+    #[tokio::test]
+    async fn failed_sync_serves_cached_listing() {
+        let state: Shared = Arc::default();
+        state.lock().assets = vec![asset("a", "2024-01-01T00:00:00.000Z")];
+        let app = Router::new()
+            .route("/api/search/metadata", post(search_metadata))
+            .route(
+                "/api/assets/{id}/thumbnail",
+                get(|| async { ([(header::CONTENT_TYPE, "image/jpeg")], vec![1u8, 2, 3]) }),
+            )
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+
+        let source_id = format!("test_immich_cached_{}", std::process::id());
+        let cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            ..Default::default()
+        };
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        source.refresh().await.unwrap();
+
+        state.lock().unavailable = true;
+        assert!(source.refresh().await.is_err());
+        let page = source.list(&ListQuery::default()).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert!(source.thumbnail("a").await.unwrap().is_some());
+        assert!(!source.health().await.healthy);
+
+        let _ = std::fs::remove_file(&source.state_path);
+    }
+
+    #[test]
+    fn listing_date_round_trips_through_exif_blob() {
+        let item = json!({
+            "localDateTime": "2023-06-10T16:22:00.000Z",
+            "exifInfo": { "dateTimeOriginal": "2023-06-10T14:22:00.000Z", "timeZone": "Europe/Berlin" },
+        });
+        let date = immich_exif_date_taken(&item).unwrap();
+        assert_eq!(date.to_rfc3339(), "2023-06-10T14:22:00+00:00");
+        let blob = crate::frame::exif_blob_for_date(date).unwrap();
+        assert_eq!(
+            crate::frame::extract_exif_date_taken_from_blob(&blob).unwrap(),
+            Some(date)
+        );
+        assert_eq!(immich_exif_date_taken(&json!({ "exifInfo": {} })), None);
+    }
+
+    /// Serves a fixed body, honouring `Range: bytes=0-N` with 206 responses.
+    async fn original(
+        State(body): State<Arc<Vec<u8>>>,
+        headers: HeaderMap,
+    ) -> (StatusCode, Vec<u8>) {
+        let end = headers
+            .get(header::RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|r| r.strip_prefix("bytes=0-"))
+            .and_then(|n| n.parse::<usize>().ok());
+        match end {
+            Some(end) => (
+                StatusCode::PARTIAL_CONTENT,
+                body[..=end.min(body.len() - 1)].to_vec(),
+            ),
+            None => (StatusCode::OK, body.to_vec()),
+        }
+    }
+
+    #[tokio::test]
+    async fn exif_read_from_original_header_only() {
+        let date = DateTime::parse_from_rfc3339("2021-03-04T05:06:07Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let tiff = crate::frame::exif_blob_for_date(date).unwrap();
+        // Pad the file well past the probe size.
+        let mut file = test_support::jpeg_with_exif(8, 8, &tiff);
+        file.resize(4 * 1024 * 1024, 0);
+        let total = file.len() as u64;
+
+        let app = Router::new()
+            .route("/api/assets/{id}/original", get(original))
+            .with_state(Arc::new(file));
+        let addr = test_support::serve(app).await;
+
+        let cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            ..Default::default()
+        };
+        let source = ImmichImageSource::new("test_immich_exif_range", &cfg).unwrap();
+        let (taken, blob, downloaded) = source
+            .extract_exif_metadata(&source.accounts[0], "x")
+            .await
+            .unwrap();
+        assert_eq!(taken, Some(date));
+        assert!(blob.is_some());
+        assert_eq!(downloaded, EXIF_PROBE_BYTES[0]);
+        assert!(downloaded < total);
+    }
+
+    #[test]
+    fn rendition_follows_panel_size() {
+        use Rendition::*;
+        assert_eq!(rendition_candidates(Some(250), true)[0], Thumbnail);
+        assert_eq!(rendition_candidates(Some(800), true)[0], Preview);
+        assert_eq!(rendition_candidates(None, true)[0], Preview);
+        assert_eq!(
+            rendition_candidates(Some(1448), true),
+            [Original, Fullsize, Preview]
+        );
+        // HEIC/RAW originals are never fetched for display.
+        assert_eq!(rendition_candidates(Some(1448), false), [Fullsize, Preview]);
+        assert!(is_decodable_mime("image/jpeg; charset=binary"));
+        assert!(!is_decodable_mime("image/webp"));
+    }
+
+    /// `/api/search/smart`: always claims there is another page, like a relevance ranking
+    /// over the whole library would.
+    async fn search_smart(State(state): State<Shared>, Json(body): Json<Value>) -> Json<Value> {
+        let mut state = state.lock();
+        state.requests.push(body);
+        Json(json!({ "assets": { "items": state.assets, "nextPage": "2" } }))
+    }
+
+    #[tokio::test]
+    async fn smart_query_lists_first_page_and_honours_blacklist() {
+        let state: Shared = Arc::default();
+        state.lock().assets = vec![
+            asset("a", "2024-01-01T00:00:00.000Z"),
+            asset("b", "2024-01-01T00:00:00.000Z"),
+            asset("c", "2024-01-01T00:00:00.000Z"),
+        ];
+        let app = Router::new()
+            .route("/api/search/smart", post(search_smart))
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+
+        let cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            smart_query: Some("beach sunset".to_string()),
+            blacklist: vec!["b".to_string()],
+            ..Default::default()
+        };
+        let source_id = format!("test_immich_smart_{}", std::process::id());
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        source.refresh().await.unwrap();
+        source.refresh().await.unwrap();
+        assert_eq!(ids(&source), ["a", "c"]);
+        let s = state.lock();
+        assert_eq!(s.requests.len(), 2);
+        for body in &s.requests {
+            assert_eq!(body["query"], json!("beach sunset"));
+            assert_eq!(body["type"], json!("IMAGE"));
+            assert!(body.get("updatedAfter").is_none());
+        }
+        std::fs::remove_file(&source.state_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn album_and_person_names_resolve_to_ids() {
+        let state: Shared = Arc::default();
+        state.lock().assets = vec![asset("a", "2024-01-01T00:00:00.000Z")];
+        let app = Router::new()
+            .route("/api/search/metadata", post(search_metadata))
+            .route(
+                "/api/albums",
+                get(|| async {
+                    Json(json!([
+                        { "id": "al1", "albumName": "Summer 2024" },
+                        { "id": "al2", "albumName": "Trip" },
+                        { "id": "al3", "albumName": "trip" },
+                    ]))
+                }),
+            )
+            .route(
+                "/api/people",
+                get(|| async {
+                    Json(json!({
+                        "people": [{ "id": "p1", "name": "Grandma" }, { "id": "p2", "name": "Max" }],
+                        "hasNextPage": false,
+                    }))
+                }),
+            )
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+
+        let source_id = format!("test_immich_names_{}", std::process::id());
+        let mut cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            albums: vec!["summer 2024".to_string()],
+            people: vec!["Grandma".to_string(), "Max".to_string()],
+            ..Default::default()
+        };
+
+        // `any`: one search per album/person, unioned.
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        source.refresh().await.unwrap();
+        let bodies: Vec<Value> = state.lock().requests.drain(..).collect();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0]["albumIds"], json!(["al1"]));
+        assert_eq!(bodies[1]["personIds"], json!(["p1"]));
+        assert_eq!(bodies[2]["personIds"], json!(["p2"]));
+        std::fs::remove_file(&source.state_path).unwrap();
+
+        // `all`: a single search carrying every id.
+        cfg.name_match = Some(NameMatch::All);
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        source.refresh().await.unwrap();
+        let bodies: Vec<Value> = state.lock().requests.drain(..).collect();
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0]["albumIds"], json!(["al1"]));
+        assert_eq!(bodies[0]["personIds"], json!(["p1", "p2"]));
+        std::fs::remove_file(&source.state_path).unwrap();
+
+        cfg.albums = vec!["Trip".to_string()];
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        let err = source.refresh().await.unwrap_err().to_string();
+        assert!(err.contains("ambiguous Immich album \"Trip\""), "{err}");
+        assert!(!source.health().await.healthy);
+
+        cfg.albums = vec!["Winter".to_string()];
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        let err = source.refresh().await.unwrap_err().to_string();
+        assert!(err.contains("unknown Immich album \"Winter\""), "{err}");
+    }
+
+    #[test]
+    fn memories_requested_for_local_midnight() {
+        let day = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+        let helsinki: chrono_tz::Tz = "Europe/Helsinki".parse().unwrap();
+        assert_eq!(
+            local_midnight(day, helsinki).to_rfc3339(),
+            "2024-06-09T21:00:00+00:00"
+        );
+        assert_eq!(
+            local_midnight(day, chrono_tz::UTC).to_rfc3339(),
+            "2024-06-10T00:00:00+00:00"
+        );
+    }
+
+    #[tokio::test]
+    async fn memories_listed_once_per_day_with_filter_fallback() {
+        let state: Shared = Arc::default();
+        state.lock().assets = vec![asset("fallback", "2024-01-01T00:00:00.000Z")];
+        let memories: Arc<parking_lot::Mutex<Vec<Value>>> = Arc::default();
+        let memory_calls = Arc::new(AtomicUsize::new(0));
+        let (m, calls) = (memories.clone(), memory_calls.clone());
+        let app = Router::new()
+            .route("/api/search/metadata", post(search_metadata))
+            .route(
+                "/api/memories",
+                get(
+                    move |query: axum::extract::Query<HashMap<String, String>>| async move {
+                        assert!(query.contains_key("for"));
+                        calls.fetch_add(1, AtomicOrdering::Relaxed);
+                        let assets = m.lock().clone();
+                        Json(if assets.is_empty() {
+                            json!([])
+                        } else {
+                            json!([{ "assets": assets }])
+                        })
+                    },
+                ),
+            )
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+
+        let mut video = asset("video", "2024-01-01T00:00:00.000Z");
+        video["type"] = json!("VIDEO");
+        *memories.lock() = vec![asset("m1", "2024-01-01T00:00:00.000Z"), video];
+        let cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            memories: true,
+            ..Default::default()
+        };
+        let source_id = format!("test_immich_memories_{}", std::process::id());
+        let source = ImmichImageSource::new(&source_id, &cfg).unwrap();
+        source.list(&ListQuery::default()).await.unwrap();
+        assert_eq!(ids(&source), ["m1"]);
+        // Same local day: no new request.
+        source.list(&ListQuery::default()).await.unwrap();
+        assert_eq!(memory_calls.load(AtomicOrdering::Relaxed), 1);
+        assert!(state.lock().requests.is_empty());
+
+        // No memories: the filtered search is used instead.
+        memories.lock().clear();
+        source.refresh().await.unwrap();
+        assert_eq!(ids(&source), ["fallback"]);
+        assert_eq!(state.lock().requests.len(), 1);
+        std::fs::remove_file(&source.state_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn asset_actions_call_asset_and_album_apis() {
+        let calls: Arc<parking_lot::Mutex<Vec<(String, Value)>>> = Arc::default();
+        let record = |path: &'static str, calls: Arc<parking_lot::Mutex<Vec<(String, Value)>>>| {
+            move |Json(body): Json<Value>| async move {
+                calls.lock().push((path.to_string(), body));
+                Json(json!([]))
+            }
+        };
+        let app = Router::new()
+            .route(
+                "/api/assets",
+                axum::routing::put(record("PUT assets", calls.clone())),
+            )
+            .route(
+                "/api/albums",
+                get(|| async { Json(json!([{ "id": "al1", "albumName": "Frame favorites" }])) })
+                    .post(record("POST albums", calls.clone())),
+            )
+            .route(
+                "/api/albums/{id}/assets",
+                axum::routing::put(record("PUT albums/al1/assets", calls.clone())),
+            );
+        let addr = test_support::serve(app).await;
+
+        let cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            ..Default::default()
+        };
+        let source = ImmichImageSource::new("test_immich_actions", &cfg).unwrap();
+        source.entries.write().push(ImmichEntry {
+            id: "x".to_string(),
+            orientation: Orientation::Landscape,
+            metadata: asset("x", "2024-01-01T00:00:00Z"),
+            account: 0,
+        });
+
+        assert!(
+            source
+                .apply_action("x", &AssetAction::Favorite)
+                .await
+                .unwrap()
+        );
+        let add = AssetAction::AddToAlbum { album: None };
+        assert!(source.apply_action("x", &add).await.unwrap());
+        let new_album = AssetAction::AddToAlbum {
+            album: Some("Kitchen".to_string()),
+        };
+        assert!(source.apply_action("x", &new_album).await.unwrap());
+        assert!(
+            source
+                .apply_action("x", &AssetAction::Archive)
+                .await
+                .unwrap()
+        );
+
+        let calls = calls.lock();
+        assert_eq!(
+            calls[0],
+            (
+                "PUT assets".to_string(),
+                json!({ "ids": ["x"], "isFavorite": true })
+            )
+        );
+        assert_eq!(
+            calls[1],
+            ("PUT albums/al1/assets".to_string(), json!({ "ids": ["x"] }))
+        );
+        assert_eq!(
+            calls[2],
+            (
+                "POST albums".to_string(),
+                json!({ "albumName": "Kitchen", "assetIds": ["x"] })
+            )
+        );
+        assert_eq!(calls[3].1["isArchived"], json!(true));
+        assert!(source.entries.read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn accounts_and_shared_links_merge_by_checksum() {
+        let with_checksum = |id: &str, checksum: &str| {
+            let mut a = asset(id, "2024-01-01T00:00:00.000Z");
+            a["checksum"] = json!(checksum);
+            a
+        };
+        let libraries: Arc<HashMap<&str, Vec<Value>>> = Arc::new(HashMap::from([
+            (
+                "k1",
+                vec![with_checksum("a", "c1"), with_checksum("b", "c2")],
+            ),
+            // `c` is the same photo as `a`, uploaded to the second account.
+            (
+                "k2",
+                vec![with_checksum("c", "c1"), with_checksum("d", "c3")],
+            ),
+        ]));
+        let shared_key =
+            |query: &HashMap<String, String>| query.get("key").map(String::as_str) == Some("s1");
+        let app = Router::new()
+            .route(
+                "/api/search/metadata",
+                post(move |headers: HeaderMap| async move {
+                    let key = headers["x-api-key"].to_str().unwrap();
+                    Json(json!({ "assets": { "items": libraries[key], "nextPage": null } }))
+                }),
+            )
+            .route(
+                "/api/shared-links/me",
+                get(
+                    move |axum::extract::Query(q): axum::extract::Query<
+                        HashMap<String, String>,
+                    >| async move {
+                        if !shared_key(&q) {
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+                        Ok(Json(json!({
+                            "assets": [with_checksum("e", "c2")],
+                            "album": { "id": "al" },
+                        })))
+                    },
+                ),
+            )
+            .route(
+                "/api/albums/al",
+                get(
+                    move |axum::extract::Query(q): axum::extract::Query<
+                        HashMap<String, String>,
+                    >| async move {
+                        if !shared_key(&q) {
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+                        let mut video = with_checksum("g", "c5");
+                        video["type"] = json!("VIDEO");
+                        Ok(Json(json!({ "assets": [with_checksum("f", "c4"), video] })))
+                    },
+                ),
+            )
+            .route(
+                "/api/assets/{id}/thumbnail",
+                get(
+                    move |axum::extract::Query(q): axum::extract::Query<
+                        HashMap<String, String>,
+                    >| async move {
+                        if !shared_key(&q) {
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+                        Ok(([(header::CONTENT_TYPE, "image/jpeg")], vec![0xff, 0xd8]))
+                    },
+                ),
+            );
+        let addr = test_support::serve(app).await;
+
+        let cfg = ImmichSource {
+            base_url: Some(format!("http://{addr}")),
+            api_key: Some("k1".to_string()),
+            accounts: vec![
+                crate::config::ImmichAccount {
+                    api_key: Some("k2".to_string()),
+                    ..Default::default()
+                },
+                crate::config::ImmichAccount {
+                    shared_key: Some("s1".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let source = ImmichImageSource::new("test_immich_accounts", &cfg).unwrap();
+        source.refresh().await.unwrap();
+
+        // Duplicates by checksum (`c`, `e`) and the shared video are dropped.
+        let listed: Vec<(String, usize)> = source
+            .entries
+            .read()
+            .iter()
+            .map(|e| (e.id.clone(), e.account))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("a".to_string(), 0),
+                ("b".to_string(), 0),
+                ("d".to_string(), 1),
+                ("f".to_string(), 2),
+            ]
+        );
+        // Shared-link assets are fetched with `key=` and are read-only.
+        assert!(source.thumbnail("f").await.unwrap().is_some());
+        assert!(
+            !source
+                .apply_action("f", &AssetAction::Favorite)
+                .await
+                .unwrap()
+        );
+        std::fs::remove_file(&source.state_path).unwrap();
+    }
+}
@@ -0,0 +1,302 @@
+//! Upload inbox: images uploaded to a frame can also be kept in a managed directory and
+//! shown again like any other source. Each upload is stored as `<item_id>.<ext>` next
+//! to `<item_id>.json` recording who uploaded it and when.
+
+use super::{
+    AssetPage, AssetSummary, ImageMeta, ImageSource, ListQuery, SelectionContext, ShuffleBagStore,
+    SourceData, SourceHealth, SourceStats,
+};
+use crate::config::{InboxSource, OrderKind, Orientation};
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::prelude::*;
+use rand::rng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+/// Longest uploader name kept.
+const MAX_UPLOADER_LEN: usize = 64;
+
+/// One stored upload, as saved in its `.json` sidecar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboxItem {
+    pub id: String,
+    /// Image file name inside the inbox directory.
+    pub file: String,
+    pub uploader: Option<String>,
+    pub uploaded_at: DateTime<Utc>,
+    pub orientation: Orientation,
+    pub date_taken: Option<DateTime<Utc>>,
+}
+
+/// Directory of the inbox source `source_id`.
+pub fn inbox_dir(source_id: &str, cfg: &InboxSource) -> PathBuf {
+    cfg.dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("{source_id}_inbox")))
+}
+
+/// Store an uploaded image in the inbox at `dir`.
+pub fn save_inbox_upload(dir: &Path, bytes: &[u8], uploader: Option<&str>) -> Result<InboxItem> {
+    let format = image::guess_format(bytes).context("upload is not a supported image")?;
+    let ext = format.extensions_str().first().copied().unwrap_or("img");
+    let (w, h) = image::ImageReader::with_format(std::io::Cursor::new(bytes), format)
+        .into_dimensions()
+        .context("reading upload dimensions")?;
+    let uploaded_at = Utc::now();
+    let id = format!(
+        "{}-{:06x}",
+        uploaded_at.format("%Y%m%dT%H%M%S"),
+        rng().random::<u32>() & 0xff_ffff
+    );
+    let uploader = uploader
+        .map(|u| u.trim().chars().take(MAX_UPLOADER_LEN).collect::<String>())
+        .filter(|u| !u.is_empty());
+    let item = InboxItem {
+        file: format!("{id}.{ext}"),
+        id,
+        uploader,
+        uploaded_at,
+        orientation: Orientation::from_dims(w, h),
+        date_taken: crate::frame::extract_exif_date_taken(bytes).ok().flatten(),
+    };
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    std::fs::write(dir.join(&item.file), bytes)?;
+    // The sidecar goes last, so a crash never leaves an item without its image.
+    std::fs::write(
+        dir.join(format!("{}.json", item.id)),
+        serde_json::to_vec_pretty(&item)?,
+    )?;
+    tracing::info!(dir = %dir.display(), item = %item.id, uploader = ?item.uploader, "Saved upload to inbox");
+    Ok(item)
+}
+
+/// All items in the inbox at `dir`, oldest first. A missing directory is an empty inbox.
+pub fn list_inbox_items(dir: &Path) -> Result<Vec<InboxItem>> {
+    let read = match std::fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", dir.display())),
+    };
+    let mut items = Vec::new();
+    for entry in read.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        let item = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|b| Ok(serde_json::from_slice::<InboxItem>(&b)?));
+        match item {
+            Ok(item) if dir.join(&item.file).is_file() => items.push(item),
+            Ok(item) => tracing::warn!(item = %item.id, "inbox item without image; skipping"),
+            Err(e) => tracing::warn!(path = %path.display(), error = %e, "unreadable inbox item"),
+        }
+    }
+    items.sort_by(|a, b| (a.uploaded_at, &a.id).cmp(&(b.uploaded_at, &b.id)));
+    Ok(items)
+}
+
+/// Delete an item and its image. Returns whether it existed.
+pub fn delete_inbox_item(dir: &Path, id: &str) -> Result<bool> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        bail!("invalid inbox item id {id:?}");
+    }
+    let sidecar = dir.join(format!("{id}.json"));
+    let item: InboxItem = match std::fs::read(&sidecar) {
+        Ok(bytes) => serde_json::from_slice(&bytes)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    match std::fs::remove_file(dir.join(&item.file)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    std::fs::remove_file(&sidecar)?;
+    tracing::info!(dir = %dir.display(), item = %id, "Deleted inbox item");
+    Ok(true)
+}
+
+pub struct InboxImageSource {
+    dir: PathBuf,
+    order: OrderKind,
+    entries: parking_lot::RwLock<Vec<ImageMeta>>,
+    cursor: AtomicUsize,   // for sequential order
+    bags: ShuffleBagStore, // for shuffle order
+    last_scan: parking_lot::RwLock<Option<DateTime<Utc>>>,
+}
+
+impl InboxImageSource {
+    pub fn new(id: &str, cfg: &InboxSource) -> Result<Self> {
+        let source = Self {
+            dir: inbox_dir(id, cfg),
+            order: cfg.order.unwrap_or_default(),
+            entries: parking_lot::RwLock::new(Vec::new()),
+            cursor: AtomicUsize::new(0),
+            bags: ShuffleBagStore::new(id),
+            last_scan: parking_lot::RwLock::new(None),
+        };
+        source.scan()?;
+        Ok(source)
+    }
+
+    fn scan(&self) -> Result<()> {
+        let entries = list_inbox_items(&self.dir)?
+            .into_iter()
+            .map(|item| ImageMeta {
+                data: SourceData::Path(self.dir.join(&item.file)),
+                orientation: item.orientation,
+                date_taken: item.date_taken,
+                exif_blob: None, // Will be extracted when loading the file
+                id: Some(item.id.clone()),
+                source_id: None, // Will be set when returning from next()
+                asset_metadata: serde_json::to_value(&item).ok(),
+            })
+            .collect();
+        *self.entries.write() = entries;
+        *self.last_scan.write() = Some(Utc::now());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ImageSource for InboxImageSource {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        let entries = self.entries.read().clone();
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let accepts =
+            |e: &ImageMeta| ctx.accepts(e.id.as_deref().unwrap_or_default(), e.orientation);
+        match self.order {
+            OrderKind::Sequential => {
+                let total = entries.len();
+                let start = self.cursor.fetch_add(1, AtomicOrdering::Relaxed);
+                for offset in 0..total {
+                    let item = &entries[(start + offset) % total];
+                    if accepts(item) {
+                        if offset > 0 {
+                            self.cursor.fetch_add(offset, AtomicOrdering::Relaxed);
+                        }
+                        return Ok(Some(item.clone()));
+                    }
+                }
+                Ok(None)
+            }
+            OrderKind::Random => {
+                let candidates: Vec<&ImageMeta> = entries.iter().filter(|e| accepts(e)).collect();
+                Ok(candidates.choose(&mut rng()).map(|e| (*e).clone()))
+            }
+            OrderKind::Shuffle => {
+                let ids: Vec<&str> = entries.iter().filter_map(|e| e.id.as_deref()).collect();
+                let drawn = self
+                    .bags
+                    .draw(&ctx.frame_id, &ids, |id| {
+                        entries
+                            .iter()
+                            .any(|e| e.id.as_deref() == Some(id) && accepts(e))
+                    })
+                    .await;
+                Ok(drawn.and_then(|id| {
+                    entries
+                        .iter()
+                        .find(|e| e.id.as_deref() == Some(id.as_str()))
+                        .cloned()
+                }))
+            }
+        }
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        self.scan()
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        let all = self
+            .entries
+            .read()
+            .iter()
+            .filter_map(|e| {
+                Some(AssetSummary {
+                    checksum: None,
+                    id: e.id.clone()?,
+                    orientation: e.orientation,
+                    date_taken: e.date_taken,
+                })
+            })
+            .collect();
+        Ok(AssetPage::from_summaries(all, query))
+    }
+
+    async fn get(&self, id: &str, _ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        Ok(self
+            .entries
+            .read()
+            .iter()
+            .find(|e| e.id.as_deref() == Some(id))
+            .cloned())
+    }
+
+    async fn health(&self) -> SourceHealth {
+        // An empty inbox is still usable; it fills up with uploads.
+        SourceHealth::healthy(*self.last_scan.read())
+    }
+
+    fn stats(&self) -> SourceStats {
+        SourceStats::from_entries(&self.entries.read())
+    }
+
+    fn serves_originals(&self) -> bool {
+        true
+    }
+
+    fn remove_asset_from_cache(&self, asset_id: &str) -> Result<bool> {
+        let mut entries = self.entries.write();
+        let before = entries.len();
+        entries.retain(|e| e.id.as_deref() != Some(asset_id));
+        Ok(before != entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+
+    #[tokio::test]
+    async fn saves_lists_and_deletes_uploads() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("inbox");
+        let cfg = InboxSource {
+            dir: Some(dir.to_string_lossy().to_string()),
+            order: Some(OrderKind::Sequential),
+        };
+        let png = test_support::png(10, 20);
+
+        assert!(save_inbox_upload(&dir, b"not an image", Some("sam")).is_err());
+        let item = save_inbox_upload(&dir, &png, Some("  sam ")).unwrap();
+        assert_eq!(item.uploader.as_deref(), Some("sam"));
+        assert_eq!(item.orientation, Orientation::Portrait);
+        assert!(item.file.ends_with(".png"));
+
+        let source = InboxImageSource::new("test_inbox", &cfg).unwrap();
+        let ctx = SelectionContext {
+            frame_id: "test".into(),
+            orientation: Orientation::Portrait,
+            ..Default::default()
+        };
+        let meta = source.next(&ctx).await.unwrap().unwrap();
+        assert_eq!(meta.id.as_deref(), Some(item.id.as_str()));
+        assert_eq!(meta.asset_metadata.unwrap()["uploader"], "sam");
+
+        assert!(delete_inbox_item(&dir, "../etc").is_err());
+        assert!(delete_inbox_item(&dir, &item.id).unwrap());
+        assert!(!delete_inbox_item(&dir, &item.id).unwrap());
+        source.refresh().await.unwrap();
+        assert!(source.next(&ctx).await.unwrap().is_none());
+        assert!(list_inbox_items(&dir).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,570 @@
+//! PhotoPrism source. Photos matching `query` are listed page by page from
+//! `/api/v1/photos`, authenticated with an app password, and shown through PhotoPrism's
+//! `fit_*` thumbnails, which are already rotated and sized to the frame. The listing's
+//! JSON for a photo is kept as its asset metadata, like Immich's.
+//!
+//! Results aren't `merged`: PhotoPrism would page through file rows and then fold
+//! stacked files (RAW+JPEG, live photos) together, leaving short pages mid-listing.
+//! Unmerged, each page holds exactly `count` file rows, de-duplicated here by `UID`.
+
+use super::remote;
+use super::{
+    AssetPage, AssetSummary, ImageMeta, ImageSource, ListQuery, SelectionContext, ShuffleBagStore,
+    SourceData, SourceHealth, SourceStats,
+};
+use crate::config::{Orientation, PhotoprismSource};
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+
+/// Photos requested per search page.
+const PAGE_SIZE: usize = 1000;
+/// Default seconds between re-listings.
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 3_600;
+/// Long edges of PhotoPrism's `fit_*` thumbnails, smallest first.
+const FIT_SIZES: [u32; 8] = [720, 1280, 1920, 2048, 2560, 3840, 4096, 7680];
+/// Thumbnail used when the frame's size is unknown.
+const DEFAULT_FIT: u32 = 1920;
+
+#[derive(Debug, Clone)]
+struct PhotoprismEntry {
+    uid: String,
+    /// SHA-1 of the file, which addresses its thumbnails.
+    hash: String,
+    /// Whether `hash` is the photo's primary file, preferred over other stacked files.
+    primary: bool,
+    orientation: Orientation,
+    date_taken: Option<DateTime<Utc>>,
+    metadata: serde_json::Value,
+}
+
+pub struct PhotoprismImageSource {
+    cfg: PhotoprismSource,
+    base_url: Option<String>,
+    entries: parking_lot::RwLock<Vec<PhotoprismEntry>>,
+    /// Token for thumbnail URLs, sent by the server with every search response.
+    preview_token: parking_lot::RwLock<Option<String>>,
+    last_list: AtomicU64,  // unix seconds of last listing, 0 = never
+    cursor: AtomicUsize,   // for sequential order
+    bags: ShuffleBagStore, // for shuffle order
+    client: reqwest::Client,
+    // Serializes listings so concurrent callers don't search twice.
+    sync_lock: tokio::sync::Mutex<()>,
+    /// Why the last listing failed, reported through `health`.
+    last_error: parking_lot::Mutex<Option<String>>,
+}
+
+impl PhotoprismImageSource {
+    pub fn new(id: &str, cfg: &PhotoprismSource) -> Result<Self> {
+        Ok(Self {
+            cfg: cfg.clone(),
+            base_url: cfg
+                .base_url
+                .as_deref()
+                .map(|u| u.trim_end_matches('/').to_string()),
+            entries: parking_lot::RwLock::new(Vec::new()),
+            preview_token: parking_lot::RwLock::new(None),
+            last_list: AtomicU64::new(0),
+            cursor: AtomicUsize::new(0),
+            bags: ShuffleBagStore::new(id),
+            client: reqwest::Client::new(),
+            sync_lock: tokio::sync::Mutex::new(()),
+            last_error: parking_lot::Mutex::new(None),
+        })
+    }
+
+    fn get_req(&self, base_url: &str, path: &str) -> reqwest::RequestBuilder {
+        let req = self.client.get(format!("{base_url}{path}"));
+        match &self.cfg.app_password {
+            Some(password) => req.bearer_auth(password),
+            None => req,
+        }
+    }
+
+    /// One page of `/api/v1/photos` file rows, oldest first, with the preview token it
+    /// came with.
+    async fn search(
+        &self,
+        base_url: &str,
+        count: usize,
+        offset: usize,
+    ) -> Result<(Vec<serde_json::Value>, Option<String>)> {
+        let mut query = vec![
+            ("count", count.to_string()),
+            ("offset", offset.to_string()),
+            ("order", "oldest".to_string()),
+        ];
+        if let Some(q) = self.cfg.query.as_deref().filter(|q| !q.trim().is_empty()) {
+            query.push(("q", q.to_string()));
+        }
+        let resp = self
+            .get_req(base_url, "/api/v1/photos")
+            .query(&query)
+            .send()
+            .await
+            .with_context(|| format!("photoprism search {base_url}"))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("photoprism search failed: {status} {body}");
+        }
+        let token = resp
+            .headers()
+            .get("X-Preview-Token")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let photos = resp
+            .json()
+            .await
+            .context("decoding photoprism search response")?;
+        Ok((photos, token))
+    }
+
+    async fn list_if_needed(&self) -> Result<()> {
+        let result = self.sync_if_needed().await;
+        *self.last_error.lock() = result.as_ref().err().map(|e| format!("{e:#}"));
+        result
+    }
+
+    async fn sync_if_needed(&self) -> Result<()> {
+        let Some(base_url) = &self.base_url else {
+            return Ok(());
+        };
+        let _guard = self.sync_lock.lock().await;
+        let now = Utc::now().timestamp() as u64;
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let interval = self
+            .cfg
+            .refresh_interval_secs
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL_SECS);
+        if last != 0 && now.saturating_sub(last) < interval {
+            return Ok(());
+        }
+
+        let mut entries: Vec<PhotoprismEntry> = Vec::new();
+        let mut by_uid: HashMap<String, usize> = HashMap::new();
+        let mut offset = 0;
+        loop {
+            let (rows, token) = self.search(base_url, PAGE_SIZE, offset).await?;
+            if let Some(token) = token {
+                *self.preview_token.write() = Some(token);
+            }
+            offset += rows.len();
+            let last_page = rows.len() < PAGE_SIZE;
+            for entry in rows.into_iter().filter_map(entry_from_photo) {
+                match by_uid.get(&entry.uid) {
+                    Some(&i) => {
+                        if entry.primary && !entries[i].primary {
+                            entries[i] = entry;
+                        }
+                    }
+                    None => {
+                        by_uid.insert(entry.uid.clone(), entries.len());
+                        entries.push(entry);
+                    }
+                }
+            }
+            if last_page {
+                break;
+            }
+        }
+        tracing::info!(base_url = %base_url, total = entries.len(), "Listed photoprism photos");
+        *self.entries.write() = entries;
+        self.last_list.store(now, AtomicOrdering::Relaxed);
+        Ok(())
+    }
+
+    /// Download the smallest `fit_*` thumbnail covering `max_dimension`, falling back
+    /// to smaller ones if the server doesn't render that size.
+    async fn fetch(
+        &self,
+        entry: &PhotoprismEntry,
+        max_dimension: Option<u32>,
+    ) -> Result<Option<ImageMeta>> {
+        let Some(base_url) = &self.base_url else {
+            return Ok(None);
+        };
+        let token = self
+            .preview_token
+            .read()
+            .clone()
+            .unwrap_or_else(|| "public".to_string());
+        for size in fit_candidates(max_dimension) {
+            let path = format!("/api/v1/t/{}/{token}/fit_{size}", entry.hash);
+            let resp = self.get_req(base_url, &path).send().await?;
+            if resp.status() == reqwest::StatusCode::NOT_FOUND {
+                // Deleted or archived since the last listing.
+                tracing::debug!(asset_id = %entry.uid, "photoprism photo gone; removing from cache");
+                self.remove_asset_from_cache(&entry.uid)?;
+                return Ok(None);
+            }
+            if !resp.status().is_success() {
+                tracing::debug!(asset_id = %entry.uid, size, status = %resp.status(), "photoprism thumbnail unusable; trying next");
+                continue;
+            }
+            let bytes = resp.bytes().await?;
+            tracing::info!(asset_id = %entry.uid, size, bytes_downloaded = bytes.len(), "Fetched PhotoPrism photo");
+            return Ok(Some(ImageMeta {
+                data: SourceData::Bytes(bytes.to_vec()),
+                orientation: entry.orientation,
+                date_taken: entry.date_taken,
+                exif_blob: None, // The date is added to the image's EXIF when loading
+                id: Some(entry.uid.clone()),
+                source_id: None, // Will be set when returning from next()
+                asset_metadata: Some(entry.metadata.clone()),
+            }));
+        }
+        Ok(None)
+    }
+}
+
+/// Thumbnail sizes to try: the smallest covering `max_dimension`, then smaller ones.
+fn fit_candidates(max_dimension: Option<u32>) -> Vec<u32> {
+    let edge = max_dimension.unwrap_or(DEFAULT_FIT);
+    let best = FIT_SIZES
+        .iter()
+        .position(|&s| s >= edge)
+        .unwrap_or(FIT_SIZES.len() - 1);
+    FIT_SIZES[..=best].iter().rev().copied().collect()
+}
+
+/// Listing entry for a search result; videos are skipped since only their still frame
+/// could be shown.
+fn entry_from_photo(photo: serde_json::Value) -> Option<PhotoprismEntry> {
+    if photo.get("Type").and_then(|v| v.as_str()) == Some("video") {
+        return None;
+    }
+    let uid = photo.get("UID")?.as_str()?.to_string();
+    let hash = photo.get("Hash")?.as_str()?.to_string();
+    let primary = photo
+        .get("FilePrimary")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    Some(PhotoprismEntry {
+        uid,
+        hash,
+        primary,
+        orientation: photoprism_photo_orientation(&photo),
+        date_taken: photoprism_photo_date(&photo),
+        metadata: photo,
+    })
+}
+
+/// Display orientation of a search result. PhotoPrism reports the primary file's
+/// `Width` and `Height` with its EXIF rotation already applied.
+fn photoprism_photo_orientation(photo: &serde_json::Value) -> Orientation {
+    let dim = |key| photo.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    Orientation::from_dims(dim("Width"), dim("Height"))
+}
+
+/// Capture time of a search result. `TakenAt` is an instant when PhotoPrism knows the
+/// photo's time zone; otherwise the wall-clock `TakenAtLocal` is interpreted in local
+/// time, matching how offset-less EXIF dates are read from files. Times PhotoPrism
+/// guessed from file dates (`TakenSrc` "auto") are ignored.
+fn photoprism_photo_date(photo: &serde_json::Value) -> Option<DateTime<Utc>> {
+    if photo.get("TakenSrc").and_then(|v| v.as_str()) == Some("auto") {
+        return None;
+    }
+    let parse = |key| {
+        photo
+            .get(key)
+            .and_then(|v| v.as_str())
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+    };
+    let has_zone = photo
+        .get("TimeZone")
+        .and_then(|v| v.as_str())
+        .is_some_and(|z| !z.is_empty() && z != "Local");
+    if !has_zone
+        && let Some(local) = parse("TakenAtLocal")
+        && let Some(dt) = Local.from_local_datetime(&local.naive_utc()).earliest()
+    {
+        return Some(dt.with_timezone(&Utc));
+    }
+    parse("TakenAt").map(|dt| dt.with_timezone(&Utc))
+}
+
+#[async_trait]
+impl ImageSource for PhotoprismImageSource {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        if let Err(e) = self.list_if_needed().await {
+            tracing::warn!(error = %format!("{e:#}"), "photoprism listing failed");
+        }
+        let snapshot: Vec<PhotoprismEntry> = { self.entries.read().clone() };
+        let items: Vec<(&str, Orientation)> = snapshot
+            .iter()
+            .map(|f| (f.uid.as_str(), f.orientation))
+            .collect();
+        let order = self.cfg.order.unwrap_or_default();
+        remote::pick_by_order(order, &items, ctx, &self.cursor, &self.bags, |i| {
+            self.fetch(&snapshot[i], ctx.max_dimension)
+        })
+        .await
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        // force next call to list to actually list now
+        self.last_list.store(0, AtomicOrdering::Relaxed);
+        self.list_if_needed().await
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        self.list_if_needed().await?;
+        let all: Vec<AssetSummary> = self
+            .entries
+            .read()
+            .iter()
+            .map(|e| AssetSummary {
+                checksum: None,
+                id: e.uid.clone(),
+                orientation: e.orientation,
+                date_taken: e.date_taken,
+            })
+            .collect();
+        Ok(AssetPage::from_summaries(all, query))
+    }
+
+    async fn get(&self, id: &str, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        let entry = self.entries.read().iter().find(|e| e.uid == id).cloned();
+        match entry {
+            Some(entry) => self.fetch(&entry, ctx.max_dimension).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn health(&self) -> SourceHealth {
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let last_refresh = (last > 0)
+            .then(|| DateTime::from_timestamp(last as i64, 0))
+            .flatten();
+        let Some(base_url) = &self.base_url else {
+            return SourceHealth::unhealthy("base_url not configured", last_refresh);
+        };
+        if let Some(error) = self.last_error.lock().clone() {
+            return SourceHealth::unhealthy(format!("listing failed: {error}"), last_refresh);
+        }
+        match self.search(base_url, 1, 0).await {
+            Ok(_) => SourceHealth::healthy(last_refresh),
+            Err(e) => SourceHealth::unhealthy(format!("{e:#}"), last_refresh),
+        }
+    }
+
+    fn stats(&self) -> SourceStats {
+        SourceStats::from_orientations(self.entries.read().iter().map(|f| f.orientation))
+    }
+
+    fn remove_asset_from_cache(&self, asset_id: &str) -> Result<bool> {
+        let mut entries = self.entries.write();
+        let before = entries.len();
+        entries.retain(|e| e.uid != asset_id);
+        Ok(before != entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+    use axum::extract::{Path, Query, State};
+    use axum::http::{HeaderMap, StatusCode, header};
+    use axum::response::{IntoResponse, Response};
+    use axum::{Json, Router, routing::get};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct MockPhotoprism {
+        photos: Vec<Value>,
+        /// Search queries and thumbnail paths requested.
+        searches: Vec<HashMap<String, String>>,
+        thumbnails: Vec<String>,
+    }
+
+    type Shared = Arc<parking_lot::Mutex<MockPhotoprism>>;
+
+    fn authorized(headers: &HeaderMap) -> bool {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            == Some("Bearer app-pass")
+    }
+
+    async fn photos(
+        State(state): State<Shared>,
+        headers: HeaderMap,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Response {
+        if !authorized(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        let mut state = state.lock();
+        let count: usize = query["count"].parse().unwrap();
+        let offset: usize = query["offset"].parse().unwrap();
+        let mut page: Vec<Value> = state
+            .photos
+            .iter()
+            .skip(offset)
+            .take(count)
+            .cloned()
+            .collect();
+        if query.get("merged").map(String::as_str) == Some("true") {
+            // Like PhotoPrism: stacked files are merged after paging, shortening the page.
+            page.dedup_by(|a, b| a["UID"] == b["UID"]);
+        }
+        state.searches.push(query);
+        ([("X-Preview-Token", "prev1")], Json(page)).into_response()
+    }
+
+    async fn thumbnail(
+        State(state): State<Shared>,
+        Path((hash, token, size)): Path<(String, String, String)>,
+    ) -> Response {
+        state
+            .lock()
+            .thumbnails
+            .push(format!("{hash}/{token}/{size}"));
+        if token != "prev1" || hash == "gone" {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        if size == "fit_7680" {
+            // Larger than the server's configured thumbnail limit.
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        (
+            [(header::CONTENT_TYPE, "image/jpeg")],
+            format!("{hash}-{size}"),
+        )
+            .into_response()
+    }
+
+    #[tokio::test]
+    async fn lists_with_query_and_fetches_fit_thumbnails() {
+        let state: Shared = Arc::default();
+        state.lock().photos = vec![
+            json!({"UID": "p1", "Type": "image", "Hash": "h1", "Width": 3000, "Height": 4000,
+                   "TakenAt": "2021-06-01T10:00:00Z", "TakenAtLocal": "2021-06-01T12:00:00Z",
+                   "TakenSrc": "meta", "TimeZone": "Europe/Berlin", "Title": "Lake"}),
+            json!({"UID": "v1", "Type": "video", "Hash": "hv", "Width": 1920, "Height": 1080}),
+            json!({"UID": "p2", "Type": "raw", "Hash": "gone", "Width": 6000, "Height": 4000,
+                   "TakenAt": "2020-01-01T00:00:00Z", "TakenSrc": "auto"}),
+        ];
+        let app = Router::new()
+            .route("/api/v1/photos", get(photos))
+            .route("/api/v1/t/{hash}/{token}/{size}", get(thumbnail))
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+
+        let cfg = PhotoprismSource {
+            base_url: Some(format!("http://{addr}/")),
+            app_password: Some("app-pass".into()),
+            query: Some("favorite:true label:cat".into()),
+            ..Default::default()
+        };
+        let source = PhotoprismImageSource::new("test_photoprism", &cfg).unwrap();
+
+        let page = source
+            .list(&ListQuery {
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let listed: Vec<_> = page
+            .items
+            .iter()
+            .map(|a| (a.id.as_str(), a.orientation, a.date_taken))
+            .collect();
+        let taken = DateTime::parse_from_rfc3339("2021-06-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            listed,
+            [
+                ("p1", Orientation::Portrait, Some(taken)),
+                ("p2", Orientation::Landscape, None),
+            ]
+        );
+        let searches = std::mem::take(&mut state.lock().searches);
+        assert_eq!(searches.len(), 1);
+        assert_eq!(searches[0]["q"], "favorite:true label:cat");
+
+        let ctx = SelectionContext {
+            max_dimension: Some(1600),
+            ..Default::default()
+        };
+        let meta = source.get("p1", &ctx).await.unwrap().unwrap();
+        match meta.data {
+            SourceData::Bytes(bytes) => assert_eq!(bytes, b"h1-fit_1920"),
+            _ => panic!("expected bytes"),
+        }
+        assert_eq!(meta.date_taken, Some(taken));
+        assert_eq!(meta.asset_metadata.unwrap()["Title"], "Lake");
+
+        // Sizes the server refuses fall back to the next smaller one.
+        let ctx = SelectionContext {
+            max_dimension: Some(5000),
+            ..Default::default()
+        };
+        source.get("p1", &ctx).await.unwrap().unwrap();
+        assert_eq!(
+            state.lock().thumbnails[1..],
+            ["h1/prev1/fit_7680", "h1/prev1/fit_4096"]
+        );
+
+        // Photos whose thumbnail is gone are dropped from the listing.
+        assert!(source.get("p2", &ctx).await.unwrap().is_none());
+        assert!(!source.remove_asset_from_cache("p2").unwrap());
+    }
+
+    #[tokio::test]
+    async fn pages_through_stacked_files() {
+        let state: Shared = Arc::default();
+        // A RAW+JPEG stack in the first page and another straddling the page boundary.
+        let mut rows = vec![
+            json!({"UID": "s0", "Type": "raw", "Hash": "raw0", "FilePrimary": false,
+                   "Width": 4000, "Height": 3000}),
+            json!({"UID": "s0", "Type": "raw", "Hash": "jpg0", "FilePrimary": true,
+                   "Width": 4000, "Height": 3000}),
+        ];
+        for i in 1..PAGE_SIZE + 200 {
+            rows.push(
+                json!({"UID": format!("p{i}"), "Type": "image", "Hash": format!("h{i}"),
+                             "FilePrimary": true, "Width": 4000, "Height": 3000}),
+            );
+            if i == PAGE_SIZE - 2 {
+                rows.push(
+                    json!({"UID": format!("p{i}"), "Type": "image", "Hash": "sidecar",
+                                 "FilePrimary": false, "Width": 4000, "Height": 3000}),
+                );
+            }
+        }
+        state.lock().photos = rows;
+        let app = Router::new()
+            .route("/api/v1/photos", get(photos))
+            .with_state(state.clone());
+        let addr = test_support::serve(app).await;
+        let cfg = PhotoprismSource {
+            base_url: Some(format!("http://{addr}")),
+            app_password: Some("app-pass".into()),
+            ..Default::default()
+        };
+        let source = PhotoprismImageSource::new("test_photoprism_stacks", &cfg).unwrap();
+        source.refresh().await.unwrap();
+
+        let entries = source.entries.read().clone();
+        assert_eq!(entries.len(), PAGE_SIZE + 200);
+        assert_eq!(entries[0].hash, "jpg0");
+        let straddling = format!("p{}", PAGE_SIZE - 2);
+        let entry = entries.iter().find(|e| e.uid == straddling).unwrap();
+        assert_eq!(entry.hash, format!("h{}", PAGE_SIZE - 2));
+        assert!(
+            state
+                .lock()
+                .searches
+                .iter()
+                .all(|q| !q.contains_key("merged"))
+        );
+    }
+}
@@ -0,0 +1,564 @@
+//! Building blocks for sources that list files on a server and download them on
+//! selection (WebDAV, S3, HTTP, PhotoPrism, Google Photos): picking from the listing by
+//! `order`, include globs, header probing for orientation and date, the persisted
+//! listing and the ETag-revalidated download cache.
+
+use super::shuffle::ShuffleBagStore;
+use super::{ImageMeta, SelectionContext, SourceData};
+use crate::config::{OrderKind, Orientation};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use image::ImageDecoder;
+use rand::{Rng, rng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::SystemTime;
+
+/// Files listed when no `include` patterns are configured: formats we can decode.
+const DEFAULT_INCLUDE: [&str; 3] = ["**/*.jpg", "**/*.jpeg", "**/*.png"];
+/// Successive header sizes fetched from a file to find its dimensions and EXIF.
+const PROBE_BYTES: [u64; 2] = [64 * 1024, 1024 * 1024];
+/// Files probed concurrently while listing.
+const PROBE_CONCURRENCY: usize = 8;
+/// Downloads kept on disk for revalidation; older ones are deleted.
+const DOWNLOAD_CACHE_FILES: usize = 64;
+
+/// Attempts at finding a random or shuffled item that can be fetched.
+const PICK_ATTEMPTS: usize = 32;
+
+/// Pick an item of a listing for `ctx` according to `order`. `items` are the asset ids
+/// and orientations of the listing; `fetch` downloads the item at an index, or yields
+/// `None` if it can't be shown, in which case another item is tried. Sequential order
+/// advances `cursor`; shuffle order draws from the frame's bag in `bags`.
+pub(super) async fn pick_by_order<F, Fut>(
+    order: OrderKind,
+    items: &[(&str, Orientation)],
+    ctx: &SelectionContext,
+    cursor: &AtomicUsize,
+    bags: &ShuffleBagStore,
+    mut fetch: F,
+) -> Result<Option<ImageMeta>>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Result<Option<ImageMeta>>>,
+{
+    if items.is_empty() {
+        return Ok(None);
+    }
+    match order {
+        OrderKind::Random => {
+            for _ in 0..PICK_ATTEMPTS {
+                let idx = rng().random_range(0..items.len());
+                let (id, orientation) = items[idx];
+                if !ctx.accepts(id, orientation) {
+                    continue;
+                }
+                if let Some(meta) = fetch(idx).await? {
+                    return Ok(Some(meta));
+                }
+            }
+            Ok(None)
+        }
+        OrderKind::Sequential => {
+            let total = items.len();
+            let start = cursor.fetch_add(1, AtomicOrdering::Relaxed);
+            for offset in 0..total {
+                let idx = (start + offset) % total;
+                let (id, orientation) = items[idx];
+                if !ctx.accepts(id, orientation) {
+                    continue;
+                }
+                if let Some(meta) = fetch(idx).await? {
+                    if offset > 0 {
+                        cursor.fetch_add(offset, AtomicOrdering::Relaxed);
+                    }
+                    return Ok(Some(meta));
+                }
+            }
+            Ok(None)
+        }
+        OrderKind::Shuffle => {
+            let index: HashMap<&str, usize> = items
+                .iter()
+                .enumerate()
+                .map(|(i, (id, _))| (*id, i))
+                .collect();
+            let ids: Vec<&str> = items.iter().map(|(id, _)| *id).collect();
+            // Items that fail to download are counted as drawn; try a bounded number.
+            for _ in 0..PICK_ATTEMPTS.min(items.len()) {
+                let Some(id) = bags
+                    .draw(&ctx.frame_id, &ids, |id| {
+                        index.get(id).is_some_and(|&i| ctx.accepts(id, items[i].1))
+                    })
+                    .await
+                else {
+                    return Ok(None);
+                };
+                if let Some(meta) = fetch(index[id.as_str()]).await? {
+                    return Ok(Some(meta));
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// A listed file with the metadata probed from its header.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct RemoteFile {
+    /// Stable identifier within the source (a path or object key), used as the asset id.
+    pub id: String,
+    pub url: String,
+    pub etag: Option<String>,
+    /// `None` until the header has been probed; a failed probe is retried on the next
+    /// listing.
+    pub orientation: Option<Orientation>,
+    pub date_taken: Option<DateTime<Utc>>,
+}
+
+impl RemoteFile {
+    /// A freshly listed file whose header hasn't been probed yet.
+    pub fn listed(id: String, url: String, etag: Option<String>) -> Self {
+        Self {
+            id,
+            url,
+            etag,
+            orientation: None,
+            date_taken: None,
+        }
+    }
+
+    /// Probed orientation, or landscape for files not probed yet.
+    pub fn orientation(&self) -> Orientation {
+        self.orientation.unwrap_or(Orientation::Landscape)
+    }
+
+    pub fn image_meta(&self, bytes: Vec<u8>) -> ImageMeta {
+        ImageMeta {
+            data: SourceData::Bytes(bytes),
+            orientation: self.orientation(),
+            date_taken: self.date_taken,
+            exif_blob: None, // Extracted from the bytes when loading
+            id: Some(self.id.clone()),
+            source_id: None, // Will be set when returning from next()
+            asset_metadata: None,
+        }
+    }
+}
+
+/// `include` glob patterns, matched case-insensitively against relative paths; `*`
+/// stops at `/`, `**` doesn't.
+pub(super) struct IncludeFilter(Vec<glob::Pattern>);
+
+impl IncludeFilter {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns: Vec<&str> = if patterns.is_empty() {
+            DEFAULT_INCLUDE.to_vec()
+        } else {
+            patterns.iter().map(String::as_str).collect()
+        };
+        patterns
+            .into_iter()
+            .map(|p| glob::Pattern::new(p).with_context(|| format!("invalid include pattern {p}")))
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.0.iter().any(|p| p.matches_with(path, options))
+    }
+}
+
+/// Listing persisted between runs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ListingState {
+    /// Where the files were listed from; a mismatch discards the listing.
+    origin: String,
+    files: Vec<RemoteFile>,
+}
+
+/// Restore a listing saved by [`save_listing`] for the same `origin`.
+pub(super) fn load_listing(path: &Path, origin: &str) -> Option<Vec<RemoteFile>> {
+    let bytes = std::fs::read(path).ok()?;
+    let state: ListingState = match serde_json::from_slice(&bytes) {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::warn!(path=%path.display(), error=%e, "ignoring unreadable listing cache");
+            return None;
+        }
+    };
+    if state.origin != origin {
+        tracing::info!(path=%path.display(), "source location changed; discarding listing cache");
+        return None;
+    }
+    tracing::debug!(path=%path.display(), files = state.files.len(), "restored listing cache");
+    Some(state.files)
+}
+
+pub(super) async fn save_listing(path: &Path, origin: &str, files: &[RemoteFile]) -> Result<()> {
+    let state = ListingState {
+        origin: origin.to_string(),
+        files: files.to_vec(),
+    };
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(&state)?)
+        .await
+        .with_context(|| format!("writing {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .with_context(|| format!("renaming {}", path.display()))?;
+    Ok(())
+}
+
+/// Merge a new listing with the previous one: probed files with an unchanged ETag keep
+/// their metadata, the rest are returned separately for probing.
+pub(super) fn reuse_unchanged(
+    known: &[RemoteFile],
+    listed: Vec<RemoteFile>,
+) -> (Vec<RemoteFile>, Vec<RemoteFile>) {
+    reuse_matching(known, listed, |old, new| {
+        old.etag.is_some() && old.etag == new.etag
+    })
+}
+
+/// [`reuse_unchanged`] for listings without ETags, where `same` tells whether a listed
+/// file is still the one probed before. A date from the new listing wins.
+pub(super) fn reuse_matching(
+    known: &[RemoteFile],
+    listed: Vec<RemoteFile>,
+    same: impl Fn(&RemoteFile, &RemoteFile) -> bool,
+) -> (Vec<RemoteFile>, Vec<RemoteFile>) {
+    let known: HashMap<&str, &RemoteFile> = known.iter().map(|f| (f.id.as_str(), f)).collect();
+    let mut unchanged = Vec::with_capacity(listed.len());
+    let mut changed = Vec::new();
+    for file in listed {
+        match known.get(file.id.as_str()) {
+            Some(old) if old.orientation.is_some() && same(old, &file) => {
+                unchanged.push(RemoteFile {
+                    url: file.url,
+                    date_taken: file.date_taken.or(old.date_taken),
+                    ..(*old).clone()
+                });
+            }
+            _ => changed.push(file),
+        }
+    }
+    (unchanged, changed)
+}
+
+/// Probe the headers of `pending` files, a few at a time. `request` builds a GET for a
+/// file, to which a Range header is added; it is called just before the request is
+/// sent, so signatures don't go stale while a long listing is probed. Files without a
+/// request stay unprobed.
+pub(super) async fn probe_all<F>(pending: Vec<RemoteFile>, request: F) -> Vec<RemoteFile>
+where
+    F: Fn(&RemoteFile) -> Option<reqwest::RequestBuilder>,
+{
+    let mut probed = Vec::with_capacity(pending.len());
+    let mut pending = pending.into_iter().peekable();
+    while pending.peek().is_some() {
+        let mut set = tokio::task::JoinSet::new();
+        for mut file in pending.by_ref().take(PROBE_CONCURRENCY) {
+            let Some(req) = request(&file) else {
+                probed.push(file);
+                continue;
+            };
+            set.spawn(async move {
+                if let Some((orientation, date_taken)) = probe_header(&file.id, req).await {
+                    file.orientation = Some(orientation);
+                    // A date already known from the listing wins over the file's EXIF.
+                    file.date_taken = file.date_taken.or(date_taken);
+                }
+                file
+            });
+        }
+        while let Some(joined) = set.join_next().await {
+            match joined {
+                Ok(file) => probed.push(file),
+                Err(e) => tracing::warn!(error=%e, "header probe task failed"),
+            }
+        }
+    }
+    probed
+}
+
+/// Orientation and capture date from the start of a file, widening the Range window
+/// once if the headers don't fit. Files without readable dimensions are treated as
+/// landscape; `None` if the request failed.
+async fn probe_header(
+    id: &str,
+    req: reqwest::RequestBuilder,
+) -> Option<(Orientation, Option<DateTime<Utc>>)> {
+    for limit in PROBE_BYTES {
+        let Some(req) = req.try_clone() else {
+            break;
+        };
+        let mut resp = match req
+            .header(reqwest::header::RANGE, format!("bytes=0-{}", limit - 1))
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => resp,
+            Ok(resp) => {
+                tracing::debug!(asset_id = %id, status = %resp.status(), "header probe failed");
+                return None;
+            }
+            Err(e) => {
+                tracing::debug!(asset_id = %id, error = %e, "header probe failed");
+                return None;
+            }
+        };
+        let mut bytes = Vec::new();
+        while (bytes.len() as u64) < limit {
+            match resp.chunk().await {
+                Ok(Some(chunk)) => bytes.extend_from_slice(&chunk),
+                _ => break,
+            }
+        }
+        bytes.truncate(limit as usize);
+        if let Some(found) = header_metadata(&bytes) {
+            return Some(found);
+        }
+        if (bytes.len() as u64) < limit {
+            break; // whole file read
+        }
+    }
+    tracing::debug!(asset_id = %id, "no dimensions in file header; assuming landscape");
+    Some((Orientation::Landscape, None))
+}
+
+/// Display orientation (dimensions with EXIF rotation applied) and EXIF capture date
+/// from a possibly truncated image. `None` if the dimensions aren't in `bytes`.
+pub(super) fn header_metadata(bytes: &[u8]) -> Option<(Orientation, Option<DateTime<Utc>>)> {
+    use image::metadata::Orientation as Exif;
+    let mut decoder = image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_decoder()
+        .ok()?;
+    let (w, h) = decoder.dimensions();
+    let rotated = matches!(
+        decoder.orientation().unwrap_or(Exif::NoTransforms),
+        Exif::Rotate90 | Exif::Rotate270 | Exif::Rotate90FlipH | Exif::Rotate270FlipH
+    );
+    let (w, h) = if rotated { (h, w) } else { (w, h) };
+    let date_taken = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(bytes))
+        .ok()
+        .and_then(|exif| crate::frame::exif_date_taken(&exif));
+    Some((Orientation::from_dims(w, h), date_taken))
+}
+
+/// Outcome of [`DownloadCache::fetch`].
+pub(super) enum Download {
+    Bytes(Vec<u8>),
+    /// The server no longer has the file.
+    Gone,
+    /// Any other failure, already logged.
+    Failed,
+}
+
+/// The most recent downloads of a source, each stored next to its ETag so showing a
+/// file again only costs a conditional request.
+pub(super) struct DownloadCache {
+    dir: PathBuf,
+}
+
+impl DownloadCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    #[cfg(test)]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        id.hash(&mut hasher);
+        self.dir.join(format!("{:016x}", hasher.finish()))
+    }
+
+    /// Download `file` with `req` (a GET for it), reusing the kept copy when the server
+    /// answers `If-None-Match` with 304.
+    pub async fn fetch(&self, file: &RemoteFile, req: reqwest::RequestBuilder) -> Result<Download> {
+        let path = self.path(&file.id);
+        let cached_etag = match tokio::fs::try_exists(&path).await {
+            Ok(true) => tokio::fs::read_to_string(path.with_extension("etag"))
+                .await
+                .ok(),
+            _ => None,
+        };
+
+        let req = match &cached_etag {
+            Some(etag) => req.header(reqwest::header::IF_NONE_MATCH, etag),
+            None => req,
+        };
+        let resp = req
+            .send()
+            .await
+            .with_context(|| format!("downloading {}", file.id))?;
+        match resp.status() {
+            reqwest::StatusCode::NOT_MODIFIED => {
+                tracing::debug!(asset_id = %file.id, "file unchanged; using kept download");
+                // Refresh the mtime so the copy counts as recently used.
+                if let Ok(f) = std::fs::File::options().write(true).open(&path) {
+                    let _ = f.set_modified(SystemTime::now());
+                }
+                let bytes = tokio::fs::read(&path)
+                    .await
+                    .with_context(|| format!("reading {}", path.display()))?;
+                Ok(Download::Bytes(bytes))
+            }
+            reqwest::StatusCode::NOT_FOUND => Ok(Download::Gone),
+            status if !status.is_success() => {
+                tracing::warn!(asset_id = %file.id, %status, "download failed");
+                Ok(Download::Failed)
+            }
+            _ => {
+                let etag = resp
+                    .headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+                let bytes = resp.bytes().await?.to_vec();
+                tracing::info!(asset_id = %file.id, bytes = bytes.len(), "Downloaded remote file");
+                if let Some(etag) = etag
+                    && let Err(e) = self.keep(&path, &bytes, &etag).await
+                {
+                    tracing::warn!(error=%e, "failed to keep download");
+                }
+                Ok(Download::Bytes(bytes))
+            }
+        }
+    }
+
+    /// Store a download with its ETag and delete the least recently used copies beyond
+    /// [`DOWNLOAD_CACHE_FILES`].
+    async fn keep(&self, path: &Path, bytes: &[u8], etag: &str) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        tokio::fs::write(path.with_extension("etag"), etag).await?;
+
+        let mut kept = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let p = entry.path();
+            if p.extension().is_none()
+                && let Ok(modified) = entry.metadata().await.and_then(|m| m.modified())
+            {
+                kept.push((modified, p));
+            }
+        }
+        if kept.len() > DOWNLOAD_CACHE_FILES {
+            kept.sort();
+            for (_, old) in &kept[..kept.len() - DOWNLOAD_CACHE_FILES] {
+                let _ = tokio::fs::remove_file(old).await;
+                let _ = tokio::fs::remove_file(old.with_extension("etag")).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::test_support;
+    use axum::{Router, http::StatusCode, routing::get};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn failed_probes_are_retried_on_next_listing() {
+        let portrait = test_support::png(30, 40);
+        let up = Arc::new(AtomicBool::new(false));
+        let flag = up.clone();
+        let app = Router::new().route(
+            "/tall.png",
+            get(move || async move {
+                if flag.load(Ordering::Relaxed) {
+                    Ok(portrait)
+                } else {
+                    Err(StatusCode::SERVICE_UNAVAILABLE)
+                }
+            }),
+        );
+        let url = format!("http://{}/tall.png", test_support::serve(app).await);
+
+        let client = reqwest::Client::new();
+        let listing = || {
+            vec![RemoteFile::listed(
+                "tall".into(),
+                url.clone(),
+                Some("v1".into()),
+            )]
+        };
+        let probe = |files: Vec<RemoteFile>| probe_all(files, |f| Some(client.get(&f.url)));
+
+        let known = probe(listing()).await;
+        assert_eq!(known[0].orientation, None);
+
+        up.store(true, Ordering::Relaxed);
+        let (unchanged, changed) = reuse_unchanged(&known, listing());
+        assert!(unchanged.is_empty());
+        let known = probe(changed).await;
+        assert_eq!(known[0].orientation, Some(Orientation::Portrait));
+
+        let (unchanged, changed) = reuse_unchanged(&known, listing());
+        assert_eq!(unchanged.len(), 1);
+        assert!(changed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sequential_pick_skips_rejected_and_unfetchable_items() {
+        let items = [
+            ("a", Orientation::Landscape),
+            ("b", Orientation::Portrait),
+            ("c", Orientation::Landscape),
+            ("d", Orientation::Landscape),
+        ];
+        let ctx = SelectionContext {
+            orientation: Orientation::Landscape,
+            exclude: ["c".to_string()].into(),
+            ..Default::default()
+        };
+        let cursor = AtomicUsize::new(0);
+        let bags = ShuffleBagStore::new("test_pick");
+        let fetch = |i: usize| async move {
+            // `a` can't be downloaded.
+            Ok((i != 0).then(|| ImageMeta {
+                data: SourceData::Bytes(Vec::new()),
+                orientation: items[i].1,
+                date_taken: None,
+                exif_blob: None,
+                id: Some(items[i].0.to_string()),
+                source_id: None,
+                asset_metadata: None,
+            }))
+        };
+        let pick = || pick_by_order(OrderKind::Sequential, &items, &ctx, &cursor, &bags, fetch);
+        let first = pick().await.unwrap().unwrap();
+        assert_eq!(first.id.as_deref(), Some("d"));
+        // The cursor moved past `d`, so the next round starts over and still finds it.
+        let second = pick().await.unwrap().unwrap();
+        assert_eq!(second.id.as_deref(), Some("d"));
+        assert!(
+            pick_by_order(OrderKind::Random, &[], &ctx, &cursor, &bags, fetch)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
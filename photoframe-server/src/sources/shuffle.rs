@@ -0,0 +1,255 @@
+//! Shuffle-bag ordering: every eligible asset is drawn once before any asset repeats.
+//!
+//! Bags are kept per (frame, source) and persisted as `<frame_id>_<source_id>_shuffle.json`
+//! so a restart continues the current round instead of starting over.
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ShuffleBag {
+    /// Asset ids still to be drawn in the current round, in draw order.
+    remaining: Vec<String>,
+    /// Asset ids already drawn in the current round.
+    drawn: HashSet<String>,
+}
+
+impl ShuffleBag {
+    /// Reconcile the bag with the current entry list: forget ids that disappeared and
+    /// insert newly listed ids at random positions among the remaining ones.
+    pub fn sync<R: Rng + ?Sized>(&mut self, ids: &[&str], rng: &mut R) {
+        let current: HashSet<&str> = ids.iter().copied().collect();
+        self.remaining.retain(|id| current.contains(id.as_str()));
+        self.drawn.retain(|id| current.contains(id.as_str()));
+
+        let known: HashSet<&str> = self
+            .remaining
+            .iter()
+            .map(String::as_str)
+            .chain(self.drawn.iter().map(String::as_str))
+            .collect();
+        let mut added: Vec<String> = ids
+            .iter()
+            .filter(|id| !known.contains(*id))
+            .map(|id| id.to_string())
+            .collect();
+        // Deduplicate in case the listing itself contained repeats.
+        let mut seen = HashSet::new();
+        added.retain(|id| seen.insert(id.clone()));
+        for id in added {
+            let pos = rng.random_range(0..=self.remaining.len());
+            self.remaining.insert(pos, id);
+        }
+    }
+
+    /// Draw the next id accepted by `eligible`. When every eligible id has already been
+    /// drawn this round, the drawn ids are reshuffled back into the bag and drawing is
+    /// retried once.
+    pub fn draw<R, F>(&mut self, rng: &mut R, mut eligible: F) -> Option<String>
+    where
+        R: Rng + ?Sized,
+        F: FnMut(&str) -> bool,
+    {
+        for _ in 0..2 {
+            if let Some(pos) = self.remaining.iter().position(|id| eligible(id)) {
+                let id = self.remaining.remove(pos);
+                self.drawn.insert(id.clone());
+                return Some(id);
+            }
+            if self.drawn.is_empty() {
+                return None;
+            }
+            let mut refill: Vec<String> = self.drawn.drain().collect();
+            refill.shuffle(rng);
+            self.remaining.extend(refill);
+        }
+        None
+    }
+}
+
+/// Per-frame shuffle bags for a single source, loaded lazily from disk.
+pub struct ShuffleBagStore {
+    source_id: String,
+    bags: parking_lot::Mutex<HashMap<String, ShuffleBag>>,
+    /// Incremented for every snapshot handed to the writer.
+    version: AtomicU64,
+    /// Version last written per frame; older snapshots that finish late are skipped.
+    written: Arc<parking_lot::Mutex<HashMap<String, u64>>>,
+}
+
+impl ShuffleBagStore {
+    pub fn new(source_id: &str) -> Self {
+        Self {
+            source_id: source_id.to_string(),
+            bags: parking_lot::Mutex::new(HashMap::new()),
+            version: AtomicU64::new(0),
+            written: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn path(&self, frame_id: &str) -> PathBuf {
+        PathBuf::from(format!("{frame_id}_{}_shuffle.json", self.source_id))
+    }
+
+    /// Sync the frame's bag against `ids`, draw the next eligible id and persist the bag.
+    /// The bag file is only read on a frame's first draw and written in the background.
+    pub async fn draw<F>(&self, frame_id: &str, ids: &[&str], eligible: F) -> Option<String>
+    where
+        F: FnMut(&str) -> bool,
+    {
+        if !self.bags.lock().contains_key(frame_id) {
+            let loaded = self.load(frame_id).await;
+            self.bags
+                .lock()
+                .entry(frame_id.to_string())
+                .or_insert(loaded);
+        }
+        let mut bags = self.bags.lock();
+        let bag = bags.entry(frame_id.to_string()).or_default();
+        let mut rng = rand::rng();
+        bag.sync(ids, &mut rng);
+        let drawn = bag.draw(&mut rng, eligible);
+        if drawn.is_some() {
+            match serde_json::to_vec(bag) {
+                Ok(bytes) => self.persist(frame_id, bytes),
+                Err(e) => {
+                    tracing::warn!(frame = %frame_id, source = %self.source_id, error = %e, "failed to serialize shuffle bag")
+                }
+            }
+        }
+        drawn
+    }
+
+    /// Read the frame's persisted bag off the async workers; an empty bag if there is none.
+    async fn load(&self, frame_id: &str) -> ShuffleBag {
+        let path = self.path(frame_id);
+        let read = move || {
+            std::fs::read(path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+                .unwrap_or_default()
+        };
+        tokio::task::spawn_blocking(read).await.unwrap_or_default()
+    }
+
+    /// Write a bag snapshot off the async workers (inline when there is no runtime).
+    fn persist(&self, frame_id: &str, bytes: Vec<u8>) {
+        let version = self.version.fetch_add(1, Ordering::Relaxed) + 1;
+        let path = self.path(frame_id);
+        let frame_id = frame_id.to_string();
+        let source_id = self.source_id.clone();
+        let written = self.written.clone();
+        let write = move || {
+            let mut written = written.lock();
+            if written.get(&frame_id).is_some_and(|v| *v >= version) {
+                return;
+            }
+            let tmp = path.with_extension("json.tmp");
+            let res = std::fs::write(&tmp, &bytes).and_then(|_| std::fs::rename(&tmp, &path));
+            match res {
+                Ok(()) => {
+                    written.insert(frame_id, version);
+                }
+                Err(e) => {
+                    tracing::warn!(frame = %frame_id, source = %source_id, error = %e, "failed to persist shuffle bag")
+                }
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(write);
+            }
+            Err(_) => write(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn draws_every_id_once_before_repeating() {
+        let ids = ["a", "b", "c", "d", "e"];
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut bag = ShuffleBag::default();
+        bag.sync(&ids, &mut rng);
+        let mut first_round: Vec<String> = (0..ids.len())
+            .map(|_| bag.draw(&mut rng, |_| true).unwrap())
+            .collect();
+        first_round.sort();
+        assert_eq!(first_round, vec!["a", "b", "c", "d", "e"]);
+        // Next draw starts a fresh round.
+        assert!(bag.draw(&mut rng, |_| true).is_some());
+        assert_eq!(bag.drawn.len(), 1);
+    }
+
+    #[test]
+    fn skips_ineligible_without_consuming_them() {
+        let ids = ["land1", "port1", "land2"];
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut bag = ShuffleBag::default();
+        bag.sync(&ids, &mut rng);
+        let a = bag.draw(&mut rng, |id| id.starts_with("land")).unwrap();
+        let b = bag.draw(&mut rng, |id| id.starts_with("land")).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(
+            bag.draw(&mut rng, |id| id.starts_with("port")).unwrap(),
+            "port1"
+        );
+    }
+
+    #[test]
+    fn sync_handles_changed_listing() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut bag = ShuffleBag::default();
+        bag.sync(&["a", "b", "c"], &mut rng);
+        let first = bag.draw(&mut rng, |_| true).unwrap();
+        // Re-list: the drawn id and one other vanish, a new id appears.
+        let survivor = ["a", "b", "c"].into_iter().find(|id| *id != first).unwrap();
+        bag.sync(&[survivor, "z"], &mut rng);
+        let mut rest = vec![
+            bag.draw(&mut rng, |_| true).unwrap(),
+            bag.draw(&mut rng, |_| true).unwrap(),
+        ];
+        rest.sort();
+        let mut expected = vec![survivor.to_string(), "z".to_string()];
+        expected.sort();
+        assert_eq!(rest, expected);
+    }
+
+    #[tokio::test]
+    async fn store_resumes_round_from_disk() {
+        let source = format!("shuffle_test_{}", std::process::id());
+        let ids = ["a", "b", "c"];
+        let store = ShuffleBagStore::new(&source);
+        let first = store.draw("frame", &ids, |_| true).await.unwrap();
+        // The bag is written in the background.
+        for _ in 0..100 {
+            if store.path("frame").exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        // A fresh store (as after a restart) continues the round without repeating.
+        let reloaded = ShuffleBagStore::new(&source);
+        let mut rest = vec![
+            reloaded.draw("frame", &ids, |_| true).await.unwrap(),
+            reloaded.draw("frame", &ids, |_| true).await.unwrap(),
+        ];
+        rest.push(first);
+        rest.sort();
+        assert_eq!(rest, ["a", "b", "c"]);
+        // Let the last background write land before cleaning up.
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let _ = std::fs::remove_file(store.path("frame"));
+    }
+}
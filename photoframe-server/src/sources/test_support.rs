@@ -0,0 +1,53 @@
+//! Fixtures shared by the source tests: small encoded images and local mock servers.
+
+use std::net::SocketAddr;
+
+/// Serve `app` on an ephemeral local port for the rest of the test.
+pub async fn serve(app: axum::Router) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+/// A black `w`x`h` PNG.
+pub fn png(w: u32, h: u32) -> Vec<u8> {
+    let mut png = Vec::new();
+    image::DynamicImage::new_rgb8(w, h)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+/// A TIFF-structured EXIF blob holding `fields`.
+pub fn exif_blob(fields: &[exif::Field]) -> Vec<u8> {
+    let mut writer = exif::experimental::Writer::new();
+    for f in fields {
+        writer.push_field(f);
+    }
+    let mut tiff = std::io::Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).unwrap();
+    tiff.into_inner()
+}
+
+/// A black `w`x`h` JPEG with `tiff` spliced in as its APP1 Exif segment.
+pub fn jpeg_with_exif(w: u32, h: u32, tiff: &[u8]) -> Vec<u8> {
+    encode_jpeg_with_exif(&image::DynamicImage::new_rgb8(w, h), tiff)
+}
+
+/// `img` encoded as JPEG with `tiff` spliced in as its APP1 Exif segment.
+pub fn encode_jpeg_with_exif(img: &image::DynamicImage, tiff: &[u8]) -> Vec<u8> {
+    let mut jpeg = Vec::new();
+    img.write_to(
+        &mut std::io::Cursor::new(&mut jpeg),
+        image::ImageFormat::Jpeg,
+    )
+    .unwrap();
+    let mut file = jpeg[..2].to_vec();
+    file.extend_from_slice(&[0xFF, 0xE1]);
+    file.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+    file.extend_from_slice(b"Exif\0\0");
+    file.extend_from_slice(tiff);
+    file.extend_from_slice(&jpeg[2..]);
+    file
+}
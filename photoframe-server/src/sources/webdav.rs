@@ -0,0 +1,648 @@
+//! WebDAV source (Nextcloud, ownCloud, Apache mod_dav, ...). The collection is walked
+//! with `Depth: 1` PROPFIND requests down to `depth` levels, since many servers refuse
+//! `Depth: infinity`. Orientation and capture date of new or changed files are read
+//! from the first bytes of each file (a Range request) and cached by ETag, together
+//! with the listing, in `<source_id>_webdav_cache.json`.
+//!
+//! Files are downloaded on selection. The most recent downloads are kept in
+//! `<source_id>_webdav_files/` next to their ETag and revalidated with
+//! `If-None-Match`, so a file shown again is only transferred if it changed.
+
+use super::remote::{self, Download, DownloadCache, IncludeFilter, RemoteFile};
+use super::{
+    AssetPage, AssetSummary, ImageMeta, ImageSource, ListQuery, SelectionContext, ShuffleBagStore,
+    SourceHealth, SourceStats,
+};
+use crate::config::{Orientation, WebdavSource};
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use chrono::DateTime;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default levels of sub-collections listed below the configured URL.
+const DEFAULT_DEPTH: u32 = 3;
+/// Default seconds between re-listings.
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 3_600;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getetag/><d:getcontenttype/></d:prop></d:propfind>"#;
+
+/// One `<response>` of a PROPFIND multistatus.
+#[derive(Debug, Clone, PartialEq)]
+struct DavEntry {
+    url: reqwest::Url,
+    collection: bool,
+    etag: Option<String>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+pub struct WebdavImageSource {
+    cfg: WebdavSource,
+    /// Collection URL, always with a trailing slash.
+    root: Option<reqwest::Url>,
+    include: IncludeFilter,
+    entries: parking_lot::RwLock<Vec<RemoteFile>>,
+    last_list: AtomicU64,  // unix seconds of last listing, 0 = never
+    cursor: AtomicUsize,   // for sequential order
+    bags: ShuffleBagStore, // for shuffle order
+    cache_path: PathBuf,
+    downloads: DownloadCache,
+    client: reqwest::Client,
+    // Serializes listings so concurrent callers don't walk the tree twice.
+    sync_lock: tokio::sync::Mutex<()>,
+    /// Why the last listing failed, reported through `health`.
+    last_error: parking_lot::Mutex<Option<String>>,
+}
+
+impl WebdavImageSource {
+    pub fn new(id: &str, cfg: &WebdavSource) -> Result<Self> {
+        Self::in_dir(id, cfg, Path::new(""))
+    }
+
+    /// Like [`Self::new`], keeping the listing cache and downloads under `dir`.
+    fn in_dir(id: &str, cfg: &WebdavSource, dir: &Path) -> Result<Self> {
+        let root = cfg
+            .url
+            .as_deref()
+            .map(|url| {
+                let mut root = reqwest::Url::parse(url)
+                    .with_context(|| format!("invalid webdav url {url}"))?;
+                if !root.path().ends_with('/') {
+                    root.set_path(&format!("{}/", root.path()));
+                }
+                anyhow::Ok(root)
+            })
+            .transpose()?;
+        let source = Self {
+            cfg: cfg.clone(),
+            root,
+            include: IncludeFilter::new(&cfg.include)?,
+            entries: parking_lot::RwLock::new(Vec::new()),
+            last_list: AtomicU64::new(0),
+            cursor: AtomicUsize::new(0),
+            bags: ShuffleBagStore::new(id),
+            cache_path: dir.join(format!("{id}_webdav_cache.json")),
+            downloads: DownloadCache::new(dir.join(format!("{id}_webdav_files"))),
+            client: reqwest::Client::new(),
+            sync_lock: tokio::sync::Mutex::new(()),
+            last_error: parking_lot::Mutex::new(None),
+        };
+        // Restored so a restart only re-probes changed files; still re-listed on first use.
+        if let Some(files) = remote::load_listing(&source.cache_path, &source.origin()) {
+            *source.entries.write() = files;
+        }
+        Ok(source)
+    }
+
+    /// Identifies the listing in the cache file.
+    fn origin(&self) -> String {
+        self.root
+            .as_ref()
+            .map(|u| u.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Request carrying the configured credentials.
+    fn request(&self, method: reqwest::Method, url: reqwest::Url) -> reqwest::RequestBuilder {
+        let req = self.client.request(method, url);
+        match &self.cfg.username {
+            Some(user) => req.basic_auth(user, self.cfg.password.as_ref()),
+            None => req,
+        }
+    }
+
+    async fn propfind(&self, url: &reqwest::Url, depth: &str) -> Result<Vec<DavEntry>> {
+        let resp = self
+            .request(
+                reqwest::Method::from_bytes(b"PROPFIND").expect("valid method"),
+                url.clone(),
+            )
+            .header("Depth", depth)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/xml; charset=utf-8",
+            )
+            .body(PROPFIND_BODY)
+            .send()
+            .await
+            .with_context(|| format!("webdav PROPFIND {url}"))?;
+        if !resp.status().is_success() {
+            bail!("webdav PROPFIND {url} failed: {}", resp.status());
+        }
+        let body = resp.text().await?;
+        parse_multistatus(url, &body)
+    }
+
+    /// Path of a file relative to the collection, percent-decoded.
+    fn relative_id(&self, root: &reqwest::Url, url: &reqwest::Url) -> Option<String> {
+        let rel = url.path().strip_prefix(root.path())?;
+        Some(
+            percent_encoding::percent_decode_str(rel)
+                .decode_utf8_lossy()
+                .into_owned(),
+        )
+    }
+
+    /// All included files below `root`, descending at most `depth` levels into
+    /// sub-collections.
+    async fn walk(&self, root: &reqwest::Url) -> Result<Vec<RemoteFile>> {
+        let max_depth = self.cfg.depth.unwrap_or(DEFAULT_DEPTH);
+        let mut files = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![(root.clone(), 0u32)];
+        while let Some((dir, level)) = pending.pop() {
+            if !visited.insert(dir.path().to_string()) {
+                continue;
+            }
+            for entry in self.propfind(&dir, "1").await? {
+                // The collection itself is part of its own listing.
+                if entry.url.path().trim_end_matches('/') == dir.path().trim_end_matches('/') {
+                    continue;
+                }
+                let Some(id) = self.relative_id(root, &entry.url) else {
+                    continue; // outside the configured collection
+                };
+                if entry.collection {
+                    if level < max_depth {
+                        let mut sub = entry.url;
+                        if !sub.path().ends_with('/') {
+                            sub.set_path(&format!("{}/", sub.path()));
+                        }
+                        pending.push((sub, level + 1));
+                    }
+                } else if self.include.matches(&id) {
+                    files.push(RemoteFile::listed(id, entry.url.to_string(), entry.etag));
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    async fn list_if_needed(&self) -> Result<()> {
+        let result = self.sync_if_needed().await;
+        *self.last_error.lock() = result.as_ref().err().map(|e| format!("{e:#}"));
+        result
+    }
+
+    async fn sync_if_needed(&self) -> Result<()> {
+        let Some(root) = &self.root else {
+            return Ok(());
+        };
+        let _guard = self.sync_lock.lock().await;
+        let now = unix_now();
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let interval = self
+            .cfg
+            .refresh_interval_secs
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL_SECS);
+        if last != 0 && now.saturating_sub(last) < interval {
+            return Ok(());
+        }
+
+        let listed = self.walk(root).await?;
+        let (mut files, changed) = remote::reuse_unchanged(&self.entries.read(), listed);
+        let probed = changed.len();
+        files.extend(
+            remote::probe_all(changed, |f| {
+                let url = reqwest::Url::parse(&f.url).ok()?;
+                Some(self.request(reqwest::Method::GET, url))
+            })
+            .await,
+        );
+        files.sort_by(|a, b| a.id.cmp(&b.id));
+        tracing::info!(url = %root, total = files.len(), probed, "Listed webdav collection");
+
+        if let Err(e) = remote::save_listing(&self.cache_path, &self.origin(), &files).await {
+            tracing::warn!(error=%e, "failed to persist webdav cache");
+        }
+        *self.entries.write() = files;
+        self.last_list.store(now, AtomicOrdering::Relaxed);
+        Ok(())
+    }
+
+    /// Download a file, reusing the kept copy when it is unchanged.
+    async fn fetch(&self, file: &RemoteFile) -> Result<Option<ImageMeta>> {
+        let req = self.request(reqwest::Method::GET, reqwest::Url::parse(&file.url)?);
+        match self.downloads.fetch(file, req).await? {
+            Download::Bytes(bytes) => Ok(Some(file.image_meta(bytes))),
+            Download::Gone => {
+                tracing::debug!(asset_id = %file.id, "webdav file gone; removing from cache");
+                self.remove_asset_from_cache(&file.id)?;
+                Ok(None)
+            }
+            Download::Failed => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl ImageSource for WebdavImageSource {
+    async fn next(&self, ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        if let Err(e) = self.list_if_needed().await {
+            tracing::warn!(error = %format!("{e:#}"), "webdav listing failed");
+        }
+        let snapshot: Vec<RemoteFile> = { self.entries.read().clone() };
+        let items: Vec<(&str, Orientation)> = snapshot
+            .iter()
+            .map(|f| (f.id.as_str(), f.orientation()))
+            .collect();
+        let order = self.cfg.order.unwrap_or_default();
+        remote::pick_by_order(order, &items, ctx, &self.cursor, &self.bags, |i| {
+            self.fetch(&snapshot[i])
+        })
+        .await
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        // force next call to list to actually list now
+        self.last_list.store(0, AtomicOrdering::Relaxed);
+        self.list_if_needed().await
+    }
+
+    async fn list(&self, query: &ListQuery) -> Result<AssetPage> {
+        self.list_if_needed().await?;
+        let all: Vec<AssetSummary> = self
+            .entries
+            .read()
+            .iter()
+            .map(|f| AssetSummary {
+                checksum: None,
+                id: f.id.clone(),
+                orientation: f.orientation(),
+                date_taken: f.date_taken,
+            })
+            .collect();
+        Ok(AssetPage::from_summaries(all, query))
+    }
+
+    async fn get(&self, id: &str, _ctx: &SelectionContext) -> Result<Option<ImageMeta>> {
+        let file = self.entries.read().iter().find(|f| f.id == id).cloned();
+        match file {
+            Some(file) => self.fetch(&file).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn health(&self) -> SourceHealth {
+        let last = self.last_list.load(AtomicOrdering::Relaxed);
+        let last_refresh = (last > 0)
+            .then(|| DateTime::from_timestamp(last as i64, 0))
+            .flatten();
+        let Some(root) = &self.root else {
+            return SourceHealth::unhealthy("url not configured", last_refresh);
+        };
+        if let Some(error) = self.last_error.lock().clone() {
+            return SourceHealth::unhealthy(format!("listing failed: {error}"), last_refresh);
+        }
+        match self.propfind(root, "0").await {
+            Ok(_) => SourceHealth::healthy(last_refresh),
+            Err(e) => SourceHealth::unhealthy(format!("{e:#}"), last_refresh),
+        }
+    }
+
+    fn stats(&self) -> SourceStats {
+        SourceStats::from_orientations(self.entries.read().iter().map(|f| f.orientation()))
+    }
+
+    fn serves_originals(&self) -> bool {
+        true
+    }
+
+    fn remove_asset_from_cache(&self, asset_id: &str) -> Result<bool> {
+        let mut entries = self.entries.write();
+        let before = entries.len();
+        entries.retain(|f| f.id != asset_id);
+        Ok(before != entries.len())
+    }
+}
+
+/// Parse a PROPFIND multistatus body. Hrefs are resolved against `base`; properties
+/// are only taken from `200` propstats.
+fn parse_multistatus(base: &reqwest::Url, xml: &str) -> Result<Vec<DavEntry>> {
+    let doc = roxmltree::Document::parse(xml).context("parsing PROPFIND response")?;
+    let dav = |n: &roxmltree::Node, name: &str| {
+        n.is_element() && n.tag_name().name() == name && n.tag_name().namespace() == Some("DAV:")
+    };
+    let mut entries = Vec::new();
+    for response in doc.descendants().filter(|n| dav(n, "response")) {
+        let Some(href) = response
+            .children()
+            .find(|n| dav(n, "href"))
+            .and_then(|n| n.text())
+        else {
+            continue;
+        };
+        let Ok(url) = base.join(href.trim()) else {
+            continue;
+        };
+        let mut entry = DavEntry {
+            url,
+            collection: false,
+            etag: None,
+        };
+        for propstat in response.children().filter(|n| dav(n, "propstat")) {
+            let ok = propstat
+                .children()
+                .find(|n| dav(n, "status"))
+                .and_then(|n| n.text())
+                .is_none_or(|s| s.contains(" 200 "));
+            if !ok {
+                continue;
+            }
+            for prop in propstat
+                .children()
+                .filter(|n| dav(n, "prop"))
+                .flat_map(|p| p.children())
+            {
+                if dav(&prop, "resourcetype") {
+                    entry.collection = prop.children().any(|n| dav(&n, "collection"));
+                } else if dav(&prop, "getetag") {
+                    entry.etag = prop
+                        .text()
+                        .map(str::trim)
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string);
+                }
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Orientation;
+    use crate::sources::{SourceData, test_support};
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, Method, StatusCode, Uri, header};
+    use axum::response::Response;
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct MockDav {
+        /// Decoded path -> (bytes, etag).
+        files: HashMap<String, (Vec<u8>, String)>,
+        requests: Vec<Logged>,
+    }
+
+    struct Logged {
+        method: String,
+        path: String,
+        range: Option<String>,
+        if_none_match: Option<String>,
+        status: u16,
+    }
+
+    type Shared = Arc<parking_lot::Mutex<MockDav>>;
+
+    /// Minimal WebDAV server: `Depth: 1` PROPFIND on collections, GET with Range and
+    /// If-None-Match, HTTP basic auth as alice/secret.
+    async fn dav(
+        State(state): State<Shared>,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+    ) -> Response {
+        let path = percent_encoding::percent_decode_str(uri.path())
+            .decode_utf8_lossy()
+            .into_owned();
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &header::HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let mut state = state.lock();
+        let (status, mut response) = if header(header::AUTHORIZATION.as_str()).as_deref()
+            != Some("Basic YWxpY2U6c2VjcmV0")
+        {
+            (StatusCode::UNAUTHORIZED, Response::new(Body::empty()))
+        } else if method.as_str() == "PROPFIND" {
+            let dir = path.trim_end_matches('/').to_string() + "/";
+            let mut children = HashSet::new();
+            let mut responses = format!(
+                "<d:response><d:href>{dir}</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+            );
+            for (file, (_, etag)) in &state.files {
+                let Some(rest) = file.strip_prefix(&dir) else {
+                    continue;
+                };
+                let href =
+                    percent_encoding::utf8_percent_encode(file, percent_encoding::NON_ALPHANUMERIC)
+                        .to_string()
+                        .replace("%2F", "/")
+                        .replace("%2E", ".");
+                let entry = match rest.split_once('/') {
+                    Some((sub, _)) if children.insert(sub.to_string()) => format!(
+                        "<d:response><d:href>{dir}{sub}/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+                    ),
+                    Some(_) => continue,
+                    None => format!(
+                        "<d:response><d:href>{href}</d:href><d:propstat><d:prop><d:resourcetype/><d:getetag>{etag}</d:getetag></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+                    ),
+                };
+                responses.push_str(&entry);
+            }
+            let body = format!(
+                r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:">{responses}</d:multistatus>"#
+            );
+            (StatusCode::MULTI_STATUS, Response::new(Body::from(body)))
+        } else if let Some((bytes, etag)) = state.files.get(&path) {
+            if header(header::IF_NONE_MATCH.as_str()).as_ref() == Some(etag) {
+                (StatusCode::NOT_MODIFIED, Response::new(Body::empty()))
+            } else {
+                let (status, body) = match header(header::RANGE.as_str())
+                    .and_then(|r| r.strip_prefix("bytes=0-")?.parse::<usize>().ok())
+                {
+                    Some(end) => (
+                        StatusCode::PARTIAL_CONTENT,
+                        bytes[..=end.min(bytes.len() - 1)].to_vec(),
+                    ),
+                    None => (StatusCode::OK, bytes.clone()),
+                };
+                let resp = Response::builder()
+                    .header(header::ETAG, etag.as_str())
+                    .body(Body::from(body))
+                    .unwrap();
+                (status, resp)
+            }
+        } else {
+            (StatusCode::NOT_FOUND, Response::new(Body::empty()))
+        };
+        *response.status_mut() = status;
+        state.requests.push(Logged {
+            method: method.to_string(),
+            path,
+            range: header(header::RANGE.as_str()),
+            if_none_match: header(header::IF_NONE_MATCH.as_str()),
+            status: status.as_u16(),
+        });
+        response
+    }
+
+    fn date() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2022-07-08T09:10:11Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// A mock server holding a few files under `/dav/photos`, and a source for it that
+    /// keeps its state in `dir`.
+    async fn mock_source(dir: &std::path::Path) -> (Shared, WebdavImageSource) {
+        let exif = test_support::exif_blob(&[
+            exif::Field {
+                tag: exif::Tag::Orientation,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Short(vec![6]),
+            },
+            exif::Field {
+                tag: exif::Tag::DateTimeOriginal,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Ascii(vec![
+                    date().format("%Y:%m:%d %H:%M:%S").to_string().into_bytes(),
+                ]),
+            },
+            exif::Field {
+                tag: exif::Tag::OffsetTimeOriginal,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Ascii(vec![b"+00:00".to_vec()]),
+            },
+        ]);
+        let state: Shared = Arc::default();
+        {
+            let mut s = state.lock();
+            // Stored landscape, rotated to portrait by EXIF orientation 6.
+            s.files.insert(
+                "/dav/photos/a.jpg".into(),
+                (test_support::jpeg_with_exif(40, 20, &exif), "\"a1\"".into()),
+            );
+            s.files.insert(
+                "/dav/photos/2024/b b.PNG".into(),
+                (test_support::png(30, 20), "\"b1\"".into()),
+            );
+            s.files.insert(
+                "/dav/photos/2024/deep/c.png".into(),
+                (test_support::png(30, 20), "\"c1\"".into()),
+            );
+            s.files.insert(
+                "/dav/photos/notes.txt".into(),
+                (b"hi".to_vec(), "\"n1\"".into()),
+            );
+        }
+        let app = axum::Router::new().fallback(dav).with_state(state.clone());
+        let addr = test_support::serve(app).await;
+        let cfg = WebdavSource {
+            url: Some(format!("http://{addr}/dav/photos")),
+            username: Some("alice".into()),
+            password: Some("secret".into()),
+            depth: Some(1),
+            ..Default::default()
+        };
+        let source = WebdavImageSource::in_dir("test_webdav", &cfg, dir).unwrap();
+        (state, source)
+    }
+
+    /// GET requests logged since the last call.
+    fn gets(state: &Shared) -> Vec<Logged> {
+        state
+            .lock()
+            .requests
+            .drain(..)
+            .filter(|r| r.method == "GET")
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn lists_to_depth_and_probes_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let (state, source) = mock_source(dir.path()).await;
+
+        // Depth 1 skips `deep/`; the default include skips `notes.txt`.
+        let page = source
+            .list(&ListQuery {
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let listed: Vec<(&str, Orientation, Option<DateTime<Utc>>)> = page
+            .items
+            .iter()
+            .map(|a| (a.id.as_str(), a.orientation, a.date_taken))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("2024/b b.PNG", Orientation::Landscape, None),
+                ("a.jpg", Orientation::Portrait, Some(date())),
+            ]
+        );
+        let probes = gets(&state);
+        assert_eq!(probes.len(), 2);
+        assert!(
+            probes
+                .iter()
+                .all(|r| r.range.as_deref() == Some("bytes=0-65535"))
+        );
+    }
+
+    #[tokio::test]
+    async fn reprobes_only_changed_etags() {
+        let dir = tempfile::tempdir().unwrap();
+        let (state, source) = mock_source(dir.path()).await;
+        source.refresh().await.unwrap();
+        gets(&state);
+
+        state
+            .lock()
+            .files
+            .get_mut("/dav/photos/2024/b b.PNG")
+            .unwrap()
+            .1 = "\"b2\"".into();
+        source.refresh().await.unwrap();
+        let probes = gets(&state);
+        assert_eq!(probes.len(), 1);
+        assert_eq!(probes[0].path, "/dav/photos/2024/b b.PNG");
+
+        // A restarted source restores the probed listing from its cache file.
+        let restored = WebdavImageSource::in_dir("test_webdav", &source.cfg, dir.path()).unwrap();
+        restored.refresh().await.unwrap();
+        assert!(gets(&state).is_empty());
+    }
+
+    #[tokio::test]
+    async fn revalidates_kept_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let (state, source) = mock_source(dir.path()).await;
+        source.refresh().await.unwrap();
+        gets(&state);
+
+        let ctx = SelectionContext::default();
+        let first = source.get("a.jpg", &ctx).await.unwrap().unwrap();
+        let second = source.get("a.jpg", &ctx).await.unwrap().unwrap();
+        let downloads = gets(&state);
+        assert_eq!(downloads[0].status, 200);
+        assert_eq!(downloads[1].if_none_match.as_deref(), Some("\"a1\""));
+        assert_eq!(downloads[1].status, 304);
+        match (first.data, second.data) {
+            (SourceData::Bytes(a), SourceData::Bytes(b)) => assert_eq!(a, b),
+            _ => panic!("expected bytes"),
+        }
+        assert_eq!(second.orientation, Orientation::Portrait);
+        assert!(source.downloads.dir().starts_with(dir.path()));
+    }
+}